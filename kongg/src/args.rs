@@ -0,0 +1,19 @@
+use clap::{Parser, ValueHint};
+use kongg_shared::utils::cli::DocCommand;
+
+/// Watch directories and record what happens to their files.
+#[derive(Debug, Parser)]
+#[command(
+    name = "kongg",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    /// Directories to watch, recursively.
+    #[arg(required = true, value_hint = ValueHint::DirPath)]
+    pub paths: Vec<String>,
+
+    #[command(subcommand)]
+    pub command: Option<DocCommand>,
+}
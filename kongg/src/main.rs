@@ -1,28 +1,43 @@
-use futures::{
-    SinkExt, StreamExt,
-    channel::mpsc::{Receiver, channel},
-};
+use clap::{CommandFactory, Parser};
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use kongg_shared::{
-    helpers::{crud::create_file, surreal_init::init},
-    models::{
-        event::{Event as KEvent, EventType},
-        file::File,
-    },
+    config::{Config as KConfig, RetentionConfig},
+    models::{event::EventType, file::File},
+    store::{Store, open_with_config},
+    utils::cli::complete,
 };
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
+use tokio::sync::mpsc::{Receiver, Sender, channel, error::TrySendError};
 use tokio::task;
 use tokio::time::{Duration, sleep};
 
+use crate::{
+    args::Cli,
+    maintenance::spawn_maintenance,
+    metrics::Metrics,
+    queue::{EVENT_QUEUE_CAPACITY, WRITE_QUEUE_CAPACITY, spawn_writer},
+    rescan::{RescanRequests, spawn_rescanner},
+    stream::spawn_event_stream,
+};
+
+mod args;
+mod maintenance;
+mod metrics;
+mod queue;
+mod rescan;
+mod stream;
+
 //cargo run .
 
 //const IGNORED_PATHS: &[&str] = &[".git"];
 
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
 fn build_ignore_list() -> GlobSet {
     let mut builder = GlobSetBuilder::new();
     let patterns = vec![
@@ -40,188 +55,279 @@ fn build_ignore_list() -> GlobSet {
     builder.build().expect("Failed to build globset")
 }
 
+/// Locks state shared between the watcher's tasks. Every holder leaves it
+/// whole at each step, so a panic elsewhere takes the lock back rather than
+/// poisoning the watcher.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 //Todo* //Note: copy and paste returns a `Create event` while cut/move and pase return a `Rename event` with the 1st = old location and 2nd = new location
 
 #[tokio::main]
 async fn main() {
-    let e = KEvent::new(EventType::Created, "aab".to_string());
-    println!("Hello, world ==> !, {:?}", e);
+    complete(Cli::command);
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        if let Err(e) = command.run(Cli::command()) {
+            println!("error: {}", e);
+        }
+        return;
+    }
 
-    let path = std::env::args()
-        .nth(1)
-        .expect("Argument 1 needs to be a path");
-    println!("watching {}", path);
+    let paths = cli.paths;
+    println!("watching {:?}", paths);
 
-    futures::executor::block_on(async {
-        if let Err(e) = async_watch(path).await {
-            println!("error: {:?}", e)
+    let config = match KConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
         }
-    });
+    };
+    let store = match open_with_config(&config).await {
+        Ok(store) => store,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = async_watch(store, paths, config.retention).await {
+        println!("error: {:?}", e)
+    }
 }
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
-    let (mut tx, rx) = channel(1);
+/// The callback runs on notify's thread, so it must never block: events go
+/// into a bounded channel and anything that doesn't fit is counted as
+/// dropped and its root scheduled for a rescan.
+fn async_watcher(
+    metrics: Arc<Metrics>,
+    rescans: RescanRequests,
+) -> notify::Result<(
+    RecommendedWatcher,
+    Sender<notify::Result<Event>>,
+    Receiver<notify::Result<Event>>,
+)> {
+    let (tx, rx) = channel(EVENT_QUEUE_CAPACITY);
+    let callback_tx = tx.clone();
 
     let watcher = RecommendedWatcher::new(
-        move |res| {
-            futures::executor::block_on(async {
-                tx.send(res).await.unwrap();
-            })
+        move |res: notify::Result<Event>| {
+            Metrics::incr(&metrics.captured_events);
+            if let Err(TrySendError::Full(res)) = callback_tx.try_send(res) {
+                Metrics::incr(&metrics.dropped_events);
+                match res {
+                    Ok(event) => rescans.request(&event.paths),
+                    Err(e) => rescans.request(&e.paths),
+                }
+            }
         },
         Config::default(),
     )?;
 
-    Ok((watcher, rx))
+    Ok((watcher, tx, rx))
+}
+
+fn spawn_status_reporter(
+    store: Arc<dyn Store>,
+    roots: Vec<String>,
+    metrics: Arc<Metrics>,
+    events: Sender<notify::Result<Event>>,
+    queue: Sender<File>,
+) {
+    task::spawn(async move {
+        let mut last_dropped = 0;
+        loop {
+            sleep(STATUS_INTERVAL).await;
+            let status = metrics.snapshot(
+                &roots,
+                queue.max_capacity() - queue.capacity(),
+                queue.max_capacity(),
+                events.max_capacity() - events.capacity(),
+            );
+            if status.dropped_events > last_dropped {
+                println!(
+                    "⚠️ dropped {} events so far (queue {}/{})",
+                    status.dropped_events, status.queue_depth, status.queue_capacity
+                );
+                last_dropped = status.dropped_events;
+            }
+            if let Err(e) = store.save_status(status).await {
+                println!("failed to save status: {}", e);
+            }
+        }
+    });
 }
 
-async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
-    let db = init().await.unwrap();
-    let (mut watcher, mut rx) = async_watcher()?;
-    let ignore_list = build_ignore_list(); // Load ignore patterns
+async fn async_watch(
+    store: Arc<dyn Store>,
+    paths: Vec<String>,
+    retention: RetentionConfig,
+) -> notify::Result<()> {
+    let ignore_list = Arc::new(build_ignore_list()); // Load ignore patterns
+    let metrics = Arc::new(Metrics::default());
+
+    let roots = paths
+        .iter()
+        .map(|p| {
+            Path::new(p)
+                .canonicalize()
+                .map_err(|e| notify::Error::io(e).add_path(PathBuf::from(p)))
+        })
+        .collect::<notify::Result<Vec<PathBuf>>>()?;
+    let root_names: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+    let rescans = RescanRequests::new(roots.clone());
+
+    let (mut watcher, events_tx, mut rx) = async_watcher(Arc::clone(&metrics), rescans.clone())?;
+    let (queue, queue_rx) = channel::<File>(WRITE_QUEUE_CAPACITY);
+    spawn_writer(
+        Arc::clone(&store),
+        Arc::new(roots.clone()),
+        queue_rx,
+        Arc::clone(&metrics),
+        spawn_event_stream(),
+    );
+    spawn_rescanner(
+        Arc::clone(&store),
+        rescans.clone(),
+        Arc::clone(&ignore_list),
+        queue.clone(),
+        Arc::clone(&metrics),
+    );
+    spawn_maintenance(Arc::clone(&store), retention, Arc::clone(&metrics));
+    spawn_status_reporter(
+        Arc::clone(&store),
+        root_names,
+        Arc::clone(&metrics),
+        events_tx,
+        queue.clone(),
+    );
 
     // Add a path to be watched. All files and directories at that path and
     // below will be monitored for changes.
-    watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
+    for root in &roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
     let recent_events = Arc::new(Mutex::new(HashMap::<PathBuf, bool>::new()));
 
-    while let Some(res) = rx.next().await {
+    while let Some(res) = rx.recv().await {
         match res {
             Ok(event) => {
-                let mut events = recent_events.lock().unwrap();
+                if event.need_rescan() {
+                    // The kernel queue overflowed and events were lost
+                    Metrics::incr(&metrics.overflows);
+                    rescans.request(&event.paths);
+                    continue;
+                }
+
                 if event.paths.iter().any(|p| ignore_list.is_match(p)) {
                     continue; // Skip ignored patterns
                 }
 
                 match event.kind {
-                    notify::EventKind::Remove(remove_kind) => {
-                        // create_file(
-                        //     &db,
-                        //     File::new(
-                        //         EventType::Deleted,
-                        //         event.paths[0].to_string_lossy().to_string(),
-                        //     ),
-                        // )
-                        // .await;
-                        println!("removed ${:?}", remove_kind);
+                    notify::EventKind::Remove(_) => {
+                        for path in &event.paths {
+                            let _ = queue
+                                .send(File::missing(
+                                    EventType::Deleted,
+                                    path.to_string_lossy().to_string(),
+                                ))
+                                .await;
+                        }
                     }
-                    notify::EventKind::Create(create_kind) => match create_kind {
-                        notify::event::CreateKind::File | notify::event::CreateKind::Folder => {
-                            create_file(
-                                &db,
-                                File::new(
-                                    EventType::Created,
-                                    event.paths[0].to_string_lossy().to_string(),
-                                ),
-                            )
-                            .await;
-                            println!("created =>: {:?}", event.paths)
+                    notify::EventKind::Create(
+                        notify::event::CreateKind::File | notify::event::CreateKind::Folder,
+                    ) => {
+                        match File::new(
+                            EventType::Created,
+                            event.paths[0].to_string_lossy().to_string(),
+                        ) {
+                            Ok(file) => {
+                                let _ = queue.send(file).await;
+                            }
+                            Err(e) => {
+                                Metrics::incr(&metrics.failed_events);
+                                println!("skipping created event: {}", e)
+                            }
                         }
-                        _ => (),
-                    },
-                    notify::EventKind::Modify(modify_kind) => {
-                        match modify_kind {
-                            notify::event::ModifyKind::Name(_rename_mode) => {
-                                let from = event.paths.get(0);
-                                let to = event.paths.get(1);
-                                match (from, to) {
-                                    (Some(old_path), None) => {
-                                        println!(
-                                            "-------------------- 1 ======={:?}/ {:?}",
-                                            modify_kind, old_path
-                                        );
-                                        let old_path_clone = old_path.clone();
-                                        events.insert(old_path_clone.clone(), true);
-
-                                        // 🕒 Wait before confirming deletion
-                                        let events_clone = Arc::clone(&recent_events);
-                                        task::spawn({
-                                            let db = db.clone();
-                                            async move {
-                                                sleep(Duration::from_millis(100)).await;
-                                                let should_create_file = {
-                                                    let mut events = events_clone.lock().unwrap();
-                                                    events.remove(&old_path_clone).is_some()
-                                                };
-                                                if should_create_file {
-                                                    create_file(
-                                                        &db,
-                                                        File::new(
-                                                            EventType::Deleted,
-                                                            event.paths[0]
-                                                                .to_string_lossy()
-                                                                .to_string(),
-                                                        ),
-                                                    )
-                                                    .await;
-                                                    // println!(
-                                                    //     "✅ File Deleted: {:?}",
-                                                    //     old_path_clone
-                                                    // );
-                                                }
-                                            }
-                                        });
-                                    }
-                                    (Some(old_path), Some(new_path)) => {
-                                        // 🔄 Confirm Rename (Remove from deletion tracking)
-
-                                        let old_file_name = old_path
-                                            .to_string_lossy()
-                                            .to_string()
-                                            .rsplit(['/', '\\'])
-                                            .next()
-                                            .map(|s| s.to_string());
-
-                                        let new_file_name = Path::new(&new_path)
-                                            .canonicalize()
-                                            .ok()
-                                            .and_then(|p| {
-                                                p.file_name()
-                                                    .map(|f| f.to_string_lossy().to_string())
-                                            });
-
-                                        if old_file_name == new_file_name {
-                                            create_file(
-                                                &db,
-                                                File::new(
-                                                    EventType::Moved,
-                                                    new_path.to_string_lossy().to_string(),
-                                                )
-                                                .add_previous_path(
-                                                    old_path.to_string_lossy().to_string(),
-                                                ),
-                                            )
-                                            .await;
-                                        } else {
-                                            create_file(
-                                                &db,
-                                                File::new(
-                                                    EventType::Renamed,
-                                                    new_path.to_string_lossy().to_string(),
-                                                )
-                                                .add_previous_path(
-                                                    old_path.to_string_lossy().to_string(),
-                                                ),
-                                            )
-                                            .await;
+                    }
+                    notify::EventKind::Modify(notify::event::ModifyKind::Name(_rename_mode)) => {
+                        let from = event.paths.first();
+                        let to = event.paths.get(1);
+                        match (from, to) {
+                            (Some(old_path), None) => {
+                                let old_path_clone = old_path.clone();
+                                lock(&recent_events).insert(old_path_clone.clone(), true);
+
+                                // 🕒 Wait before confirming deletion
+                                let events_clone = Arc::clone(&recent_events);
+                                task::spawn({
+                                    let queue = queue.clone();
+                                    async move {
+                                        sleep(Duration::from_millis(100)).await;
+                                        let should_create_file =
+                                            lock(&events_clone).remove(&old_path_clone).is_some();
+                                        if should_create_file {
+                                            let _ = queue
+                                                .send(File::missing(
+                                                    EventType::Deleted,
+                                                    old_path_clone.to_string_lossy().to_string(),
+                                                ))
+                                                .await;
                                         }
-                                        events.remove(old_path);
-                                        events.remove(new_path);
+                                    }
+                                });
+                            }
+                            (Some(old_path), Some(new_path)) => {
+                                // 🔄 Confirm Rename (Remove from deletion tracking)
+
+                                let old_file_name = old_path
+                                    .to_string_lossy()
+                                    .to_string()
+                                    .rsplit(['/', '\\'])
+                                    .next()
+                                    .map(|s| s.to_string());
 
-                                        println!(
-                                            "📂 File Renamed: {:?} -> {:?}",
-                                            old_path, new_path
-                                        );
+                                let new_file_name =
+                                    Path::new(&new_path).canonicalize().ok().and_then(|p| {
+                                        p.file_name().map(|f| f.to_string_lossy().to_string())
+                                    });
+
+                                let event_type = if old_file_name == new_file_name {
+                                    EventType::Moved
+                                } else {
+                                    EventType::Renamed
+                                };
+                                match File::new(event_type, new_path.to_string_lossy().to_string())
+                                {
+                                    Ok(file) => {
+                                        let _ = queue
+                                            .send(file.add_previous_path(
+                                                old_path.to_string_lossy().to_string(),
+                                            ))
+                                            .await;
+                                    }
+                                    Err(e) => {
+                                        Metrics::incr(&metrics.failed_events);
+                                        println!("skipping rename event: {}", e)
                                     }
-                                    _ => {}
                                 }
+
+                                let mut events = lock(&recent_events);
+                                events.remove(old_path);
+                                events.remove(new_path);
                             }
-                            _ => (),
+                            _ => {}
                         }
                     }
                     _ => (),
                 }
             }
-            Err(e) => println!("watch error: {:?}", e),
+            Err(e) => {
+                Metrics::incr(&metrics.watch_errors);
+                println!("watch error: {:?}", e)
+            }
         }
     }
 
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use kongg_shared::{config::RetentionConfig, retention::prune, store::Store};
+use tokio::{
+    task,
+    time::{Duration, interval},
+};
+
+use crate::metrics::Metrics;
+
+/// Applies the retention rules at startup and then every
+/// `interval_hours`. Does nothing when no rule is configured.
+pub fn spawn_maintenance(store: Arc<dyn Store>, config: RetentionConfig, metrics: Arc<Metrics>) {
+    if config.max_age_days.is_empty()
+        && config.collapse_daily.is_empty()
+        && config.max_rows_per_root.is_none()
+    {
+        return;
+    }
+
+    task::spawn(async move {
+        let hours = config.interval_hours.max(1);
+        let mut ticker = interval(Duration::from_secs(hours * 60 * 60));
+        loop {
+            ticker.tick().await;
+            match prune(store.as_ref(), &config, false).await {
+                Ok(report) => {
+                    let removed = report.removed.len() as u64;
+                    if removed > 0 {
+                        println!("pruned {} of {} events", removed, report.scanned);
+                    }
+                    Metrics::add(&metrics.pruned_events, removed);
+                }
+                Err(e) => println!("failed to prune events: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::Utc;
+use kongg_shared::models::status::DaemonStatus;
+
+/// Counters shared between the notify callback, the event loop and the
+/// background tasks.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub captured_events: AtomicU64,
+    pub dropped_events: AtomicU64,
+    pub overflows: AtomicU64,
+    pub written_events: AtomicU64,
+    pub batches: AtomicU64,
+    pub rescans: AtomicU64,
+    pub failed_events: AtomicU64,
+    pub failed_writes: AtomicU64,
+    pub watch_errors: AtomicU64,
+    pub pruned_events: AtomicU64,
+}
+
+impl Metrics {
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(
+        &self,
+        roots: &[String],
+        queue_depth: usize,
+        queue_capacity: usize,
+        pending_events: usize,
+    ) -> DaemonStatus {
+        DaemonStatus {
+            roots: roots.to_vec(),
+            queue_depth,
+            queue_capacity,
+            pending_events,
+            captured_events: self.captured_events.load(Ordering::Relaxed),
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+            written_events: self.written_events.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            rescans: self.rescans.load(Ordering::Relaxed),
+            failed_events: self.failed_events.load(Ordering::Relaxed),
+            failed_writes: self.failed_writes.load(Ordering::Relaxed),
+            watch_errors: self.watch_errors.load(Ordering::Relaxed),
+            pruned_events: self.pruned_events.load(Ordering::Relaxed),
+            updated_at: Some(Utc::now()),
+        }
+    }
+}
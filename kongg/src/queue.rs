@@ -0,0 +1,90 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use kongg_shared::{models::file::File, store::Store};
+use tokio::{
+    sync::{broadcast, mpsc::Receiver},
+    task::{self, JoinHandle},
+    time::{Duration, timeout},
+};
+
+use crate::metrics::Metrics;
+
+/// Raw notify events buffered between the kernel-facing callback and the
+/// event loop. When this fills up events are dropped and the root rescanned.
+pub const EVENT_QUEUE_CAPACITY: usize = 4_096;
+/// Records buffered between the event loop and the database writer. When
+/// this fills up the event loop waits (backpressure) instead of dropping.
+pub const WRITE_QUEUE_CAPACITY: usize = 1_024;
+pub const BATCH_SIZE: usize = 256;
+/// How long the writer waits for a partial batch to fill up before flushing.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The most specific watch root containing `path`.
+pub fn root_of(roots: &[PathBuf], path: &str) -> Option<String> {
+    roots
+        .iter()
+        .filter(|root| Path::new(path).starts_with(root))
+        .max_by_key(|root| root.as_os_str().len())
+        .map(|root| root.display().to_string())
+}
+
+pub fn spawn_writer(
+    store: Arc<dyn Store>,
+    roots: Arc<Vec<PathBuf>>,
+    mut rx: Receiver<File>,
+    metrics: Arc<Metrics>,
+    stream: broadcast::Sender<File>,
+) -> JoinHandle<()> {
+    task::spawn(async move {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+        while rx.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+            if batch.len() < BATCH_SIZE {
+                let remaining = BATCH_SIZE - batch.len();
+                let _ = timeout(FLUSH_INTERVAL, rx.recv_many(&mut batch, remaining)).await;
+            }
+
+            let records: Vec<File> = std::mem::take(&mut batch)
+                .into_iter()
+                .map(|file| match root_of(&roots, file.path()) {
+                    Some(root) => file.with_root(root),
+                    None => file,
+                })
+                .collect();
+            let written = records.len() as u64;
+            match store.insert(records.clone()).await {
+                Ok(()) => {
+                    Metrics::add(&metrics.written_events, written);
+                    for record in records {
+                        // nobody following is fine
+                        let _ = stream.send(record);
+                    }
+                }
+                Err(e) => {
+                    // One bad record aborts the whole transaction, so retry
+                    // individually and only skip the ones that still fail.
+                    println!(
+                        "batch of {} events failed ({}), retrying one by one",
+                        written, e
+                    );
+                    for record in records {
+                        match store.insert(vec![record.clone()]).await {
+                            Ok(()) => {
+                                Metrics::incr(&metrics.written_events);
+                                let _ = stream.send(record);
+                            }
+                            Err(e) => {
+                                println!("failed to write event: {}", e);
+                                Metrics::incr(&metrics.failed_writes);
+                            }
+                        }
+                    }
+                }
+            }
+            Metrics::incr(&metrics.batches);
+        }
+    })
+}
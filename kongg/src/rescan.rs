@@ -0,0 +1,140 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use globset::GlobSet;
+use kongg_shared::{
+    models::{event::EventType, file::File},
+    store::Store,
+};
+use tokio::{
+    sync::mpsc::Sender,
+    task,
+    time::{Duration, sleep},
+};
+
+use crate::{lock, metrics::Metrics};
+
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Roots whose events were lost and need to be reconciled with the disk.
+#[derive(Debug, Clone)]
+pub struct RescanRequests {
+    roots: Arc<Vec<PathBuf>>,
+    pending: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl RescanRequests {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots: Arc::new(roots),
+            pending: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Marks the roots containing `paths`, or every root when the lost
+    /// event carried no path (kernel queue overflow).
+    pub fn request(&self, paths: &[PathBuf]) {
+        let mut pending = lock(&self.pending);
+        let affected: Vec<&PathBuf> = self
+            .roots
+            .iter()
+            .filter(|root| paths.iter().any(|p| p.starts_with(root)))
+            .collect();
+
+        if affected.is_empty() {
+            pending.extend(self.roots.iter().cloned());
+        } else {
+            pending.extend(affected.into_iter().cloned());
+        }
+    }
+
+    fn take(&self) -> Vec<PathBuf> {
+        lock(&self.pending).drain().collect()
+    }
+}
+
+pub fn spawn_rescanner(
+    store: Arc<dyn Store>,
+    requests: RescanRequests,
+    ignore_list: Arc<GlobSet>,
+    queue: Sender<File>,
+    metrics: Arc<Metrics>,
+) {
+    task::spawn(async move {
+        loop {
+            sleep(RESCAN_INTERVAL).await;
+            for root in requests.take() {
+                println!("rescanning {:?}", root);
+                rescan_root(store.as_ref(), &root, Arc::clone(&ignore_list), &queue).await;
+                Metrics::incr(&metrics.rescans);
+            }
+        }
+    });
+}
+
+/// Compares what the log believes exists under `root` with what is actually
+/// on disk and queues the missing Created/Deleted events.
+async fn rescan_root(
+    store: &dyn Store,
+    root: &Path,
+    ignore_list: Arc<GlobSet>,
+    queue: &Sender<File>,
+) {
+    let root_str = root.to_string_lossy().to_string();
+    let known: HashSet<String> = match store.inventory(&root_str).await {
+        Ok(files) => files.into_iter().map(|f| f.path).collect(),
+        Err(e) => {
+            println!("rescan of {} failed: {}", root_str, e);
+            return;
+        }
+    };
+
+    let walk_root = root.to_path_buf();
+    let on_disk = task::spawn_blocking(move || {
+        let mut found = HashSet::new();
+        walk(&walk_root, &ignore_list, &mut found);
+        found
+    })
+    .await
+    .unwrap_or_default();
+
+    for path in on_disk.difference(&known) {
+        // Files can disappear again between the walk and the lookup
+        let Ok(file) = File::new(EventType::Created, path.clone()) else {
+            continue;
+        };
+        if queue.send(file).await.is_err() {
+            return;
+        }
+    }
+    for path in known.difference(&on_disk) {
+        if queue
+            .send(File::missing(EventType::Deleted, path.clone()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+fn walk(dir: &Path, ignore_list: &GlobSet, found: &mut HashSet<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if ignore_list.is_match(&path) {
+            continue;
+        }
+        found.insert(path.to_string_lossy().to_string());
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            walk(&path, ignore_list, found);
+        }
+    }
+}
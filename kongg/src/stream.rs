@@ -0,0 +1,103 @@
+use kongg_shared::models::file::File;
+use tokio::sync::broadcast::{self, Sender};
+
+/// Events buffered per follower. A follower that falls further behind
+/// skips ahead and is told how many it missed.
+pub const STREAM_CAPACITY: usize = 1_024;
+
+/// Serves written events to `kongg_cli tail` and returns where the writer
+/// publishes them. Without a socket (another daemon already serves one, or
+/// the platform has none) events are published to nobody.
+pub fn spawn_event_stream() -> Sender<File> {
+    let (tx, _) = broadcast::channel(STREAM_CAPACITY);
+    #[cfg(unix)]
+    if let Err(e) = unix::serve(tx.clone()) {
+        println!("event stream unavailable: {}", e);
+    }
+    tx
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs::{self, DirBuilder},
+        io,
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+    };
+
+    use kongg_shared::{
+        models::file::File,
+        stream::{encode, socket_path},
+    };
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{UnixListener, UnixStream},
+        sync::broadcast::{Sender, error::RecvError},
+        task,
+    };
+
+    pub fn serve(tx: Sender<File>) -> kongg_shared::Result<()> {
+        let path = socket_path()?;
+        // the socket is only chmod-ed once bound, so the directory is what
+        // keeps other users out in between
+        if let Some(parent) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)?;
+            if fs::metadata(parent)?.permissions().mode() & 0o077 != 0 {
+                fs::set_permissions(parent, fs::Permissions::from_mode(0o700))?;
+            }
+        }
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is served by another daemon", path.display()),
+                )
+                .into());
+            }
+            // left behind by a daemon that didn't shut down cleanly
+            fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+        task::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((socket, _)) => {
+                        task::spawn(follow(socket, tx.clone()));
+                    }
+                    Err(e) => println!("event stream: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn follow(mut socket: UnixStream, tx: Sender<File>) {
+        let mut rx = tx.subscribe();
+        loop {
+            let line = match rx.recv().await {
+                Ok(file) => match encode(&file) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        println!("event stream: {}", e);
+                        continue;
+                    }
+                },
+                Err(RecvError::Lagged(missed)) => {
+                    println!("event stream: a follower missed {} events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if socket.write_all(line.as_bytes()).await.is_err() {
+                // the follower went away
+                return;
+            }
+        }
+    }
+}
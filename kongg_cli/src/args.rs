@@ -0,0 +1,421 @@
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::ArgValueCandidates;
+use kongg_shared::{
+    export::ExportFormat,
+    models::{
+        event::EventType,
+        query::{FileQuery, SortField, SortOrder},
+    },
+    utils::{
+        cli::DocCommand,
+        media::MediaCategory,
+        size::{SizeRange, parse_size, parse_size_range},
+        time::{TimeRange, parse_period, parse_range},
+    },
+};
+
+use crate::{commands::absolute, complete};
+
+/// Browse and manage the file history recorded by the kongg daemon.
+#[derive(Debug, Parser)]
+#[command(name = "kongg_cli", version)]
+pub struct Cli {
+    /// Never colour the output (also honoured through NO_COLOR).
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Lists events when no command is given.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// parsed once, so the size of the larger variants doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List recorded events, newest first.
+    List(ListArgs),
+    /// Find files by name or path, tolerating typos.
+    Search {
+        /// Words to look for.
+        #[arg(required = true)]
+        words: Vec<String>,
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Print new events as the daemon records them.
+    Tail {
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Recent events to print before following.
+        #[arg(long, short = 'n', default_value_t = 10)]
+        lines: usize,
+        /// One JSON object per line, in the export layout.
+        #[arg(long)]
+        json: bool,
+    },
+    /// The life of one file across renames and moves, oldest first.
+    History {
+        /// Where the file is, or any name it had before.
+        #[arg(value_hint = ValueHint::AnyPath)]
+        path: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Files that were deleted and didn't come back, newest first.
+    Deleted {
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// List them per directory or per day.
+        #[arg(long, value_enum)]
+        by: Option<GroupBy>,
+        /// Leave out files that are back on disk, whatever put them there.
+        #[arg(long)]
+        still_missing: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Event counts and sizes: per event type, over time, per directory or
+    /// extension, and the largest deletions.
+    Stats {
+        #[arg(value_enum, default_value_t = StatsReport::Types)]
+        report: StatsReport,
+        /// Length of the periods `activity` counts in, in local time.
+        #[arg(long, value_enum, default_value_t = Period::Day)]
+        per: Period,
+        /// Rows shown by `dirs`, `exts` and `largest`.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// What a directory contained at some point in time.
+    At {
+        #[arg(value_hint = ValueHint::DirPath)]
+        directory: String,
+        /// A time expression, e.g. `2025-03-14 18:00` or `last friday`;
+        /// for a period, its end.
+        #[arg(required = true, num_args = 1..)]
+        time: Vec<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Write events to a file or stdout for other tools.
+    Export {
+        #[arg(long, short, default_value = "json", value_parser = export_format)]
+        format: ExportFormat,
+        /// Defaults to stdout.
+        #[arg(long, short, value_hint = ValueHint::FilePath)]
+        output: Option<String>,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+    /// Merge an NDJSON export from another machine.
+    Import {
+        /// File to read, `-` for stdin.
+        #[arg(value_hint = ValueHint::FilePath)]
+        source: String,
+        /// Machine the export was taken on. Records it had imported from
+        /// other machines keep their own host.
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// Remove events according to the retention rules in the config.
+    Prune {
+        /// Only show what would be removed.
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Directories the daemon watches.
+    Roots {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// What the daemon is doing.
+    Status {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show the configuration.
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+    /// Backups, consistency checks and encryption.
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    #[command(flatten)]
+    Docs(DocCommand),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub filters: FilterArgs,
+    /// Stop after this many events.
+    #[arg(long, short = 'n')]
+    pub limit: Option<usize>,
+    /// What the events are ordered by.
+    #[arg(long, value_enum, default_value_t = SortBy::Time)]
+    pub sort: SortBy,
+    /// Oldest (or smallest, or first) first.
+    #[arg(long)]
+    pub asc: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the configuration in effect, defaults included.
+    Show,
+    /// Print where the config file lives.
+    Path,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Write a compressed snapshot of the whole history. Records are in
+    /// clear text, also from an encrypted store.
+    Backup {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+    },
+    /// Load a snapshot into an empty store.
+    Restore {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+        /// Swap the events already in the store for the snapshot's, all or
+        /// nothing.
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Check the schema and every record.
+    Check {
+        /// Migrate and remove what can't be read or makes no sense.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Encrypt paths and names at rest.
+    Encrypt {
+        /// Keep a random key in a file instead of asking for a passphrase.
+        #[arg(long)]
+        key_file: bool,
+    },
+    /// Seal every record again with a new key.
+    RotateKey,
+}
+
+/// Filters shared by every command that reads events.
+#[derive(Debug, Clone, Default, Args)]
+pub struct FilterArgs {
+    /// Event type, repeat for several.
+    #[arg(long = "type", value_parser = event_type)]
+    pub types: Vec<EventType>,
+    /// Extension without the dot, repeat for several.
+    #[arg(long = "ext", add = ArgValueCandidates::new(complete::extensions))]
+    pub extensions: Vec<String>,
+    /// image, video, audio, document, archive or code, repeat for several.
+    #[arg(long = "category", value_parser = category)]
+    pub categories: Vec<MediaCategory>,
+    /// Events at or after this time, e.g. `3d`, `last monday`, `2025-03`.
+    #[arg(long, value_parser = time_range)]
+    pub since: Option<TimeRange>,
+    /// Events before this time, or before the end of this period.
+    #[arg(long, value_parser = time_range)]
+    pub until: Option<TimeRange>,
+    /// Events in this period, e.g. `yesterday`, `last week`,
+    /// `around 2 weeks ago`.
+    #[arg(long, value_parser = period, conflicts_with_all = ["since", "until"])]
+    pub when: Option<TimeRange>,
+    /// Only paths in this directory.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub under: Option<String>,
+    /// Glob on the file name, or on the path when it has a `/`.
+    #[arg(long)]
+    pub glob: Option<String>,
+    /// Size range, e.g. `>700MB`, `<=10k`, `1GiB..4GiB`; a single size is a
+    /// minimum.
+    #[arg(long, value_parser = size_range, conflicts_with_all = ["min_size", "max_size"])]
+    pub size: Option<SizeRange>,
+    /// At least this size, e.g. `4096`, `700MB`, `1.5GiB`.
+    #[arg(long, value_parser = size)]
+    pub min_size: Option<u64>,
+    /// At most this size.
+    #[arg(long, value_parser = size)]
+    pub max_size: Option<u64>,
+    /// Watch root the event was captured under.
+    #[arg(long, add = ArgValueCandidates::new(complete::roots))]
+    pub root: Option<String>,
+    /// Machine the event was captured on.
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Machine id the event was captured on.
+    #[arg(long = "machine")]
+    pub machine_id: Option<String>,
+    /// Owner of the file.
+    #[arg(long = "user")]
+    pub username: Option<String>,
+    /// Case-insensitive text in the path.
+    #[arg(long)]
+    pub text: Option<String>,
+}
+
+impl FilterArgs {
+    pub fn to_query(&self) -> FileQuery {
+        let mut query = FileQuery::new()
+            .event_types(self.types.iter().copied())
+            .extensions(self.extensions.iter().map(String::as_str))
+            .extensions(
+                self.categories
+                    .iter()
+                    .flat_map(|c| c.extensions().iter().copied()),
+            );
+        if let Some(since) = self.since {
+            query = query.since(since.start);
+        }
+        if let Some(until) = self.until {
+            query = query.until(until.end);
+        }
+        if let Some(when) = self.when {
+            query = query.since(when.start).until(when.end);
+        }
+        if let Some(under) = &self.under {
+            query = query.under(absolute(under));
+        }
+        if let Some(glob) = &self.glob {
+            query = query.glob(glob.clone());
+        }
+        if let Some(size) = self.size {
+            if let Some(min) = size.min {
+                query = query.min_size(min);
+            }
+            if let Some(max) = size.max {
+                query = query.max_size(max);
+            }
+        }
+        if let Some(min) = self.min_size {
+            query = query.min_size(min);
+        }
+        if let Some(max) = self.max_size {
+            query = query.max_size(max);
+        }
+        if let Some(root) = &self.root {
+            query = query.root(root.clone());
+        }
+        if let Some(host) = &self.host {
+            query = query.host(host.clone());
+        }
+        if let Some(machine_id) = &self.machine_id {
+            query = query.machine_id(machine_id.clone());
+        }
+        if let Some(username) = &self.username {
+            query = query.username(username.clone());
+        }
+        if let Some(text) = &self.text {
+            query = query.text(text.clone());
+        }
+        query
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct OutputArgs {
+    /// How results are printed.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Dir,
+    Day,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatsReport {
+    /// Events and bytes per event type; for deletions, the space freed.
+    Types,
+    /// Events per type in each day or week.
+    Activity,
+    /// Directories with the most events.
+    Dirs,
+    /// Extensions with the most bytes created and deleted.
+    Exts,
+    /// The biggest files deleted.
+    Largest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    #[default]
+    Time,
+    Size,
+    Name,
+    Path,
+}
+
+impl SortBy {
+    pub fn order(self, asc: bool) -> (SortField, SortOrder) {
+        let field = match self {
+            SortBy::Time => SortField::Timestamp,
+            SortBy::Size => SortField::Size,
+            SortBy::Name => SortField::Name,
+            SortBy::Path => SortField::Path,
+        };
+        let order = if asc { SortOrder::Asc } else { SortOrder::Desc };
+        (field, order)
+    }
+}
+
+fn event_type(value: &str) -> Result<EventType, String> {
+    EventType::from_str(value).map_err(|e| e.to_string())
+}
+
+fn time_range(value: &str) -> Result<TimeRange, String> {
+    parse_range(value).map_err(|e| e.to_string())
+}
+
+fn period(value: &str) -> Result<TimeRange, String> {
+    parse_period(value).map_err(|e| e.to_string())
+}
+
+fn size(value: &str) -> Result<u64, String> {
+    parse_size(value).map_err(|e| e.to_string())
+}
+
+fn size_range(value: &str) -> Result<SizeRange, String> {
+    parse_size_range(value).map_err(|e| e.to_string())
+}
+
+fn category(value: &str) -> Result<MediaCategory, String> {
+    MediaCategory::from_str(value).map_err(|e| e.to_string())
+}
+
+fn export_format(value: &str) -> Result<ExportFormat, String> {
+    ExportFormat::from_str(value).map_err(|e| e.to_string())
+}
@@ -0,0 +1,112 @@
+use std::fs;
+
+use kongg_shared::{
+    Error, Result,
+    backup::{backup, restore},
+    check::check,
+    config::{Config, KeySource},
+    crypto::prompt_passphrase,
+    store::{
+        Store,
+        encrypted::{encrypt as encrypt_store, rotate_key as rotate_store_key},
+        open,
+    },
+};
+
+use crate::args::DbCommand;
+
+pub async fn run(store: &dyn Store, command: DbCommand) -> Result<()> {
+    match command {
+        DbCommand::Backup { file: path } => {
+            let file = fs::File::create(&path).map_err(|e| Error::path(&path, e))?;
+            let summary = backup(store, file).await?;
+            println!(
+                "backed up {} events as of {} (schema {}) to {}",
+                summary.records, summary.header.taken_at, summary.header.schema_version, path
+            );
+        }
+        DbCommand::Restore {
+            file: path,
+            replace,
+        } => {
+            let file = fs::File::open(&path).map_err(|e| Error::path(&path, e))?;
+            let summary = restore(store, file, replace).await?;
+            println!(
+                "restored {} events from a backup taken {} on {}",
+                summary.records,
+                summary.header.taken_at,
+                summary.header.host.as_deref().unwrap_or("an unknown host")
+            );
+        }
+        DbCommand::Check { repair } => {
+            let report = check(store, repair).await?;
+
+            println!(
+                "schema version {} (latest {})",
+                report.schema_version, report.latest_version
+            );
+            if let Some(version) = report.migrated_to {
+                println!("migrated to schema version {}", version);
+            }
+            for (id, reason) in &report.malformed {
+                println!("|malformed | {} | {} |", id, reason);
+            }
+            for (file, reason) in &report.orphaned {
+                println!("|orphaned | {} | {} | {} |", file.id, file.path, reason);
+            }
+            if report.orphans_skipped {
+                println!("orphan check skipped, repair the malformed records first");
+            }
+            if let Some(e) = &report.status_error {
+                println!("daemon status unreadable: {}", e);
+            }
+            println!("-----------------------------------");
+            println!(
+                "{} records read, {} malformed, {} orphaned{}",
+                report.records,
+                report.malformed.len(),
+                report.orphaned.len(),
+                if repair {
+                    format!(", {} removed", report.removed)
+                } else {
+                    String::new()
+                }
+            );
+            if !repair && !report.is_healthy() {
+                println!("run `kongg_cli db check --repair` to fix what can be fixed");
+            }
+        }
+        DbCommand::Encrypt { .. } | DbCommand::RotateKey => {
+            unreachable!("handled before opening the store")
+        }
+    }
+    Ok(())
+}
+
+pub async fn encrypt(config: &mut Config, key_file: bool) -> Result<()> {
+    let source = if key_file {
+        KeySource::KeyFile
+    } else {
+        KeySource::Passphrase
+    };
+    let store = open(&config.storage).await?;
+    let sealed = encrypt_store(store, config, source, prompt_passphrase).await?;
+    report_new_key(config, sealed)
+}
+
+pub async fn rotate_key(config: &mut Config) -> Result<()> {
+    let store = open(&config.storage).await?;
+    let sealed = rotate_store_key(store, config, prompt_passphrase).await?;
+    report_new_key(config, sealed)
+}
+
+fn report_new_key(config: &Config, sealed: u64) -> Result<()> {
+    println!("sealed {} records with the new key", sealed);
+    if config.encryption.key_source == KeySource::KeyFile {
+        println!(
+            "the key is in {}, keep a copy somewhere safe",
+            config.encryption.resolved_key_file()?.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use kongg_shared::{
+    Result,
+    deleted::{DeletedFile, deleted as deleted_files},
+    models::query::FileQuery,
+    stats::local_day,
+    store::Store,
+};
+
+use crate::{
+    args::{GroupBy, OutputFormat},
+    output::{Output, Table, local_time},
+};
+
+/// Files whose last event is a deletion, newest first, optionally per
+/// directory (alphabetically) or per day (newest first).
+pub async fn deleted(
+    store: &dyn Store,
+    query: &FileQuery,
+    by: Option<GroupBy>,
+    still_missing: bool,
+    output: &Output,
+) -> Result<()> {
+    let mut files = deleted_files(store, query).await?;
+    if still_missing {
+        files.retain(DeletedFile::still_missing);
+    }
+    let count = files.len();
+    let bytes: u64 = files.iter().filter_map(|d| d.size).sum();
+    let size = |d: &DeletedFile| d.size.map(|s| output.size(s)).unwrap_or_else(|| "".into());
+
+    let table = match by {
+        None => {
+            let mut table = Table::new(vec!["deleted", "path", "size", "host"]);
+            for d in &files {
+                table.row(vec![
+                    local_time(&d.file.timestamp).into(),
+                    d.file.path.as_str().into(),
+                    size(d),
+                    d.file.host.as_deref().unwrap_or("local").into(),
+                ]);
+            }
+            table
+        }
+        Some(by) => {
+            let mut groups: BTreeMap<String, Vec<&DeletedFile>> = BTreeMap::new();
+            for d in &files {
+                let key = match by {
+                    GroupBy::Dir => d.file.parent_directory.clone().unwrap_or_default(),
+                    GroupBy::Day => local_day(&d.file.timestamp),
+                };
+                groups.entry(key).or_default().push(d);
+            }
+            let groups: Vec<(String, Vec<&DeletedFile>)> = match by {
+                GroupBy::Dir => groups.into_iter().collect(),
+                GroupBy::Day => groups.into_iter().rev().collect(),
+            };
+
+            let (group, item) = match by {
+                GroupBy::Dir => ("directory", "name"),
+                GroupBy::Day => ("day", "path"),
+            };
+            let mut table = Table::new(vec![group, item, "size", "deleted"]);
+            for (key, files) in groups {
+                // in a table the group is named once, with its totals
+                let label = if output.format == OutputFormat::Table {
+                    let bytes: u64 = files.iter().filter_map(|d| d.size).sum();
+                    format!(
+                        "{}\n{} files, {}",
+                        key,
+                        files.len(),
+                        output.readable_size(bytes)
+                    )
+                } else {
+                    key.clone()
+                };
+                for (i, d) in files.into_iter().enumerate() {
+                    let name = match by {
+                        GroupBy::Dir => d.file.file_name.as_deref().unwrap_or(d.file.path.as_str()),
+                        GroupBy::Day => d.file.path.as_str(),
+                    };
+                    let shown = if i == 0 || output.format != OutputFormat::Table {
+                        label.as_str()
+                    } else {
+                        ""
+                    };
+                    table.row(vec![
+                        shown.into(),
+                        name.into(),
+                        size(d),
+                        local_time(&d.file.timestamp).into(),
+                    ]);
+                }
+            }
+            table
+        }
+    };
+    output.table(table)?;
+
+    eprintln!(
+        "{} deleted files{}, {} known",
+        count,
+        if still_missing { " still missing" } else { "" },
+        output.readable_size(bytes)
+    );
+    Ok(())
+}
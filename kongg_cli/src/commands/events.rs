@@ -0,0 +1,116 @@
+use std::{collections::HashSet, path::Path};
+
+use comfy_table::Color;
+use kongg_shared::{
+    Result, history::history as file_history, models::query::FileQuery,
+    search::search as search_store, store::Store, utils::time::parse_range,
+};
+
+use crate::{
+    args::{ListArgs, OutputFormat},
+    commands::collect,
+    output::{Cell, Output, Table, event_color, local_time},
+};
+
+/// A path as the user typed it, made absolute the way the daemon records
+/// it. The file may be gone, in which case it is only joined to the
+/// current directory.
+pub(crate) fn absolute(path: &str) -> String {
+    Path::new(path)
+        .canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+pub async fn list(store: &dyn Store, args: &ListArgs, output: &Output) -> Result<()> {
+    let (sort, order) = args.sort.order(args.asc);
+    let query = args.filters.to_query().sort(sort, order);
+    output.events(collect(store, query, args.limit).await?)
+}
+
+pub async fn search(
+    store: &dyn Store,
+    words: &[String],
+    filter: &FileQuery,
+    output: &Output,
+) -> Result<()> {
+    let hits = search_store(store, &words.join(" "), filter).await?;
+    if output.format != OutputFormat::Table {
+        return output.events(hits.into_iter().map(|hit| hit.file).collect());
+    }
+
+    let mut table = Table::new(vec!["score", "event", "path", "size", "time", "host"]);
+    for hit in hits {
+        let mut row = vec![Cell::from(format!("{:.2}", hit.score))];
+        row.extend(output.event_row(&hit.file));
+        table.row(row);
+    }
+    output.table(table)
+}
+
+/// The life of the file at `path`, or of the one that used to be there,
+/// oldest first.
+pub async fn history(store: &dyn Store, path: &str, output: &Output) -> Result<()> {
+    let entries = file_history(store, &absolute(path)).await?;
+
+    let mut table = Table::new(vec![
+        "time", "step", "path", "size", "change", "content", "host",
+    ]);
+    for entry in entries {
+        let file = entry.file;
+        let path = match &file.previous_path {
+            Some(previous) => format!("{} -> {}", previous, file.path),
+            None => file.path,
+        };
+        table.row(vec![
+            local_time(&file.timestamp).into(),
+            Cell::colored(entry.step.to_string(), event_color(file.event_type)),
+            path.into(),
+            file.file_size
+                .map(|s| output.size(s))
+                .unwrap_or_else(|| "".into()),
+            entry
+                .size_delta
+                .filter(|d| *d != 0)
+                .map(|d| output.size_change(d))
+                .unwrap_or_else(|| "".into()),
+            if entry.hash_changed { "changed" } else { "" }.into(),
+            file.host.as_deref().unwrap_or("local").into(),
+        ]);
+    }
+    output.table(table)
+}
+
+/// What `directory` contained at `time`, and whether each file is still
+/// there.
+pub async fn at(store: &dyn Store, directory: &str, time: &str, output: &Output) -> Result<()> {
+    let at = parse_range(time)?.end;
+    let directory = absolute(directory);
+
+    let then = store.inventory_at(&directory, Some(at)).await?;
+    let now: HashSet<String> = store
+        .inventory(&directory)
+        .await?
+        .into_iter()
+        .map(|f| f.path)
+        .collect();
+
+    if output.format == OutputFormat::Table {
+        println!("{} as of {}", directory, local_time(&at));
+    }
+    let mut table = Table::new(vec!["state", "path", "last event"]);
+    for file in then {
+        let state = if now.contains(&file.path) {
+            Cell::colored("here", Color::Green)
+        } else {
+            Cell::colored("gone", Color::Red)
+        };
+        table.row(vec![
+            state,
+            file.path.into(),
+            local_time(&file.timestamp).into(),
+        ]);
+    }
+    output.table(table)
+}
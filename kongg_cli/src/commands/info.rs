@@ -0,0 +1,118 @@
+use chrono::Utc;
+use comfy_table::Color;
+use kongg_shared::{
+    Result,
+    config::Config,
+    retention::{PruneReason, prune as prune_store},
+    store::Store,
+};
+
+use crate::{
+    args::ConfigCommand,
+    output::{Cell, Output, Table, event_color, local_time},
+};
+
+/// Applies the retention rules and lists what they removed.
+pub async fn prune(
+    store: &dyn Store,
+    config: &Config,
+    dry_run: bool,
+    output: &Output,
+) -> Result<()> {
+    let report = prune_store(store, &config.retention, dry_run).await?;
+
+    let mut table = Table::new(vec!["reason", "event", "path", "time"]);
+    for (file, reason) in &report.removed {
+        table.row(vec![
+            format!("{:?}", reason).into(),
+            Cell::colored(file.event_type.to_string(), event_color(file.event_type)),
+            file.path.as_str().into(),
+            local_time(&file.timestamp).into(),
+        ]);
+    }
+    output.table(table)?;
+
+    eprintln!(
+        "{} {} of {} events ({} expired, {} collapsed, {} over the per-root cap)",
+        if dry_run { "would remove" } else { "removed" },
+        report.removed.len(),
+        report.scanned,
+        report.count(PruneReason::Expired),
+        report.count(PruneReason::Collapsed),
+        report.count(PruneReason::OverCap),
+    );
+    Ok(())
+}
+
+/// Watch roots of the daemon with what they currently hold.
+pub async fn roots(store: &dyn Store, output: &Output) -> Result<()> {
+    let status = store.status().await?.unwrap_or_default();
+
+    let mut table = Table::new(vec!["root", "files", "size"]);
+    for root in &status.roots {
+        let files = store.inventory(root).await?;
+        let bytes: u64 = files.iter().filter_map(|f| f.file_size).sum();
+        table.row(vec![
+            root.as_str().into(),
+            files.len().to_string().into(),
+            output.size(bytes),
+        ]);
+    }
+    output.table(table)
+}
+
+/// The daemon's last saved status.
+pub async fn status(store: &dyn Store, output: &Output) -> Result<()> {
+    let Some(status) = store.status().await? else {
+        println!("the daemon has never run against this store");
+        return Ok(());
+    };
+
+    let state = if status.is_running(Utc::now()) {
+        Cell::colored("running", Color::Green)
+    } else {
+        Cell::colored("stopped", Color::Red)
+    };
+    let count = |n: u64| Cell::from(n.to_string());
+
+    let mut table = Table::new(vec!["field", "value"]);
+    let rows = vec![
+        ("state", state),
+        (
+            "updated at",
+            status
+                .updated_at
+                .map(|t| local_time(&t))
+                .unwrap_or_default()
+                .into(),
+        ),
+        ("roots", status.roots.join(", ").into()),
+        (
+            "queue",
+            format!("{}/{}", status.queue_depth, status.queue_capacity).into(),
+        ),
+        ("pending events", count(status.pending_events as u64)),
+        ("captured events", count(status.captured_events)),
+        ("written events", count(status.written_events)),
+        ("batches", count(status.batches)),
+        ("rescans", count(status.rescans)),
+        ("dropped events", count(status.dropped_events)),
+        ("overflows", count(status.overflows)),
+        ("failed events", count(status.failed_events)),
+        ("failed writes", count(status.failed_writes)),
+        ("watch errors", count(status.watch_errors)),
+        ("pruned events", count(status.pruned_events)),
+    ];
+    for (field, value) in rows {
+        table.row(vec![field.into(), value]);
+    }
+    output.table(table)
+}
+
+pub fn config(config: &Config, command: Option<ConfigCommand>) -> Result<()> {
+    match command.unwrap_or(ConfigCommand::Show) {
+        ConfigCommand::Show => print!("{}", config.to_toml()?),
+        ConfigCommand::Path => println!("{}", Config::path()?.display()),
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use clap::CommandFactory;
+use kongg_shared::{
+    Result,
+    config::Config,
+    models::{file::FileResponse, query::FileQuery},
+    store::{Store, open_with_config},
+};
+
+use crate::{
+    args::{Cli, Command, DbCommand, ListArgs, OutputFormat},
+    output::Output,
+};
+
+mod db;
+mod deleted;
+mod events;
+mod info;
+mod stats;
+mod tail;
+mod transfer;
+
+pub(crate) use events::absolute;
+
+/// Rows fetched per round trip.
+const PAGE_SIZE: usize = 1000;
+
+pub async fn run(cli: Cli) -> Result<()> {
+    if let Some(Command::Docs(command)) = &cli.command {
+        return command.run(Cli::command());
+    }
+
+    let mut config = Config::load()?;
+    let no_color = cli.no_color;
+    let units = config.display.size_units;
+    let output = |format| Output::new(format, no_color, units);
+
+    // these don't read events, or work on the records as stored, so the
+    // store isn't opened (and unlocked) for them
+    let command = match cli.command.unwrap_or(Command::List(ListArgs::default())) {
+        Command::Config { command } => return info::config(&config, command),
+        Command::Db {
+            command: DbCommand::Encrypt { key_file },
+        } => return db::encrypt(&mut config, key_file).await,
+        Command::Db {
+            command: DbCommand::RotateKey,
+        } => return db::rotate_key(&mut config).await,
+        command => command,
+    };
+
+    let store = open_with_config(&config).await?;
+    let store = store.as_ref();
+    match command {
+        Command::List(args) => events::list(store, &args, &output(args.output.format)).await,
+        Command::Search {
+            words,
+            filters,
+            output: args,
+        } => events::search(store, &words, &filters.to_query(), &output(args.format)).await,
+        Command::Tail {
+            filters,
+            lines,
+            json,
+        } => {
+            let format = if json {
+                OutputFormat::Json
+            } else {
+                OutputFormat::Table
+            };
+            tail::tail(store, filters.to_query(), lines, &output(format)).await
+        }
+        Command::History { path, output: args } => {
+            events::history(store, &path, &output(args.format)).await
+        }
+        Command::Deleted {
+            filters,
+            by,
+            still_missing,
+            output: args,
+        } => {
+            let query = filters.to_query();
+            deleted::deleted(store, &query, by, still_missing, &output(args.format)).await
+        }
+        Command::At {
+            directory,
+            time,
+            output: args,
+        } => events::at(store, &directory, &time.join(" "), &output(args.format)).await,
+        Command::Stats {
+            report,
+            per,
+            top,
+            filters,
+            output: args,
+        } => {
+            let query = filters.to_query();
+            stats::stats(store, &query, report, per, top, &output(args.format)).await
+        }
+        Command::Export {
+            format,
+            output: path,
+            filters,
+        } => transfer::export(store, &filters.to_query(), format, path.as_deref()).await,
+        Command::Import { source, host } => transfer::import(store, &source, host.as_deref()).await,
+        Command::Prune {
+            dry_run,
+            output: args,
+        } => info::prune(store, &config, dry_run, &output(args.format)).await,
+        Command::Roots { output: args } => info::roots(store, &output(args.format)).await,
+        Command::Status { output: args } => info::status(store, &output(args.format)).await,
+        Command::Db { command } => db::run(store, command).await,
+        Command::Config { .. } | Command::Docs(_) => {
+            unreachable!("handled before opening the store")
+        }
+    }
+}
+
+/// Every event matching `query`, in its order, up to `limit`.
+pub async fn collect(
+    store: &dyn Store,
+    query: FileQuery,
+    limit: Option<usize>,
+) -> Result<Vec<FileResponse>> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut query = query.limit(limit.min(PAGE_SIZE));
+    let mut events = Vec::new();
+    loop {
+        let page = store.query(&query).await?;
+        events.extend(page.items);
+        match page.next {
+            Some(cursor) if events.len() < limit => query = query.after(cursor),
+            _ => break,
+        }
+    }
+    events.truncate(limit);
+    Ok(events)
+}
@@ -0,0 +1,180 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use kongg_shared::{
+    Result,
+    models::{event::EventType, query::FileQuery},
+    stats::{Bucket, GroupBy, by_week, totals_by_key},
+    store::Store,
+};
+
+use crate::{
+    args::{Period, StatsReport},
+    output::{Cell, Output, Table, event_color, local_time},
+};
+
+const EVENT_TYPES: [EventType; 5] = [
+    EventType::Created,
+    EventType::Copied,
+    EventType::Renamed,
+    EventType::Moved,
+    EventType::Deleted,
+];
+
+/// One of the [`StatsReport`]s, computed by the store.
+pub async fn stats(
+    store: &dyn Store,
+    query: &FileQuery,
+    report: StatsReport,
+    per: Period,
+    top: usize,
+    output: &Output,
+) -> Result<()> {
+    let table = match report {
+        StatsReport::Types => types(store, query, output).await?,
+        StatsReport::Activity => activity(store, query, per, output).await?,
+        StatsReport::Dirs => dirs(store, query, top).await?,
+        StatsReport::Exts => exts(store, query, top, output).await?,
+        StatsReport::Largest => largest(store, query, top, output).await?,
+    };
+    output.table(table)
+}
+
+async fn types(store: &dyn Store, query: &FileQuery, output: &Output) -> Result<Table> {
+    let mut per_type: BTreeMap<String, (EventType, u64, u64)> = BTreeMap::new();
+    for bucket in store.aggregate(query, GroupBy::Day).await? {
+        let (_, count, bytes) =
+            per_type
+                .entry(bucket.event_type.to_string())
+                .or_insert((bucket.event_type, 0, 0));
+        *count += bucket.count;
+        *bytes += bucket.bytes;
+    }
+
+    let mut table = Table::new(vec!["event", "count", "size"]);
+    let mut total = 0;
+    for (name, (event_type, count, bytes)) in per_type {
+        total += count;
+        table.row(vec![
+            Cell::colored(name, event_color(event_type)),
+            count.to_string().into(),
+            output.size(bytes),
+        ]);
+    }
+    if !table.is_empty() {
+        table.row(vec!["total".into(), total.to_string().into(), "".into()]);
+    }
+    Ok(table)
+}
+
+/// Counts per event type in each period, oldest first, and the space
+/// freed.
+async fn activity(
+    store: &dyn Store,
+    query: &FileQuery,
+    per: Period,
+    output: &Output,
+) -> Result<Table> {
+    let mut buckets = store.aggregate(query, GroupBy::Day).await?;
+    if per == Period::Week {
+        buckets = by_week(buckets);
+    }
+
+    let mut periods: BTreeMap<String, Vec<Bucket>> = BTreeMap::new();
+    for bucket in buckets {
+        periods.entry(bucket.key.clone()).or_default().push(bucket);
+    }
+
+    let header = match per {
+        Period::Day => "day",
+        Period::Week => "week",
+    };
+    let mut table = Table::new(vec![
+        header, "created", "copied", "renamed", "moved", "deleted", "freed",
+    ]);
+    for (period, buckets) in periods {
+        let of = |event_type: EventType| buckets.iter().find(|b| b.event_type == event_type);
+        let mut row: Vec<Cell> = vec![period.into()];
+        for event_type in EVENT_TYPES {
+            row.push(of(event_type).map_or(0, |b| b.count).to_string().into());
+        }
+        row.push(output.size(of(EventType::Deleted).map_or(0, |b| b.bytes)));
+        table.row(row);
+    }
+    Ok(table)
+}
+
+/// The `top` directories by number of events.
+async fn dirs(store: &dyn Store, query: &FileQuery, top: usize) -> Result<Table> {
+    let buckets = store.aggregate(query, GroupBy::Directory).await?;
+    let count = |dir: &str, event_type: EventType| {
+        buckets
+            .iter()
+            .find(|b| b.key == dir && b.event_type == event_type)
+            .map_or(0, |b| b.count)
+    };
+
+    let mut totals: Vec<(&str, (u64, u64))> = totals_by_key(&buckets).into_iter().collect();
+    totals.sort_by_key(|(_, (events, _))| Reverse(*events));
+
+    let mut table = Table::new(vec!["directory", "events", "created", "deleted"]);
+    for (dir, (events, _)) in totals.into_iter().take(top) {
+        table.row(vec![
+            dir.into(),
+            events.to_string().into(),
+            count(dir, EventType::Created).to_string().into(),
+            count(dir, EventType::Deleted).to_string().into(),
+        ]);
+    }
+    Ok(table)
+}
+
+/// The `top` extensions by bytes created (or copied) and deleted together.
+async fn exts(store: &dyn Store, query: &FileQuery, top: usize, output: &Output) -> Result<Table> {
+    let mut per_extension: BTreeMap<String, (u64, u64, u64)> = BTreeMap::new();
+    for bucket in store.aggregate(query, GroupBy::Extension).await? {
+        let (events, created, deleted) = per_extension.entry(bucket.key).or_default();
+        *events += bucket.count;
+        match bucket.event_type {
+            EventType::Created | EventType::Copied => *created += bucket.bytes,
+            EventType::Deleted => *deleted += bucket.bytes,
+            EventType::Renamed | EventType::Moved => {}
+        }
+    }
+
+    let mut extensions: Vec<_> = per_extension.into_iter().collect();
+    extensions.sort_by_key(|(_, (_, created, deleted))| Reverse(created + deleted));
+
+    let mut table = Table::new(vec!["extension", "events", "created", "deleted"]);
+    for (extension, (events, created, deleted)) in extensions.into_iter().take(top) {
+        let extension = if extension.is_empty() {
+            "(none)".to_string()
+        } else {
+            extension
+        };
+        table.row(vec![
+            extension.into(),
+            events.to_string().into(),
+            output.size(created),
+            output.size(deleted),
+        ]);
+    }
+    Ok(table)
+}
+
+async fn largest(
+    store: &dyn Store,
+    query: &FileQuery,
+    top: usize,
+    output: &Output,
+) -> Result<Table> {
+    let mut table = Table::new(vec!["deleted", "path", "size", "host"]);
+    for (file, size) in store.largest_deleted(query, top).await? {
+        table.row(vec![
+            local_time(&file.timestamp).into(),
+            file.path.as_str().into(),
+            output.size(size),
+            file.host.as_deref().unwrap_or("local").into(),
+        ]);
+    }
+    Ok(table)
+}
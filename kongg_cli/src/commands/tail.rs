@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use kongg_shared::{
+    Error, Result,
+    models::{
+        file::FileResponse,
+        query::{FileQuery, SortField, SortOrder},
+    },
+    store::Store,
+    stream::{EventKey, decode, event_key},
+};
+
+use crate::{commands::collect, output::Output};
+
+/// The last `lines` events, then every new one the daemon writes until it
+/// stops.
+pub async fn tail(
+    store: &dyn Store,
+    query: FileQuery,
+    lines: usize,
+    output: &Output,
+) -> Result<()> {
+    // connect first so nothing written in between is missed; what the
+    // stream repeats of the recent events is skipped
+    let mut stream = follow::connect().await?;
+
+    let mut recent = if lines == 0 {
+        Vec::new()
+    } else {
+        let query = query.clone().sort(SortField::Timestamp, SortOrder::Desc);
+        collect(store, query, Some(lines)).await?
+    };
+    recent.reverse();
+    for file in &recent {
+        output.event_line(file)?;
+    }
+    let shown: HashSet<EventKey> = recent.iter().map(event_key).collect();
+
+    while let Some(line) = follow::next_line(&mut stream).await? {
+        let file = decode(&line)?;
+        if is_new(&file, &query, &shown) {
+            output.event_line(&file)?;
+        }
+    }
+    Err(Error::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "the daemon stopped",
+    )))
+}
+
+/// Whether a streamed `file` is to be printed: `query` lets it through and
+/// it isn't one of the recent events already `shown`.
+fn is_new(file: &FileResponse, query: &FileQuery, shown: &HashSet<EventKey>) -> bool {
+    query.matches(file) && !shown.contains(&event_key(file))
+}
+
+#[cfg(unix)]
+mod follow {
+    use std::io;
+
+    use kongg_shared::{Result, stream::socket_path};
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader, Lines},
+        net::UnixStream,
+    };
+
+    pub type Stream = Lines<BufReader<UnixStream>>;
+
+    pub async fn connect() -> Result<Stream> {
+        let path = socket_path()?;
+        let socket = UnixStream::connect(&path).await.map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "can't follow the daemon at {} ({}), is it running?",
+                    path.display(),
+                    e
+                ),
+            )
+        })?;
+        Ok(BufReader::new(socket).lines())
+    }
+
+    pub async fn next_line(stream: &mut Stream) -> Result<Option<String>> {
+        Ok(stream.next_line().await?)
+    }
+}
+
+#[cfg(not(unix))]
+mod follow {
+    use kongg_shared::{Error, Result};
+
+    pub struct Stream;
+
+    pub async fn connect() -> Result<Stream> {
+        Err(Error::Config(
+            "following the daemon needs Unix sockets, which this platform lacks".to_string(),
+        ))
+    }
+
+    pub async fn next_line(_: &mut Stream) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use kongg_shared::{
+        models::{event::EventType, file::File},
+        stream::encode,
+    };
+    use surrealdb::RecordId;
+
+    use super::*;
+
+    /// `file` as the daemon's stream hands it over.
+    fn streamed(file: &FileResponse) -> FileResponse {
+        decode(&encode(&File::from(file.clone())).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn skips_what_was_shown_and_nothing_else() {
+        // as read back from the store
+        let created = File::missing(EventType::Created, "/w/notes.txt".to_string());
+        let mut recent = decode(&encode(&created).unwrap()).unwrap();
+        recent.id = RecordId::from_table_key("file", 7);
+        let shown = HashSet::from([event_key(&recent)]);
+        let everything = FileQuery::new();
+
+        assert!(!is_new(&streamed(&recent), &everything, &shown));
+
+        // another type at the same instant, the same type a moment later
+        // and another file at the same instant are all new
+        let mut deleted = recent.clone();
+        deleted.event_type = EventType::Deleted;
+        let mut again = recent.clone();
+        again.timestamp += Duration::milliseconds(1);
+        let mut elsewhere = recent.clone();
+        elsewhere.path = "/w/other.txt".to_string();
+        for file in [&deleted, &again, &elsewhere] {
+            assert!(is_new(&streamed(file), &everything, &shown));
+        }
+
+        let created_only = FileQuery::new().event_type(EventType::Created);
+        assert!(!is_new(&streamed(&deleted), &created_only, &shown));
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    fs,
+    io::{self, BufReader},
+};
+
+use kongg_shared::{
+    Error, Result,
+    export::{ExportFormat, export as export_store},
+    import::import as import_store,
+    models::query::FileQuery,
+    store::Store,
+};
+
+/// Writes the matching events to `path`, or stdout without one.
+pub async fn export(
+    store: &dyn Store,
+    query: &FileQuery,
+    format: ExportFormat,
+    path: Option<&str>,
+) -> Result<()> {
+    let written = match path {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| Error::path(path, e))?;
+            export_store(store, query, format, file).await?
+        }
+        None => export_store(store, query, format, io::stdout().lock()).await?,
+    };
+    eprintln!("exported {} events", written);
+    Ok(())
+}
+
+/// Merges an NDJSON export read from `source`, `-` being stdin.
+pub async fn import(store: &dyn Store, source: &str, host: Option<&str>) -> Result<()> {
+    let report = if source == "-" {
+        import_store(store, io::stdin().lock(), host).await?
+    } else {
+        let file = fs::File::open(source).map_err(|e| Error::path(source, e))?;
+        import_store(store, BufReader::new(file), host).await?
+    };
+    println!(
+        "imported {} of {} records ({} already known)",
+        report.imported, report.read, report.duplicates
+    );
+    Ok(())
+}
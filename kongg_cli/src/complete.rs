@@ -0,0 +1,66 @@
+//! Values for shell completion that only the database knows. Completion
+//! must stay quiet and quick, so anything that goes wrong just means no
+//! suggestions, and an encrypted store is never unlocked.
+
+use std::{cmp::Reverse, future::Future, sync::Arc};
+
+use clap_complete::CompletionCandidate;
+use kongg_shared::{
+    Result,
+    config::Config,
+    models::{event::EventType, query::FileQuery},
+    stats::GroupBy,
+    store::{Store, open},
+};
+
+/// Opens the store without unlocking it, on a runtime of its own since
+/// completion runs before `main` starts one.
+fn read<F, Fut>(query: F) -> Vec<CompletionCandidate>
+where
+    F: FnOnce(Config, Arc<dyn Store>) -> Fut,
+    Fut: Future<Output = Result<Vec<CompletionCandidate>>>,
+{
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(_) => return Vec::new(),
+    };
+    runtime
+        .block_on(async {
+            let config = Config::load()?;
+            let store = open(&config.storage).await?;
+            query(config, store).await
+        })
+        .unwrap_or_default()
+}
+
+/// Directories the daemon watches, as it last reported them.
+pub fn roots() -> Vec<CompletionCandidate> {
+    read(|_, store| async move {
+        let roots = store.status().await?.map(|s| s.roots).unwrap_or_default();
+        Ok(roots.into_iter().map(CompletionCandidate::new).collect())
+    })
+}
+
+/// Extensions of the files seen so far, the most common first. Sealed in
+/// an encrypted store, so none are offered there.
+pub fn extensions() -> Vec<CompletionCandidate> {
+    read(|config, store| async move {
+        if config.encryption.enabled {
+            return Ok(Vec::new());
+        }
+        let query = FileQuery::new().event_types([EventType::Created]);
+        let mut buckets = store.aggregate(&query, GroupBy::Extension).await?;
+        buckets.retain(|b| !b.key.is_empty());
+        buckets.sort_by_key(|b| Reverse(b.count));
+        Ok(buckets
+            .into_iter()
+            .map(|b| {
+                let help = format!("{} created", b.count);
+                CompletionCandidate::new(b.key).help(Some(help.into()))
+            })
+            .collect())
+    })
+}
@@ -1,17 +1,29 @@
-use kongg_shared::helpers::{crud::get_file, surreal_init::init};
+use std::io;
+
+use clap::{CommandFactory, Parser};
+use kongg_shared::{Error, utils::cli::complete};
+
+use crate::args::Cli;
+
+mod args;
+mod commands;
+mod complete;
+mod output;
+
+fn main() {
+    complete(Cli::command);
+    run(Cli::parse());
+}
 
 #[tokio::main]
-async fn main() {
-    let db = init().await.unwrap();
-    let files = get_file(&db).await;
-    println!("----------> START");
-    println!("|event type | location| time & date |");
-    println!("-----------------------------------");
-    for file in files {
-        println!(
-            "|{:?} | {} | {} |",
-            file.event_type, file.path, file.timestamp
-        )
+async fn run(cli: Cli) {
+    match commands::run(cli).await {
+        Ok(()) => {}
+        // whatever read the output went away, e.g. `kongg_cli list | head`
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
     }
-    println!("----------> END");
 }
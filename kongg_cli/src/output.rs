@@ -0,0 +1,263 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+};
+
+use chrono::{DateTime, Local, Utc};
+use comfy_table::{Cell as TableCell, Color, ContentArrangement, Table as Grid, presets};
+use kongg_shared::{
+    Error, Result,
+    export::{ExportFormat, ExportRecord, write_records},
+    models::{event::EventType, file::FileResponse},
+    utils::size::{SizeUnits, format_size},
+};
+
+use crate::args::OutputFormat;
+
+/// One value of a [`Table`], coloured when printed to a terminal.
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Self {
+            text: text.into(),
+            color: Some(color),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, color: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+/// Rows with named columns, printed as a table, a JSON array of objects or
+/// CSV.
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<Cell>) {
+        self.rows.push(cells);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// How results are printed, decided once per run.
+pub struct Output {
+    pub format: OutputFormat,
+    color: bool,
+    units: SizeUnits,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, no_color: bool, units: SizeUnits) -> Self {
+        let color = !no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+        Self {
+            format,
+            color,
+            units,
+        }
+    }
+
+    /// Readable in a table, plain bytes for JSON and CSV.
+    pub fn size(&self, bytes: u64) -> Cell {
+        match self.format {
+            OutputFormat::Table => format_size(bytes, self.units).into(),
+            OutputFormat::Json | OutputFormat::Csv => bytes.to_string().into(),
+        }
+    }
+
+    /// For messages, always readable.
+    pub fn readable_size(&self, bytes: u64) -> String {
+        format_size(bytes, self.units)
+    }
+
+    /// Signed, e.g. `+1.2 MB`, and plain signed bytes for JSON and CSV.
+    pub fn size_change(&self, delta: i64) -> Cell {
+        let sign = if delta < 0 { "-" } else { "+" };
+        match self.format {
+            OutputFormat::Table => {
+                format!("{}{}", sign, format_size(delta.unsigned_abs(), self.units)).into()
+            }
+            OutputFormat::Json | OutputFormat::Csv => delta.to_string().into(),
+        }
+    }
+
+    pub fn event_row(&self, file: &FileResponse) -> Vec<Cell> {
+        vec![
+            Cell::colored(file.event_type.to_string(), event_color(file.event_type)),
+            file.path.as_str().into(),
+            file.file_size
+                .map(|s| self.size(s))
+                .unwrap_or_else(|| "".into()),
+            local_time(&file.timestamp).into(),
+            file.host.as_deref().unwrap_or("local").into(),
+        ]
+    }
+
+    /// Events with the columns that matter when reading a history. JSON
+    /// and CSV get every field, in the export layout.
+    pub fn events(&self, events: Vec<FileResponse>) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                write_records(events, ExportFormat::Json, io::stdout().lock())?;
+            }
+            OutputFormat::Csv => {
+                write_records(events, ExportFormat::Csv, io::stdout().lock())?;
+            }
+            OutputFormat::Table => {
+                let mut table = Table::new(vec!["event", "path", "size", "time", "host"]);
+                for file in &events {
+                    table.row(self.event_row(file));
+                }
+                self.table(table)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One event per line, written as soon as it comes, for output that
+    /// doesn't end. JSON prints one export record per line.
+    pub fn event_line(&self, file: &FileResponse) -> Result<()> {
+        let mut out = io::stdout().lock();
+        if self.format == OutputFormat::Json {
+            serde_json::to_writer(&mut out, &ExportRecord::from(file.clone()))
+                .map_err(|e| Error::Parse(e.to_string()))?;
+        } else {
+            let path = match &file.previous_path {
+                Some(previous) => format!("{} -> {}", previous, file.path),
+                None => file.path.clone(),
+            };
+            let size = file
+                .file_size
+                .map(|s| self.readable_size(s))
+                .unwrap_or_default();
+            write!(
+                out,
+                "{}  {}  {}  {}",
+                local_time(&file.timestamp),
+                self.paint(
+                    &format!("{:<8}", file.event_type.to_string()),
+                    event_color(file.event_type)
+                ),
+                path,
+                size
+            )?;
+        }
+        writeln!(out)?;
+        Ok(out.flush()?)
+    }
+
+    pub fn table(&self, table: Table) -> Result<()> {
+        let mut out = io::stdout().lock();
+        match self.format {
+            OutputFormat::Table => {
+                if table.is_empty() {
+                    return Ok(writeln!(out, "nothing found")?);
+                }
+                writeln!(out, "{}", self.grid(table))?;
+            }
+            OutputFormat::Json => {
+                let rows: Vec<serde_json::Map<String, serde_json::Value>> = table
+                    .rows
+                    .into_iter()
+                    .map(|row| {
+                        table
+                            .headers
+                            .iter()
+                            .map(|h| h.to_string())
+                            .zip(row.into_iter().map(|c| c.text.into()))
+                            .collect()
+                    })
+                    .collect();
+                serde_json::to_writer_pretty(&mut out, &rows)
+                    .map_err(|e| Error::Parse(e.to_string()))?;
+                writeln!(out)?;
+            }
+            OutputFormat::Csv => {
+                let mut csv = csv::Writer::from_writer(out);
+                csv.write_record(&table.headers).map_err(io::Error::from)?;
+                for row in table.rows {
+                    csv.write_record(row.iter().map(|c| &c.text))
+                        .map_err(io::Error::from)?;
+                }
+                csv.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn paint(&self, text: &str, color: Color) -> String {
+        if !self.color {
+            return text.to_string();
+        }
+        let code = match color {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            _ => 39,
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+
+    /// Fits the terminal width, wrapping the widest columns.
+    fn grid(&self, table: Table) -> Grid {
+        let mut grid = Grid::new();
+        grid.load_preset(presets::UTF8_HORIZONTAL_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(table.headers);
+        for row in table.rows {
+            grid.add_row(row.into_iter().map(|cell| {
+                let styled = TableCell::new(cell.text);
+                match cell.color {
+                    Some(color) if self.color => styled.fg(color),
+                    _ => styled,
+                }
+            }));
+        }
+        grid
+    }
+}
+
+pub fn event_color(event_type: EventType) -> Color {
+    match event_type {
+        EventType::Created => Color::Green,
+        EventType::Copied => Color::Cyan,
+        EventType::Renamed => Color::Yellow,
+        EventType::Moved => Color::Blue,
+        EventType::Deleted => Color::Red,
+    }
+}
+
+pub fn local_time(timestamp: &DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
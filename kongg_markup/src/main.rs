@@ -1,3 +1,23 @@
+use clap::{CommandFactory, Parser};
+use kongg_shared::utils::cli::{DocCommand, complete};
+
+/// Markup for recorded files (not written yet).
+#[derive(Debug, Parser)]
+#[command(name = "kongg_markup", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<DocCommand>,
+}
+
 fn main() {
+    complete(Cli::command);
+    if let Some(command) = Cli::parse().command {
+        if let Err(e) = command.run(Cli::command()) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     println!("Hello, world!");
 }
@@ -0,0 +1,31 @@
+-- Mirrors the SurrealDB `file` table so the same records can be queried
+-- with standard SQL tooling.
+CREATE TABLE IF NOT EXISTS file (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_type TEXT NOT NULL
+        CHECK (event_type IN ('Created', 'Renamed', 'Deleted', 'Moved', 'Copied')),
+    path TEXT NOT NULL,
+    previous_path TEXT,
+    file_name TEXT,
+    extension TEXT,
+    file_size INTEGER,
+    hash TEXT,
+    previous_hash TEXT,
+    file_id TEXT,
+    parent_directory TEXT,
+    permissions TEXT,
+    is_read_only INTEGER NOT NULL DEFAULT 0,
+    -- fixed width RFC 3339 in UTC, so text order is time order
+    timestamp TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS file_path ON file (path);
+CREATE INDEX IF NOT EXISTS file_file_name ON file (file_name);
+CREATE INDEX IF NOT EXISTS file_extension ON file (extension);
+CREATE INDEX IF NOT EXISTS file_event_type ON file (event_type);
+CREATE INDEX IF NOT EXISTS file_timestamp ON file (timestamp);
+
+CREATE TABLE IF NOT EXISTS status (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE file ADD COLUMN root TEXT;
+
+CREATE INDEX IF NOT EXISTS file_root ON file (root);
@@ -0,0 +1,25 @@
+-- Words of the path as produced by search::tokenize, filled in by the
+-- store on insert (and for older rows when the store is opened).
+ALTER TABLE file ADD COLUMN search_text TEXT;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS file_search USING fts5(
+    search_text,
+    content = 'file',
+    content_rowid = 'id',
+    prefix = '2 3'
+);
+
+CREATE TRIGGER IF NOT EXISTS file_search_insert AFTER INSERT ON file BEGIN
+    INSERT INTO file_search (rowid, search_text) VALUES (new.id, new.search_text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS file_search_delete AFTER DELETE ON file BEGIN
+    INSERT INTO file_search (file_search, rowid, search_text)
+        VALUES ('delete', old.id, old.search_text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS file_search_update AFTER UPDATE OF search_text ON file BEGIN
+    INSERT INTO file_search (file_search, rowid, search_text)
+        VALUES ('delete', old.id, old.search_text);
+    INSERT INTO file_search (rowid, search_text) VALUES (new.id, new.search_text);
+END;
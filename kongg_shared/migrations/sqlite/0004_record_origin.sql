@@ -0,0 +1,6 @@
+ALTER TABLE file ADD COLUMN host TEXT;
+-- "<host>:<id>" of an imported record, NULL for events captured here
+ALTER TABLE file ADD COLUMN origin TEXT;
+
+CREATE INDEX IF NOT EXISTS file_host ON file (host);
+CREATE INDEX IF NOT EXISTS file_origin ON file (origin);
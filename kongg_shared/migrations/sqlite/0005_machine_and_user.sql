@@ -0,0 +1,6 @@
+ALTER TABLE file ADD COLUMN machine_id TEXT;
+-- owner of the file, or the user kongg ran as when it couldn't be read
+ALTER TABLE file ADD COLUMN username TEXT;
+
+CREATE INDEX IF NOT EXISTS file_machine_id ON file (machine_id);
+CREATE INDEX IF NOT EXISTS file_username ON file (username);
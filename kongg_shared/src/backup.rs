@@ -0,0 +1,146 @@
+//! Snapshots of the event log as gzip-compressed NDJSON: a header line, one
+//! [`ExportRecord`] per line and a trailer with the record count, so a
+//! truncated file is noticed on restore. Snapshots are independent of the
+//! backend they were taken from, and in clear text even when the store is
+//! encrypted.
+
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Result,
+    export::ExportRecord,
+    models::query::FileQuery,
+    store::{Store, ensure_daemon_stopped},
+    utils::identity::host_name,
+};
+
+/// Bumped when the layout of a snapshot changes.
+const BACKUP_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupHeader {
+    pub format: u32,
+    pub schema_version: u32,
+    /// Events after this instant are not part of the snapshot.
+    pub taken_at: DateTime<Utc>,
+    pub host: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupTrailer {
+    records: u64,
+}
+
+#[derive(Debug)]
+pub struct BackupSummary {
+    pub header: BackupHeader,
+    pub records: u64,
+}
+
+fn json_error(e: serde_json::Error) -> Error {
+    Error::Parse(e.to_string())
+}
+
+fn write_line(out: &mut impl Write, value: &impl Serialize) -> Result<()> {
+    serde_json::to_writer(&mut *out, value).map_err(json_error)?;
+    Ok(out.write_all(b"\n")?)
+}
+
+/// Writes a snapshot of every event, read as of one instant so the daemon
+/// can keep running.
+pub async fn backup(store: &dyn Store, out: impl Write) -> Result<BackupSummary> {
+    let header = BackupHeader {
+        format: BACKUP_FORMAT,
+        schema_version: store.schema_version().await?,
+        taken_at: Utc::now(),
+        host: host_name(),
+    };
+
+    let mut out = BufWriter::new(GzEncoder::new(out, Compression::default()));
+    write_line(&mut out, &header)?;
+
+    let mut records = 0;
+    for file in store.snapshot(header.taken_at).await? {
+        write_line(&mut out, &ExportRecord::from(file))?;
+        records += 1;
+    }
+    write_line(&mut out, &BackupTrailer { records })?;
+
+    out.into_inner()
+        .map_err(|e| Error::Io(e.into_error()))?
+        .finish()?
+        .flush()?;
+    Ok(BackupSummary { header, records })
+}
+
+/// Loads a snapshot into `store`, all or nothing. The store has to be
+/// empty unless `replace` is set, in which case its events are swapped for
+/// the snapshot's in the same transaction. Records get new ids; everything
+/// else is kept as it was. The whole file is read and checked before
+/// anything is written, so a damaged one leaves the store as it was.
+pub async fn restore(store: &dyn Store, input: impl Read, replace: bool) -> Result<BackupSummary> {
+    ensure_daemon_stopped(store, "restoring a backup").await?;
+    let mut lines = BufReader::new(GzDecoder::new(input)).lines();
+    let invalid = |what: &str| Error::Parse(format!("not a kongg backup: {}", what));
+
+    let header: BackupHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?).map_err(|_| invalid("missing header"))?,
+        None => return Err(invalid("empty file")),
+    };
+    if header.format > BACKUP_FORMAT {
+        return Err(invalid(&format!(
+            "format {} is newer than this build",
+            header.format
+        )));
+    }
+
+    let existing = store.query(&FileQuery::new().limit(1)).await?;
+    if !existing.items.is_empty() && !replace {
+        return Err(Error::Config(
+            "the store already has events, restore with replace to overwrite them".to_string(),
+        ));
+    }
+
+    let mut records = 0;
+    let mut trailer = None;
+    let mut files = Vec::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        if let Ok(end) = serde_json::from_str::<BackupTrailer>(&line) {
+            trailer = Some(end);
+            break;
+        }
+
+        let record: ExportRecord = serde_json::from_str(&line)
+            .map_err(|e| Error::Parse(format!("record {}: {}", number + 1, e)))?;
+        files.push(record.into_file()?);
+        records += 1;
+    }
+
+    match trailer {
+        Some(end) if end.records == records => {}
+        Some(end) => {
+            return Err(invalid(&format!(
+                "expected {} records, found {}",
+                end.records, records
+            )));
+        }
+        None => {
+            return Err(invalid(&format!(
+                "the file is truncated after {} records",
+                records
+            )));
+        }
+    }
+
+    if replace {
+        store.replace_all(files).await?;
+    } else {
+        store.insert(files).await?;
+    }
+    Ok(BackupSummary { header, records })
+}
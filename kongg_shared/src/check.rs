@@ -0,0 +1,123 @@
+use surrealdb::RecordId;
+
+use crate::{
+    Result,
+    helpers::inventory::is_under,
+    models::{
+        event::EventType,
+        file::FileResponse,
+        query::{FileQuery, SortField, SortOrder},
+    },
+    store::Store,
+};
+
+/// Rows read per round trip while looking for orphaned records.
+const CHECK_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub schema_version: u32,
+    pub latest_version: u32,
+    /// Records that were read successfully.
+    pub records: u64,
+    /// Records that can't be read back, with the reason.
+    pub malformed: Vec<(RecordId, String)>,
+    /// Records that read fine but don't make sense, with the reason.
+    pub orphaned: Vec<(FileResponse, &'static str)>,
+    /// Orphans can only be looked for once nothing is malformed.
+    pub orphans_skipped: bool,
+    /// Why the saved daemon status can't be read, if it can't.
+    pub status_error: Option<String>,
+    /// Records removed by the repair.
+    pub removed: u64,
+    /// Schema version reached by the repair, when it had to migrate.
+    pub migrated_to: Option<u32>,
+}
+
+impl CheckReport {
+    pub fn is_healthy(&self) -> bool {
+        self.schema_version == self.latest_version
+            && self.malformed.is_empty()
+            && self.orphaned.is_empty()
+            && !self.orphans_skipped
+            && self.status_error.is_none()
+    }
+}
+
+/// Why a record that deserialized fine can't be right, if it can't.
+fn orphan_reason(file: &FileResponse) -> Option<&'static str> {
+    if matches!(file.event_type, EventType::Renamed | EventType::Moved)
+        && file.previous_path.is_none()
+    {
+        return Some("rename or move without a previous path");
+    }
+    if file.origin.is_some() && file.host.is_none() {
+        return Some("imported without a source host");
+    }
+    if let (None, Some(root)) = (&file.origin, &file.root) {
+        let inside = is_under(&file.path, root)
+            || file
+                .previous_path
+                .as_deref()
+                .is_some_and(|p| is_under(p, root));
+        if !inside {
+            return Some("outside the watch root it was captured under");
+        }
+    }
+    None
+}
+
+/// Validates the schema version and every record. With `repair`, pending
+/// migrations are applied and malformed and orphaned records removed.
+pub async fn check(store: &dyn Store, repair: bool) -> Result<CheckReport> {
+    let mut report = CheckReport {
+        schema_version: store.schema_version().await?,
+        latest_version: store.latest_schema_version(),
+        ..CheckReport::default()
+    };
+
+    if repair && report.schema_version < report.latest_version {
+        report.migrated_to = Some(store.migrate().await?);
+    }
+
+    report.malformed = store.malformed().await?;
+    if !report.malformed.is_empty() {
+        if repair {
+            let ids = report.malformed.iter().map(|(id, _)| id.clone()).collect();
+            report.removed += store.delete(ids).await?;
+        } else {
+            // every page containing one of them would fail to read
+            report.orphans_skipped = true;
+        }
+    }
+
+    if !report.orphans_skipped {
+        let mut query = FileQuery::new()
+            .sort(SortField::Timestamp, SortOrder::Asc)
+            .limit(CHECK_PAGE_SIZE);
+        loop {
+            let page = store.query(&query).await?;
+            report.records += page.items.len() as u64;
+            report.orphaned.extend(
+                page.items
+                    .into_iter()
+                    .filter_map(|f| orphan_reason(&f).map(|reason| (f, reason))),
+            );
+            match page.next {
+                Some(cursor) => query = query.after(cursor),
+                None => break,
+            }
+        }
+
+        if repair && !report.orphaned.is_empty() {
+            let ids = report.orphaned.iter().map(|(f, _)| f.id.clone()).collect();
+            report.removed += store.delete(ids).await?;
+        }
+    }
+
+    if let Err(e) = store.status().await {
+        report.status_error = Some(e.to_string());
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use dirs::config_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Result,
+    models::event::EventType,
+    utils::{get_os_home::get_home, size::SizeUnits},
+};
+
+/// Settings read from `<config dir>/kongg/config.toml`. Every section has
+/// defaults, so a missing file is the same as an empty one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageConfig,
+    pub retention: RetentionConfig,
+    pub encryption: EncryptionConfig,
+    pub display: DisplayConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: Backend,
+    /// Location of the database, defaults to a file in the home directory
+    /// named after the backend.
+    pub path: Option<String>,
+}
+
+/// Which events to drop as the log grows. The defaults keep everything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Age in days after which events of a type are removed; types that
+    /// aren't listed are kept forever.
+    pub max_age_days: HashMap<EventType, u64>,
+    /// Types whose repeated events for the same path are reduced to the
+    /// last one of each local day.
+    pub collapse_daily: Vec<EventType>,
+    /// Most events kept per watch root, the oldest go first.
+    pub max_rows_per_root: Option<usize>,
+    /// How often the daemon applies the rules.
+    pub interval_hours: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: HashMap::new(),
+            collapse_daily: Vec::new(),
+            max_rows_per_root: None,
+            interval_hours: 24,
+        }
+    }
+}
+
+/// Encryption at rest of paths, names and hashes. Turned on and rotated
+/// with `kongg_cli db encrypt` and `kongg_cli db rotate-key` rather than by
+/// hand, since existing records have to be rewritten.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    pub key_source: KeySource,
+    /// Where [`KeySource::KeyFile`] keeps the keys, defaults to `keys` next
+    /// to the config file.
+    pub key_file: Option<String>,
+    /// Passphrase keys, the current one first. A second entry only appears
+    /// while a rotation is under way.
+    pub keys: Vec<PassphraseKey>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeySource {
+    /// Derived from a passphrase asked for on start, or taken from
+    /// `KONGG_PASSPHRASE`.
+    #[default]
+    Passphrase,
+    /// Random keys in a file only the user can read.
+    KeyFile,
+}
+
+/// How the CLI and the terminal UI show values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// `si` (`4.7 GB`) or `iec` (`4.4 GiB`).
+    pub size_units: SizeUnits,
+}
+
+/// What's needed to derive a passphrase key again and tell whether the
+/// passphrase was right. The key itself is never written down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassphraseKey {
+    pub id: u32,
+    /// Hex encoded.
+    pub salt: String,
+    /// A known value sealed with the key.
+    pub verifier: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    SurrealKv,
+    Sqlite,
+    /// Nothing is persisted, meant for tests and trying things out.
+    Memory,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        config_dir()
+            .map(|dir| dir.join("kongg").join("config.toml"))
+            .ok_or_else(|| Error::Config("could not determine the config directory".to_string()))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read_to_string(&path).map_err(|e| Error::path(path.display().to_string(), e))?;
+        toml::from_str(&content).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, self.to_toml()?).map_err(|e| Error::path(path.display().to_string(), e))
+    }
+}
+
+impl EncryptionConfig {
+    pub fn resolved_key_file(&self) -> Result<PathBuf> {
+        match &self.key_file {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => Ok(Config::path()?.with_file_name("keys")),
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn resolved_path(&self) -> Result<String> {
+        if let Some(path) = &self.path {
+            return Ok(path.clone());
+        }
+
+        let home = get_home()?;
+        Ok(match self.backend {
+            Backend::SurrealKv => format!("{}/kongg-db", home),
+            Backend::Sqlite => format!("{}/kongg.sqlite3", home),
+            Backend::Memory => String::new(),
+        })
+    }
+}
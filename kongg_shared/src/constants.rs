@@ -1 +1,5 @@
 pub const FILE_TABLE_NAME: &str = "file";
+pub const STATUS_TABLE_NAME: &str = "status";
+pub const DAEMON_STATUS_ID: &str = "daemon";
+pub const META_TABLE_NAME: &str = "meta";
+pub const SCHEMA_VERSION_ID: &str = "schema";
@@ -0,0 +1,321 @@
+//! Sealing of the identifying parts of a record. Values are encrypted with
+//! XChaCha20-Poly1305 under a 256-bit key that is either derived from a
+//! passphrase with Argon2id or kept in a key file, and stored as
+//! `enc1:<key id>:<hex nonce and ciphertext>`, so they still fit the text
+//! columns and records sealed with an older key can be found again.
+
+use std::{
+    env, fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+
+use crate::{
+    Error, Result,
+    config::{EncryptionConfig, KeySource, PassphraseKey},
+};
+
+/// Marks a sealed value; the number is bumped if the layout ever changes.
+const SEALED_PREFIX: &str = "enc1:";
+
+/// Sealed into every passphrase entry, so a wrong passphrase is caught on
+/// unlock instead of on the first record.
+const VERIFIER: &str = "kongg";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// Read instead of prompting, for the daemon and scripts.
+pub const PASSPHRASE_ENV: &str = "KONGG_PASSPHRASE";
+
+pub struct Key {
+    id: u32,
+    bytes: [u8; KEY_LEN],
+    cipher: XChaCha20Poly1305,
+}
+
+// never print the key material
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Key {
+    fn from_bytes(id: u32, bytes: [u8; KEY_LEN]) -> Self {
+        Self {
+            id,
+            bytes,
+            cipher: XChaCha20Poly1305::new(&bytes.into()),
+        }
+    }
+
+    pub fn generate(id: u32) -> Self {
+        let mut bytes = [0; KEY_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        Self::from_bytes(id, bytes)
+    }
+
+    pub fn from_passphrase(id: u32, passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut bytes = [0; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut bytes)
+            .map_err(|e| Error::Crypto(format!("deriving the key failed: {}", e)))?;
+        Ok(Self::from_bytes(id, bytes))
+    }
+
+    pub fn seal(&self, value: &str) -> Result<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| Error::Crypto("sealing a value failed".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!(
+            "{}{}:{}",
+            SEALED_PREFIX,
+            self.id,
+            hex::encode(sealed)
+        ))
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<String> {
+        let wrong_key = || Error::Crypto(format!("a value doesn't open with key {}", self.id));
+        if sealed.len() < NONCE_LEN {
+            return Err(wrong_key());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plain = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| wrong_key())?;
+        String::from_utf8(plain).map_err(|_| wrong_key())
+    }
+}
+
+/// Whether `value` was written by [`Key::seal`].
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// Id of the key and the hex payload of a sealed value.
+fn split_sealed(value: &str) -> Result<(u32, &str)> {
+    value
+        .strip_prefix(SEALED_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(id, payload)| Some((id.parse().ok()?, payload)))
+        .ok_or_else(|| Error::Crypto("a sealed value is damaged".to_string()))
+}
+
+/// Every key a store may have been sealed with. New values are sealed with
+/// the first one; the others are only kept to open what a rotation hasn't
+/// rewritten yet.
+#[derive(Debug)]
+pub struct Keyring {
+    keys: Vec<Key>,
+}
+
+impl Keyring {
+    pub fn new(keys: Vec<Key>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(Error::Config("no encryption key is configured".to_string()));
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn current(&self) -> &Key {
+        &self.keys[0]
+    }
+
+    pub fn seal(&self, value: &str) -> Result<String> {
+        self.current().seal(value)
+    }
+
+    /// The clear text of `value`. Values that were never sealed, like those
+    /// written before encryption was turned on, are returned as they are.
+    pub fn open(&self, value: String) -> Result<String> {
+        if !is_sealed(&value) {
+            return Ok(value);
+        }
+
+        let (id, payload) = split_sealed(&value)?;
+        let key =
+            self.keys.iter().find(|k| k.id == id).ok_or_else(|| {
+                Error::Crypto(format!("sealed with key {}, which is unknown", id))
+            })?;
+        let sealed = hex::decode(payload)
+            .map_err(|_| Error::Crypto("a sealed value is damaged".to_string()))?;
+        key.open(&sealed)
+    }
+
+    /// Whether `value` is sealed with the current key, i.e. doesn't need to
+    /// be rewritten by a rotation.
+    pub fn is_current(&self, value: &str) -> bool {
+        is_sealed(value) && split_sealed(value).is_ok_and(|(id, _)| id == self.current().id)
+    }
+}
+
+/// Asks on the terminal without echoing, unless [`PASSPHRASE_ENV`] is set.
+pub fn prompt_passphrase(label: &str) -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    Ok(rpassword::prompt_password(format!("kongg {}: ", label))?)
+}
+
+/// Loads the keys `config` describes, asking `prompt` for passphrases.
+pub fn unlock(
+    config: &EncryptionConfig,
+    prompt: impl Fn(&str) -> Result<String>,
+) -> Result<Keyring> {
+    let keys = match config.key_source {
+        KeySource::KeyFile => read_key_file(&config.resolved_key_file()?)?,
+        KeySource::Passphrase => config
+            .keys
+            .iter()
+            .map(|entry| {
+                let label = if config.keys.len() > 1 {
+                    format!("passphrase for key {}", entry.id)
+                } else {
+                    "passphrase".to_string()
+                };
+                unlock_entry(entry, &prompt(&label)?)
+            })
+            .collect::<Result<_>>()?,
+    };
+    Keyring::new(keys)
+}
+
+fn unlock_entry(entry: &PassphraseKey, passphrase: &str) -> Result<Key> {
+    let salt = hex::decode(&entry.salt)
+        .map_err(|_| Error::Config(format!("the salt of key {} is damaged", entry.id)))?;
+    let key = Key::from_passphrase(entry.id, passphrase, &salt)?;
+
+    let (_, payload) = split_sealed(&entry.verifier)?;
+    let verified = hex::decode(payload)
+        .ok()
+        .and_then(|sealed| key.open(&sealed).ok())
+        .is_some_and(|value| value == VERIFIER);
+    if !verified {
+        return Err(Error::Crypto(format!(
+            "wrong passphrase for key {}",
+            entry.id
+        )));
+    }
+    Ok(key)
+}
+
+/// Creates the next key, records it in `config` and returns the keyring
+/// with it in front of `previous`. Passphrases are asked for twice.
+pub fn add_key(
+    config: &mut EncryptionConfig,
+    previous: Option<Keyring>,
+    prompt: impl Fn(&str) -> Result<String>,
+) -> Result<Keyring> {
+    let mut keys = previous.map(|k| k.keys).unwrap_or_default();
+    let id = keys
+        .iter()
+        .map(|k| k.id)
+        .chain(config.keys.iter().map(|k| k.id))
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    let key = match config.key_source {
+        KeySource::KeyFile => Key::generate(id),
+        KeySource::Passphrase => {
+            let passphrase = prompt("new passphrase")?;
+            if passphrase.is_empty() {
+                return Err(Error::Config("the passphrase can't be empty".to_string()));
+            }
+            if prompt("new passphrase again")? != passphrase {
+                return Err(Error::Config("the passphrases don't match".to_string()));
+            }
+
+            let mut salt = [0; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = Key::from_passphrase(id, &passphrase, &salt)?;
+            config.keys.insert(
+                0,
+                PassphraseKey {
+                    id,
+                    salt: hex::encode(salt),
+                    verifier: key.seal(VERIFIER)?,
+                },
+            );
+            key
+        }
+    };
+    keys.insert(0, key);
+
+    if config.key_source == KeySource::KeyFile {
+        write_key_file(&config.resolved_key_file()?, &keys)?;
+    }
+    Keyring::new(keys)
+}
+
+/// Forgets every key but the current one, once nothing needs them.
+pub fn retire_keys(config: &mut EncryptionConfig, keyring: &Keyring) -> Result<()> {
+    match config.key_source {
+        KeySource::KeyFile => write_key_file(&config.resolved_key_file()?, &keyring.keys[..1]),
+        KeySource::Passphrase => {
+            config.keys.truncate(1);
+            Ok(())
+        }
+    }
+}
+
+/// One `<id> <hex key>` line per key, the current one first.
+fn read_key_file(path: &Path) -> Result<Vec<Key>> {
+    let content =
+        fs::read_to_string(path).map_err(|e| Error::path(path.display().to_string(), e))?;
+    let damaged = || Error::Config(format!("{}: not a kongg key file", path.display()));
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (id, key) = line.trim().split_once(' ').ok_or_else(damaged)?;
+            let bytes: [u8; KEY_LEN] = hex::decode(key)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(damaged)?;
+            Ok(Key::from_bytes(id.parse().map_err(|_| damaged())?, bytes))
+        })
+        .collect()
+}
+
+fn write_key_file(path: &Path, keys: &[Key]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| Error::path(path.display().to_string(), e))?;
+    for key in keys {
+        writeln!(file, "{} {}", key.id, hex::encode(key.bytes))?;
+    }
+    Ok(file.sync_all()?)
+}
@@ -0,0 +1,96 @@
+//! What was deleted: files whose last event is a deletion, so files
+//! created again afterwards don't show up.
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    Result,
+    models::{event::EventType, file::FileResponse, query::FileQuery},
+    store::{Store, events_touching, scan},
+};
+
+#[derive(Debug, Clone)]
+pub struct DeletedFile {
+    /// The deletion.
+    pub file: FileResponse,
+    /// Size when the file was last seen, since deletions don't record one.
+    pub size: Option<u64>,
+}
+
+impl DeletedFile {
+    /// Nothing is at the path on this machine. Imported deletions happened
+    /// elsewhere and can't be checked, so they never count.
+    pub fn still_missing(&self) -> bool {
+        self.file.origin.is_none() && !Path::new(&self.file.path).exists()
+    }
+}
+
+/// The part of `filter` that applies to the deletion itself. Deletions
+/// carry no size, so sizes are matched later against the last known one.
+fn deletions(filter: &FileQuery) -> FileQuery {
+    let mut matching = filter.clone();
+    matching.event_types = vec![EventType::Deleted];
+    matching.min_size = None;
+    matching.max_size = None;
+    matching
+}
+
+/// Deletions in a log sorted oldest first that weren't followed by the
+/// file coming back, newest first. `filter` applies to the deletion,
+/// except that its sizes are matched against the last known size; its
+/// event types are ignored.
+pub fn deleted_files(
+    events: impl IntoIterator<Item = FileResponse>,
+    filter: &FileQuery,
+) -> Vec<DeletedFile> {
+    // last event and last known size per path, imported events per host
+    let mut last: HashMap<(Option<String>, String), DeletedFile> = HashMap::new();
+
+    for event in events {
+        let source = event.origin.as_ref().and(event.host.clone());
+        let carried = match event.event_type {
+            EventType::Renamed | EventType::Moved => event
+                .previous_path
+                .as_ref()
+                .and_then(|p| last.remove(&(source.clone(), p.clone())))
+                .and_then(|d| d.size),
+            _ => None,
+        };
+        let key = (source, event.path.clone());
+        let known = last.get(&key).and_then(|d| d.size);
+        let size = event.file_size.or(carried).or(known);
+        last.insert(key, DeletedFile { file: event, size });
+    }
+
+    let matching = deletions(filter);
+    let mut deleted: Vec<DeletedFile> = last
+        .into_values()
+        .filter(|d| matching.matches(&d.file))
+        .filter(|d| match (filter.min_size, filter.max_size) {
+            (None, None) => true,
+            (min, max) => d.size.is_some_and(|size| {
+                min.is_none_or(|min| size >= min) && max.is_none_or(|max| size <= max)
+            }),
+        })
+        .collect();
+    deleted.sort_by_key(|d| Reverse(d.file.timestamp));
+    deleted
+}
+
+/// Every deleted file matching `filter`, newest first. Only the events of
+/// the paths with a matching deletion are read.
+pub async fn deleted(store: &dyn Store, filter: &FileQuery) -> Result<Vec<DeletedFile>> {
+    let paths: HashSet<String> = scan(store, &deletions(filter))
+        .await?
+        .into_iter()
+        .map(|d| d.path)
+        .collect();
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(deleted_files(events_touching(store, paths).await?, filter))
+}
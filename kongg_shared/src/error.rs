@@ -0,0 +1,81 @@
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Database(surrealdb::Error),
+    Sqlite(sqlx::Error),
+    Io(io::Error),
+    Config(String),
+    /// A stored or user supplied value that doesn't have the expected shape.
+    Parse(String),
+    /// A value that can't be sealed or opened, usually because the key is
+    /// wrong or missing.
+    Crypto(String),
+    /// A path that could not be read, usually because it vanished between
+    /// the event and the metadata lookup.
+    Path {
+        path: String,
+        source: io::Error,
+    },
+}
+
+impl Error {
+    pub fn path(path: impl Into<String>, source: io::Error) -> Self {
+        Error::Path {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Database(e) => write!(f, "database error: {}", e),
+            Error::Sqlite(e) => write!(f, "sqlite error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Config(msg) => write!(f, "config error: {}", msg),
+            Error::Parse(msg) => write!(f, "parse error: {}", msg),
+            Error::Crypto(msg) => write!(f, "encryption error: {}", msg),
+            Error::Path { path, source } => write!(f, "{}: {}", path, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Sqlite(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Config(_) | Error::Parse(_) | Error::Crypto(_) => None,
+            Error::Path { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<surrealdb::Error> for Error {
+    fn from(e: surrealdb::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for Error {
+    fn from(e: sqlx::migrate::MigrateError) -> Self {
+        Error::Sqlite(e.into())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
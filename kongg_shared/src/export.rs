@@ -0,0 +1,192 @@
+use std::{
+    io::{self, BufWriter, Write},
+    str::FromStr,
+};
+
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Result,
+    models::{file::FileResponse, query::FileQuery},
+    store::Store,
+};
+
+/// Rows fetched per round trip, so only one page is held in memory.
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single JSON array.
+    #[default]
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// Header row plus one row per event.
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(Error::Parse(format!("unknown export format `{}`", s))),
+        }
+    }
+}
+
+/// The exported shape of an event. Fields (and CSV columns) keep this
+/// order; new ones are only ever appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub id: String,
+    pub event_type: String,
+    pub path: String,
+    pub previous_path: Option<String>,
+    pub file_name: Option<String>,
+    pub extension: Option<String>,
+    pub file_size: Option<u64>,
+    pub hash: Option<String>,
+    pub previous_hash: Option<String>,
+    pub file_id: Option<String>,
+    pub parent_directory: Option<String>,
+    pub permissions: Option<String>,
+    pub is_read_only: bool,
+    /// RFC 3339 in UTC with microseconds.
+    pub timestamp: String,
+    pub root: Option<String>,
+    /// Machine the event was captured on.
+    pub host: Option<String>,
+    /// `<host>:<id>` on the machine the record came from, for records that
+    /// were themselves imported.
+    pub origin: Option<String>,
+    pub machine_id: Option<String>,
+    pub username: Option<String>,
+}
+
+impl From<FileResponse> for ExportRecord {
+    fn from(file: FileResponse) -> Self {
+        Self {
+            id: file.id.to_string(),
+            event_type: file.event_type.to_string(),
+            path: file.path,
+            previous_path: file.previous_path,
+            file_name: file.file_name,
+            extension: file.extension,
+            file_size: file.file_size,
+            hash: file.hash,
+            previous_hash: file.previous_hash,
+            file_id: file.file_id,
+            parent_directory: file.parent_directory,
+            permissions: file.permissions,
+            is_read_only: file.is_read_only,
+            timestamp: file.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            root: file.root,
+            host: file.host,
+            origin: file.origin,
+            machine_id: file.machine_id,
+            username: file.username,
+        }
+    }
+}
+
+fn json_error(e: serde_json::Error) -> Error {
+    Error::Parse(e.to_string())
+}
+
+/// Where the records go, with whatever framing the format needs.
+enum Sink<W: Write> {
+    Json { out: BufWriter<W>, first: bool },
+    Ndjson(BufWriter<W>),
+    Csv(csv::Writer<W>),
+}
+
+impl<W: Write> Sink<W> {
+    fn new(format: ExportFormat, out: W) -> Result<Self> {
+        Ok(match format {
+            ExportFormat::Json => {
+                let mut out = BufWriter::new(out);
+                out.write_all(b"[")?;
+                Sink::Json { out, first: true }
+            }
+            ExportFormat::Ndjson => Sink::Ndjson(BufWriter::new(out)),
+            ExportFormat::Csv => Sink::Csv(csv::Writer::from_writer(out)),
+        })
+    }
+
+    fn write(&mut self, record: &ExportRecord) -> Result<()> {
+        match self {
+            Sink::Json { out, first } => {
+                let separator: &[u8] = if *first { b"\n  " } else { b",\n  " };
+                out.write_all(separator)?;
+                *first = false;
+                serde_json::to_writer(out, record).map_err(json_error)
+            }
+            Sink::Ndjson(out) => {
+                serde_json::to_writer(&mut *out, record).map_err(json_error)?;
+                Ok(out.write_all(b"\n")?)
+            }
+            Sink::Csv(out) => Ok(out.serialize(record).map_err(io::Error::from)?),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Sink::Json { mut out, .. } => {
+                out.write_all(b"\n]\n")?;
+                Ok(out.flush()?)
+            }
+            Sink::Ndjson(mut out) => Ok(out.flush()?),
+            Sink::Csv(mut out) => Ok(out.flush()?),
+        }
+    }
+}
+
+/// Writes events that are already in hand to `out` and returns how many
+/// were written.
+pub fn write_records(
+    events: impl IntoIterator<Item = FileResponse>,
+    format: ExportFormat,
+    out: impl Write,
+) -> Result<u64> {
+    let mut sink = Sink::new(format, out)?;
+    let mut written = 0;
+    for file in events {
+        sink.write(&ExportRecord::from(file))?;
+        written += 1;
+    }
+    sink.finish()?;
+    Ok(written)
+}
+
+/// Writes every event matching `query` to `out`, one page at a time, and
+/// returns how many were written. The query's limit and cursor are ignored.
+pub async fn export(
+    store: &dyn Store,
+    query: &FileQuery,
+    format: ExportFormat,
+    out: impl Write,
+) -> Result<u64> {
+    let mut query = query.clone().limit(EXPORT_PAGE_SIZE);
+    query.cursor = None;
+
+    let mut sink = Sink::new(format, out)?;
+    let mut written = 0;
+    loop {
+        let page = store.query(&query).await?;
+        for file in page.items {
+            sink.write(&ExportRecord::from(file))?;
+            written += 1;
+        }
+        match page.next {
+            Some(cursor) => query = query.after(cursor),
+            None => break,
+        }
+    }
+    sink.finish()?;
+    Ok(written)
+}
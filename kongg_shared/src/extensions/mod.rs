@@ -1 +0,0 @@
-pub mod format_file_size;
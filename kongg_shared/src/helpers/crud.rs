@@ -1,36 +1,530 @@
-use surrealdb::{RecordId, Surreal, engine::local::Db};
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::{RecordId, Surreal, engine::local::Db, method::Query};
 
 use crate::{
-    constants::FILE_TABLE_NAME,
-    models::file::{File, FileResponse},
+    Result,
+    config::RetentionConfig,
+    constants::{DAEMON_STATUS_ID, FILE_TABLE_NAME, STATUS_TABLE_NAME},
+    helpers::inventory::{is_under, replay},
+    models::{
+        event::EventType,
+        file::{File, FileResponse},
+        query::{FileQuery, Page, SortField, SortKey, SortOrder},
+        status::DaemonStatus,
+    },
+    stats::{Bucket, GroupBy, into_local_days, local_day},
+    utils::glob::{glob_to_regex, matches_name_only},
 };
 
-pub async fn get_file(db: &Surreal<Db>) -> Vec<FileResponse> {
-    // db.set("table", FILE_TABLE_NAME).await.unwrap();
+/// Translates a [`FileQuery`] into parameterised SurrealQL. Every parameter
+/// is always bound; only the conditions that are set reference them.
+pub async fn query_files(db: &Surreal<Db>, query: &FileQuery) -> Result<Page> {
+    query_files_matching(db, query, &[]).await
+}
+
+/// Same as [`query_files`], additionally requiring the path's full-text
+/// index to contain every one of `terms` (as word prefixes).
+pub async fn query_files_matching(
+    db: &Surreal<Db>,
+    query: &FileQuery,
+    terms: &[String],
+) -> Result<Page> {
+    let sort_expr = match query.sort {
+        SortField::Timestamp => "timestamp",
+        SortField::Size => "(file_size ?? 0)",
+        SortField::Name => "string::lowercase(file_name ?? '')",
+        SortField::Path => "path",
+    };
+    let (direction, before) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+
+    let mut conditions = filter_conditions(query, terms);
+    if let Some(cursor) = &query.cursor {
+        let value = match cursor.key {
+            SortKey::Timestamp(_) => "<datetime> $cursor_value",
+            _ => "$cursor_value",
+        };
+        conditions.push(format!(
+            "({sort} {before} {value} OR ({sort} = {value} AND id {before} <record> $cursor_id))",
+            sort = sort_expr,
+            before = before,
+            value = value,
+        ));
+    }
+
+    let sql = format!(
+        "SELECT *, {sort} AS sort_key FROM type::table($table) WHERE {conditions} \
+         ORDER BY sort_key {direction}, id {direction} LIMIT $limit",
+        sort = sort_expr,
+        conditions = conditions.join(" AND "),
+        direction = direction,
+    );
+
+    let request = bind_filters(db.query(sql), query, terms)
+        .bind(("table", FILE_TABLE_NAME))
+        .bind(("limit", query.page_size() + 1))
+        .bind(("cursor_id", query.cursor.as_ref().map(|c| c.id.clone())));
+    let request = match query.cursor.as_ref().map(|c| &c.key) {
+        Some(SortKey::Timestamp(t)) => request.bind(("cursor_value", *t)),
+        Some(SortKey::Number(n)) => request.bind(("cursor_value", *n)),
+        Some(SortKey::Text(s)) => request.bind(("cursor_value", s.clone())),
+        None => request.bind(("cursor_value", None::<String>)),
+    };
+
+    let mut res = request.await?;
+    Ok(query.paginate(res.take(0)?))
+}
+
+/// SurrealQL counterpart of [`FileQuery::matches`], plus the full-text
+/// lookup when `terms` is not empty. The parameters come from
+/// [`bind_filters`].
+fn filter_conditions(query: &FileQuery, terms: &[String]) -> Vec<String> {
+    let mut conditions = vec!["true".to_string()];
+    if !query.event_types.is_empty() {
+        conditions.push("event_type IN $event_types".to_string());
+    }
+    if !query.paths.is_empty() {
+        conditions.push("(path IN $paths OR previous_path IN $paths)".to_string());
+    }
+    if query.path_prefix.is_some() {
+        conditions.push(
+            "(path = $path_prefix OR string::starts_with(path, $path_prefix_dir))".to_string(),
+        );
+    }
+    if let Some(glob) = &query.glob {
+        conditions.push(if matches_name_only(glob) {
+            "string::matches(file_name ?? '', $glob)".to_string()
+        } else {
+            "string::matches(path, $glob)".to_string()
+        });
+    }
+    if !query.extensions.is_empty() {
+        conditions.push("string::lowercase(extension ?? '') IN $extensions".to_string());
+    }
+    if query.min_size.is_some() {
+        conditions.push("(file_size ?? 0) >= $min_size".to_string());
+    }
+    if query.max_size.is_some() {
+        conditions.push("(file_size ?? 0) <= $max_size".to_string());
+    }
+    if query.since.is_some() {
+        conditions.push("timestamp >= <datetime> $since".to_string());
+    }
+    if query.until.is_some() {
+        conditions.push("timestamp < <datetime> $until".to_string());
+    }
+    if query.root.is_some() {
+        conditions.push("root = $root".to_string());
+    }
+    if query.host.is_some() {
+        conditions.push("host = $host".to_string());
+    }
+    if query.machine_id.is_some() {
+        conditions.push("machine_id = $machine_id".to_string());
+    }
+    if query.username.is_some() {
+        conditions.push("username = $username".to_string());
+    }
+    if query.text.is_some() {
+        conditions.push(
+            "(string::contains(string::lowercase(path), $text) \
+             OR string::contains(string::lowercase(file_name ?? ''), $text))"
+                .to_string(),
+        );
+    }
+    if !terms.is_empty() {
+        conditions.push("path @@ $terms".to_string());
+    }
+    conditions
+}
+
+fn bind_filters<'r>(request: Query<'r, Db>, query: &FileQuery, terms: &[String]) -> Query<'r, Db> {
+    let path_prefix = query.path_prefix.clone().unwrap_or_default();
+    let path_prefix_dir = format!("{}/", path_prefix.trim_end_matches(['/', '\\']));
+    request
+        .bind(("event_types", query.event_types.clone()))
+        .bind(("paths", query.paths.clone()))
+        .bind(("path_prefix", path_prefix))
+        .bind(("path_prefix_dir", path_prefix_dir))
+        .bind(("glob", query.glob.as_deref().map(glob_to_regex)))
+        .bind(("extensions", query.extensions.clone()))
+        .bind(("min_size", query.min_size))
+        .bind(("max_size", query.max_size))
+        .bind(("since", query.since))
+        .bind(("until", query.until))
+        .bind(("root", query.root.clone()))
+        .bind(("host", query.host.clone()))
+        .bind(("machine_id", query.machine_id.clone()))
+        .bind(("username", query.username.clone()))
+        .bind(("text", query.text.clone()))
+        .bind(("terms", terms.join(" ")))
+}
+
+/// Size the file at the current record's path last had before it, from
+/// the same log: the local one, or the events imported from the same host.
+const SIZE_BEFORE: &str = "(SELECT id, file_size, timestamp FROM type::table($table) \
+    WHERE path = $parent.path AND file_size != NONE \
+    AND (timestamp < $parent.timestamp \
+    OR (timestamp = $parent.timestamp AND id < $parent.id)) \
+    AND (origin IS NONE) = ($parent.origin IS NONE) \
+    AND ($parent.origin IS NONE OR host = $parent.host) \
+    ORDER BY timestamp DESC, id DESC LIMIT 1)[0].file_size";
+
+/// Events matching `filter` counted per `group` and event type, with
+/// deletions weighing the size the file had before.
+pub async fn get_aggregate(
+    db: &Surreal<Db>,
+    filter: &FileQuery,
+    group: GroupBy,
+) -> Result<Vec<Bucket>> {
+    let key = match group {
+        // folded into local days below
+        GroupBy::Day => "time::format(time::floor(timestamp, 15m), '%Y-%m-%dT%H:%M:%SZ')",
+        GroupBy::Directory => "parent_directory ?? ''",
+        GroupBy::Extension => "string::lowercase(extension ?? '')",
+    };
+    let sql = format!(
+        "SELECT key, event_type, count() AS count, math::sum(bytes) AS bytes FROM \
+         (SELECT {key} AS key, event_type, \
+         IF event_type = 'Deleted' THEN ({size_before} ?? 0) ELSE (file_size ?? 0) END AS bytes \
+         FROM type::table($table) WHERE {conditions}) \
+         GROUP BY key, event_type ORDER BY key, event_type",
+        key = key,
+        size_before = SIZE_BEFORE,
+        conditions = filter_conditions(filter, &[]).join(" AND "),
+    );
+
+    let mut res = bind_filters(db.query(sql), filter, &[])
+        .bind(("table", FILE_TABLE_NAME))
+        .await?;
+    let buckets: Vec<Bucket> = res.take(0)?;
+    Ok(match group {
+        GroupBy::Day => into_local_days(buckets),
+        _ => buckets,
+    })
+}
+
+#[derive(Deserialize)]
+struct SizedDeletion {
+    id: RecordId,
+    size_before: u64,
+}
+
+/// Deletions matching `filter` with the size the file had before, largest
+/// first.
+pub async fn get_largest_deleted(
+    db: &Surreal<Db>,
+    filter: &FileQuery,
+    limit: usize,
+) -> Result<Vec<(FileResponse, u64)>> {
+    let sql = format!(
+        "SELECT id, size_before FROM \
+         (SELECT id, {size_before} AS size_before FROM type::table($table) \
+         WHERE event_type = 'Deleted' AND {conditions}) \
+         WHERE size_before > 0 ORDER BY size_before DESC LIMIT $limit",
+        size_before = SIZE_BEFORE,
+        conditions = filter_conditions(filter, &[]).join(" AND "),
+    );
+    let mut res = bind_filters(db.query(sql), filter, &[])
+        .bind(("table", FILE_TABLE_NAME))
+        .bind(("limit", limit))
+        .await?;
+    let sized: Vec<SizedDeletion> = res.take(0)?;
+
+    let ids: Vec<RecordId> = sized.iter().map(|s| s.id.clone()).collect();
+    let mut res = db.query("SELECT * FROM $ids").bind(("ids", ids)).await?;
+    let files: Vec<FileResponse> = res.take(0)?;
+    let mut files: HashMap<RecordId, FileResponse> =
+        files.into_iter().map(|f| (f.id.clone(), f)).collect();
+    Ok(sized
+        .into_iter()
+        .filter_map(|s| files.remove(&s.id).map(|f| (f, s.size_before)))
+        .collect())
+}
+
+pub async fn create_file(db: &Surreal<Db>, file: File) -> Result<()> {
+    let _res: Option<FileResponse> = db.create(FILE_TABLE_NAME).content(file).await?;
+    Ok(())
+}
+
+/// Inserts a batch of events inside a single transaction, so a burst of
+/// events costs one round trip instead of one per file.
+pub async fn create_files(db: &Surreal<Db>, files: Vec<File>) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    db.query(format!(
+        "BEGIN TRANSACTION; INSERT INTO {} $files; COMMIT TRANSACTION;",
+        FILE_TABLE_NAME
+    ))
+    .bind(("files", files))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// Removes every event and inserts `files` in a single transaction.
+pub async fn replace_files(db: &Surreal<Db>, files: Vec<File>) -> Result<()> {
+    db.query(format!(
+        "BEGIN TRANSACTION; DELETE {table}; INSERT INTO {table} $files; COMMIT TRANSACTION;",
+        table = FILE_TABLE_NAME
+    ))
+    .bind(("files", files))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Replacement {
+    id: RecordId,
+    file: File,
+}
+
+/// Replaces the content of existing records in a single transaction.
+pub async fn update_files(db: &Surreal<Db>, records: Vec<(RecordId, File)>) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let records: Vec<Replacement> = records
+        .into_iter()
+        .map(|(id, file)| Replacement { id, file })
+        .collect();
+    db.query(
+        "BEGIN TRANSACTION; \
+         FOR $record IN $records { UPDATE $record.id CONTENT $record.file; }; \
+         COMMIT TRANSACTION;",
+    )
+    .bind(("records", records))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// Every event touching `root` (by current or previous path), oldest first.
+/// With `at`, only the events up to that instant, read from the SurrealKV
+/// version as of then so that records pruned since are still seen.
+pub async fn get_events_under(
+    db: &Surreal<Db>,
+    root: &str,
+    at: Option<DateTime<Utc>>,
+) -> Result<Vec<FileResponse>> {
+    let (until, version) = match at {
+        // formatted by chrono, so nothing user-supplied reaches the SQL
+        Some(at) => (
+            " AND timestamp <= <datetime> $at",
+            format!(
+                " VERSION d'{}'",
+                at.to_rfc3339_opts(SecondsFormat::Micros, true)
+            ),
+        ),
+        None => ("", String::new()),
+    };
+
+    let mut res = db
+        .query(format!(
+            "SELECT * FROM type::table($table) \
+             WHERE (string::starts_with(path, $root) \
+             OR string::starts_with(previous_path ?? '', $root)) AND origin IS NONE{} \
+             ORDER BY timestamp ASC{}",
+            until, version
+        ))
+        .bind(("table", FILE_TABLE_NAME))
+        .bind(("root", root.to_string()))
+        .bind(("at", at))
+        .await?;
+    Ok(res.take(0)?)
+}
+
+/// Every event before `until`, oldest first, read from the SurrealKV
+/// version as of then so that writes and prunes since don't show through.
+pub async fn get_snapshot(db: &Surreal<Db>, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+    // formatted by chrono, so nothing user-supplied reaches the SQL
+    let mut res = db
+        .query(format!(
+            "SELECT * FROM type::table($table) WHERE timestamp < <datetime> $until \
+             ORDER BY timestamp ASC, id ASC VERSION d'{}'",
+            until.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ))
+        .bind(("table", FILE_TABLE_NAME))
+        .bind(("until", until))
+        .await?;
+    Ok(res.take(0)?)
+}
+
+/// Replays the log for everything under `root` and returns the latest
+/// record of each path that existed on disk at `at` (now when `None`).
+pub async fn get_inventory(
+    db: &Surreal<Db>,
+    root: &str,
+    at: Option<DateTime<Utc>>,
+) -> Result<Vec<FileResponse>> {
+    let mut files = replay(get_events_under(db, root, at).await?);
+    files.retain(|f| is_under(&f.path, root));
+    Ok(files)
+}
+
+#[derive(Deserialize)]
+struct PathQuarter {
+    path: String,
+    event_type: EventType,
+    quarter: DateTime<Utc>,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct RootCount {
+    root: Option<String>,
+    count: u64,
+}
+
+/// Paths with events the retention rules may remove, see
+/// [`crate::store::Store::prunable_paths`].
+pub async fn get_prunable_paths(
+    db: &Surreal<Db>,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Result<HashSet<String>> {
+    let mut paths = HashSet::new();
+
+    for (event_type, days) in &config.max_age_days {
+        let mut res = db
+            .query(
+                "SELECT VALUE path FROM type::table($table) \
+                 WHERE event_type = $event_type AND timestamp < <datetime> $before",
+            )
+            .bind(("table", FILE_TABLE_NAME))
+            .bind(("event_type", *event_type))
+            .bind(("before", now - Duration::days(*days as i64)))
+            .await?;
+        let expired: Vec<String> = res.take(0)?;
+        paths.extend(expired);
+    }
+
+    if !config.collapse_daily.is_empty() {
+        // counted per quarter hour, the finest step of a UTC offset, and
+        // folded into local days here
+        let mut res = db
+            .query(
+                "SELECT path, event_type, quarter, count() AS count FROM \
+                 (SELECT path, event_type, time::floor(timestamp, 15m) AS quarter \
+                 FROM type::table($table) WHERE event_type IN $event_types) \
+                 GROUP BY path, event_type, quarter",
+            )
+            .bind(("table", FILE_TABLE_NAME))
+            .bind(("event_types", config.collapse_daily.clone()))
+            .await?;
+        let quarters: Vec<PathQuarter> = res.take(0)?;
+        let mut per_day: HashMap<(String, EventType, String), u64> = HashMap::new();
+        for quarter in quarters {
+            let day = local_day(&quarter.quarter);
+            *per_day
+                .entry((quarter.path, quarter.event_type, day))
+                .or_default() += quarter.count;
+        }
+        paths.extend(
+            per_day
+                .into_iter()
+                .filter(|(_, count)| *count > 1)
+                .map(|((path, _, _), _)| path),
+        );
+    }
+
+    if let Some(cap) = config.max_rows_per_root {
+        let mut res = db
+            .query("SELECT root, count() AS count FROM type::table($table) GROUP BY root")
+            .bind(("table", FILE_TABLE_NAME))
+            .await?;
+        let roots: Vec<RootCount> = res.take(0)?;
+        let over: Vec<Option<String>> = roots
+            .into_iter()
+            .filter(|r| r.count > cap as u64)
+            .map(|r| r.root)
+            .collect();
+        if !over.is_empty() {
+            let mut res = db
+                .query("SELECT VALUE path FROM type::table($table) WHERE root IN $roots")
+                .bind(("table", FILE_TABLE_NAME))
+                .bind(("roots", over))
+                .await?;
+            let capped: Vec<String> = res.take(0)?;
+            paths.extend(capped);
+        }
+    }
+    Ok(paths)
+}
 
+/// The subset of `origins` already present, for de-duplicating imports.
+pub async fn get_existing_origins(db: &Surreal<Db>, origins: &[String]) -> Result<HashSet<String>> {
     let mut res = db
-        .query("SELECT *  FROM type::table($table) ORDER BY timestamp DESC")
+        .query("SELECT VALUE origin FROM type::table($table) WHERE origin IN $origins")
         .bind(("table", FILE_TABLE_NAME))
-        .await
-        .unwrap();
-    res.take(0).unwrap()
+        .bind(("origins", origins.to_vec()))
+        .await?;
+    let found: Vec<String> = res.take(0)?;
+    Ok(found.into_iter().collect())
+}
+
+/// Records fetched per query while looking for malformed ones.
+const CHECK_CHUNK_SIZE: usize = 500;
+
+/// Records in the file table that don't deserialize into a
+/// [`FileResponse`]. Reads in chunks and only goes record by record in a
+/// chunk that fails, to find the culprits.
+pub async fn get_malformed(db: &Surreal<Db>) -> Result<Vec<(RecordId, String)>> {
+    let mut res = db
+        .query("SELECT VALUE id FROM type::table($table)")
+        .bind(("table", FILE_TABLE_NAME))
+        .await?;
+    let ids: Vec<RecordId> = res.take(0)?;
+
+    let mut malformed = Vec::new();
+    for chunk in ids.chunks(CHECK_CHUNK_SIZE) {
+        let mut res = db
+            .query("SELECT * FROM $ids")
+            .bind(("ids", chunk.to_vec()))
+            .await?;
+        if res.take::<Vec<FileResponse>>(0).is_ok() {
+            continue;
+        }
+
+        for id in chunk {
+            let read: surrealdb::Result<Option<FileResponse>> = db.select(id.clone()).await;
+            if let Err(e) = read {
+                malformed.push((id.clone(), e.to_string()));
+            }
+        }
+    }
+    Ok(malformed)
+}
 
-    // db.select(FILE_TABLE_NAME).await.unwrap()
+pub async fn save_status(db: &Surreal<Db>, status: DaemonStatus) -> Result<()> {
+    let _res: Option<DaemonStatus> = db
+        .upsert((STATUS_TABLE_NAME, DAEMON_STATUS_ID))
+        .content(status)
+        .await?;
+    Ok(())
 }
 
-pub async fn create_file(db: &Surreal<Db>, file: File) -> () {
-    let _res: Option<FileResponse> = db.create(FILE_TABLE_NAME).content(file).await.unwrap();
-    ()
+pub async fn get_status(db: &Surreal<Db>) -> Result<Option<DaemonStatus>> {
+    Ok(db.select((STATUS_TABLE_NAME, DAEMON_STATUS_ID)).await?)
 }
 
 #[warn(dead_code)]
-async fn _update_file(db: &Surreal<Db>, file: File, id: RecordId) -> () {
-    let _res: Option<FileResponse> = db.update(id).merge(file).await.unwrap();
-    ()
+async fn _update_file(db: &Surreal<Db>, file: File, id: RecordId) -> Result<()> {
+    let _res: Option<FileResponse> = db.update(id).merge(file).await?;
+    Ok(())
 }
 
 #[warn(dead_code)]
-async fn _delete_file(db: &Surreal<Db>, id: RecordId) -> () {
-    let _res: Option<FileResponse> = db.delete(id).await.unwrap();
-    ()
+async fn _delete_file(db: &Surreal<Db>, id: RecordId) -> Result<()> {
+    let _res: Option<FileResponse> = db.delete(id).await?;
+    Ok(())
 }
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use crate::models::{event::EventType, file::FileResponse};
+
+/// Folds an event log (oldest first) into the latest record of every path
+/// that still exists at the end of it, sorted by path.
+pub fn replay(events: impl IntoIterator<Item = FileResponse>) -> Vec<FileResponse> {
+    let mut live: HashMap<String, FileResponse> = HashMap::new();
+
+    for event in events {
+        match event.event_type {
+            EventType::Created | EventType::Copied => {
+                live.insert(event.path.clone(), event);
+            }
+            EventType::Renamed | EventType::Moved => {
+                if let Some(previous) = &event.previous_path {
+                    live.remove(previous);
+                }
+                live.insert(event.path.clone(), event);
+            }
+            EventType::Deleted => {
+                live.remove(&event.path);
+            }
+        }
+    }
+
+    let mut files: Vec<FileResponse> = live.into_values().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Whether `path` is `root` itself or somewhere below it.
+pub fn is_under(path: &str, root: &str) -> bool {
+    let root = root.trim_end_matches(['/', '\\']);
+    path == root
+        || path
+            .strip_prefix(root)
+            .is_some_and(|rest| rest.starts_with(['/', '\\']))
+}
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+use surrealdb::{Surreal, engine::local::Db};
+
+use crate::{
+    Result,
+    constants::{META_TABLE_NAME, SCHEMA_VERSION_ID},
+};
+
+/// A single schema step. Migrations are append-only: once released, a
+/// migration's SQL must never change, add a new one instead.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        sql: "
+        DEFINE TABLE IF NOT EXISTS file SCHEMAFULL;
+        DEFINE FIELD IF NOT EXISTS event_type ON file TYPE string
+            ASSERT $value IN ['Created', 'Renamed', 'Deleted', 'Moved', 'Copied'];
+        DEFINE FIELD IF NOT EXISTS path ON file TYPE string;
+        DEFINE FIELD IF NOT EXISTS previous_path ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS file_name ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS extension ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS file_size ON file TYPE option<int>;
+        DEFINE FIELD IF NOT EXISTS hash ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS previous_hash ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS file_id ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS parent_directory ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS permissions ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS is_read_only ON file TYPE bool DEFAULT false;
+        -- chrono serialises timestamps as strings, cast them on the way in
+        DEFINE FIELD IF NOT EXISTS timestamp ON file TYPE datetime VALUE <datetime> $value;
+
+        DEFINE INDEX IF NOT EXISTS file_path ON file FIELDS path;
+        DEFINE INDEX IF NOT EXISTS file_file_name ON file FIELDS file_name;
+        DEFINE INDEX IF NOT EXISTS file_extension ON file FIELDS extension;
+        DEFINE INDEX IF NOT EXISTS file_event_type ON file FIELDS event_type;
+        DEFINE INDEX IF NOT EXISTS file_timestamp ON file FIELDS timestamp;
+
+        DEFINE TABLE IF NOT EXISTS status SCHEMALESS;
+
+        -- rows written before the schema existed hold string timestamps
+        UPDATE file SET timestamp = timestamp WHERE type::is::string(timestamp);
+    ",
+    },
+    Migration {
+        version: 2,
+        name: "watch root",
+        sql: "
+        DEFINE FIELD IF NOT EXISTS root ON file TYPE option<string>;
+        DEFINE INDEX IF NOT EXISTS file_root ON file FIELDS root;
+    ",
+    },
+    Migration {
+        version: 3,
+        name: "path search index",
+        sql: "
+        -- split like search::tokenize, and index word prefixes so that
+        -- half-typed words are found
+        DEFINE ANALYZER IF NOT EXISTS file_path_words
+            TOKENIZERS blank, class, camel, punct
+            FILTERS lowercase, edgengram(2, 20);
+        DEFINE INDEX IF NOT EXISTS file_path_search ON file FIELDS path
+            SEARCH ANALYZER file_path_words BM25;
+    ",
+    },
+    Migration {
+        version: 4,
+        name: "record origin",
+        sql: "
+        DEFINE FIELD IF NOT EXISTS host ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS origin ON file TYPE option<string>;
+        DEFINE INDEX IF NOT EXISTS file_host ON file FIELDS host;
+        DEFINE INDEX IF NOT EXISTS file_origin ON file FIELDS origin;
+    ",
+    },
+    Migration {
+        version: 5,
+        name: "machine and user",
+        sql: "
+        DEFINE FIELD IF NOT EXISTS machine_id ON file TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS username ON file TYPE option<string>;
+        DEFINE INDEX IF NOT EXISTS file_machine_id ON file FIELDS machine_id;
+        DEFINE INDEX IF NOT EXISTS file_username ON file FIELDS username;
+    ",
+    },
+];
+
+#[derive(Debug, Deserialize)]
+struct SchemaVersion {
+    version: u32,
+}
+
+/// Version of the last migration applied, `0` for a fresh database.
+pub async fn current_version(db: &Surreal<Db>) -> Result<u32> {
+    let res: Option<SchemaVersion> = db.select((META_TABLE_NAME, SCHEMA_VERSION_ID)).await?;
+    Ok(res.map(|v| v.version).unwrap_or(0))
+}
+
+/// Version of the newest migration this build knows about.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Applies every pending migration in order, each in its own transaction
+/// together with the version bump, and returns the resulting version.
+pub async fn migrate(db: &Surreal<Db>) -> Result<u32> {
+    let mut version = current_version(db).await?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        db.query(format!(
+            "BEGIN TRANSACTION;
+            {}
+            UPSERT type::thing($meta, $id) SET version = $version, name = $name, migrated_at = time::now();
+            COMMIT TRANSACTION;",
+            migration.sql
+        ))
+        .bind(("meta", META_TABLE_NAME))
+        .bind(("id", SCHEMA_VERSION_ID))
+        .bind(("version", migration.version))
+        .bind(("name", migration.name))
+        .await?
+        .check()?;
+
+        version = migration.version;
+    }
+
+    Ok(version)
+}
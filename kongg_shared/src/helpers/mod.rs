@@ -1,2 +1,4 @@
 pub mod crud;
+pub mod inventory;
+pub mod migrations;
 pub mod surreal_init;
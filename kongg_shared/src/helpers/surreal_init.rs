@@ -1,22 +1,21 @@
 use surrealdb::{
-    Error, Surreal,
+    Surreal,
     engine::local::{Db, SurrealKv},
 };
 
-use crate::{constants::FILE_TABLE_NAME, utils::get_os_home::get_home};
+use crate::{Result, helpers::migrations::migrate, utils::get_os_home::get_home};
 
-pub async fn init() -> Result<Surreal<Db>, Error> {
-    let home = get_home().unwrap();
+pub async fn init() -> Result<Surreal<Db>> {
+    let home = get_home()?;
+    init_at(&format!("{}/kongg-db", home)).await
+}
 
-    let db = Surreal::new::<SurrealKv>(format!("{}/kongg-db", home))
-        .versioned()
-        .await?;
+pub async fn init_at(path: &str) -> Result<Surreal<Db>> {
+    let db = Surreal::new::<SurrealKv>(path).versioned().await?;
     db.use_ns("test").use_db("test").await?;
 
-    // let a = db
-    //     .query(format!("REMOVE TABLE {};", FILE_TABLE_NAME))
-    //     .await
-    //     .unwrap();
+    // Bring older databases up to date instead of wiping them
+    migrate(&db).await?;
 
     Ok(db)
 }
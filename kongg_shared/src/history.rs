@@ -0,0 +1,181 @@
+//! The life of one file: every event since it appeared, followed through
+//! renames and moves by `previous_path`, so an old name leads to the same
+//! history as the current one.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    Result,
+    models::{event::EventType, file::FileResponse},
+    store::{Store, events_touching},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Created,
+    /// Written again while it existed.
+    Updated,
+    /// Created again at the same path after it was deleted.
+    Recreated,
+    Renamed,
+    Moved,
+    /// Came to be as a copy of `previous_path`.
+    CopiedFrom,
+    /// Was copied to `path`; the copy has a life of its own.
+    CopiedTo,
+    Deleted,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Step::Created => "created",
+            Step::Updated => "updated",
+            Step::Recreated => "recreated",
+            Step::Renamed => "renamed",
+            Step::Moved => "moved",
+            Step::CopiedFrom => "copied from",
+            Step::CopiedTo => "copied to",
+            Step::Deleted => "deleted",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub step: Step,
+    pub file: FileResponse,
+    /// Bytes gained (or lost) since the last event that knew the size.
+    pub size_delta: Option<i64>,
+    /// The content differs from what the last known hash says.
+    pub hash_changed: bool,
+}
+
+/// Events of one file and what was last known about it.
+#[derive(Default)]
+struct Lineage {
+    entries: Vec<HistoryEntry>,
+    paths: HashSet<String>,
+    exists: bool,
+    size: Option<u64>,
+    hash: Option<String>,
+}
+
+impl Lineage {
+    fn push(&mut self, step: Step, file: FileResponse) {
+        if step == Step::CopiedTo {
+            // the copy's path and content belong to the copy
+            self.entries.push(HistoryEntry {
+                step,
+                file,
+                size_delta: None,
+                hash_changed: false,
+            });
+            return;
+        }
+
+        let size_delta = self
+            .size
+            .zip(file.file_size)
+            .map(|(before, after)| after as i64 - before as i64);
+        let hash_changed = match (&self.hash, &file.hash) {
+            (Some(before), Some(after)) => before != after,
+            _ => file.previous_hash.is_some() && file.previous_hash != file.hash,
+        };
+
+        self.exists = step != Step::Deleted;
+        if self.exists {
+            self.size = file.file_size.or(self.size);
+            self.hash = file.hash.clone().or(self.hash.take());
+        } else {
+            // a recreated file starts over
+            self.size = None;
+            self.hash = None;
+        }
+        self.paths.insert(file.path.clone());
+        if step != Step::CopiedFrom {
+            self.paths.extend(file.previous_path.clone());
+        }
+        self.entries.push(HistoryEntry {
+            step,
+            file,
+            size_delta,
+            hash_changed,
+        });
+    }
+}
+
+fn start(lineages: &mut Vec<Lineage>) -> usize {
+    lineages.push(Lineage::default());
+    lineages.len() - 1
+}
+
+/// Splits a log sorted oldest first into the lives of its files and
+/// returns those that ever had `path`, merged oldest first. A file deleted
+/// and created again at the same path is the same file.
+pub fn trace(events: impl IntoIterator<Item = FileResponse>, path: &str) -> Vec<HistoryEntry> {
+    let mut lineages: Vec<Lineage> = Vec::new();
+    // events captured here form one log, imported ones one per source host
+    let mut by_path: HashMap<(Option<String>, String), usize> = HashMap::new();
+
+    for event in events {
+        let source = event.origin.as_ref().and(event.host.clone());
+        let key = |path: &str| (source.clone(), path.to_string());
+        let previous = event.previous_path.as_deref().map(key);
+
+        let (lineage, step) = match event.event_type {
+            EventType::Renamed | EventType::Moved => {
+                let lineage = previous
+                    .and_then(|p| by_path.remove(&p))
+                    .unwrap_or_else(|| start(&mut lineages));
+                let step = if event.event_type == EventType::Renamed {
+                    Step::Renamed
+                } else {
+                    Step::Moved
+                };
+                (lineage, step)
+            }
+            EventType::Copied => {
+                let original = previous.and_then(|p| by_path.get(&p).copied());
+                if let Some(original) = original {
+                    lineages[original].push(Step::CopiedTo, event.clone());
+                }
+                let step = if event.previous_path.is_some() {
+                    Step::CopiedFrom
+                } else {
+                    Step::Created
+                };
+                (start(&mut lineages), step)
+            }
+            EventType::Created => match by_path.get(&key(&event.path)) {
+                Some(&lineage) if lineages[lineage].exists => (lineage, Step::Updated),
+                Some(&lineage) => (lineage, Step::Recreated),
+                None => (start(&mut lineages), Step::Created),
+            },
+            EventType::Deleted => match by_path.get(&key(&event.path)) {
+                Some(&lineage) => (lineage, Step::Deleted),
+                None => (start(&mut lineages), Step::Deleted),
+            },
+        };
+
+        by_path.insert(key(&event.path), lineage);
+        lineages[lineage].push(step, event);
+    }
+
+    let mut entries: Vec<HistoryEntry> = lineages
+        .into_iter()
+        .filter(|l| l.paths.contains(path))
+        .flat_map(|l| l.entries)
+        .collect();
+    entries.sort_by_key(|e| e.file.timestamp);
+    entries
+}
+
+/// Every event of the file that has, or once had, `path`, oldest first.
+pub async fn history(store: &dyn Store, path: &str) -> Result<Vec<HistoryEntry>> {
+    let events = events_touching(store, [path.to_string()]).await?;
+    Ok(trace(events, path))
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashSet, io::BufRead, str::FromStr};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Error, Result,
+    export::ExportRecord,
+    models::{event::EventType, file::File},
+    store::Store,
+};
+
+/// Records checked against the store and inserted together.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Records read from the input.
+    pub read: u64,
+    pub imported: u64,
+    /// Records skipped because the store (or the input) already had them.
+    pub duplicates: u64,
+}
+
+impl ExportRecord {
+    /// Turns an exported record back into an event, exactly as it was.
+    pub(crate) fn into_file(self) -> Result<File> {
+        let timestamp = DateTime::parse_from_rfc3339(&self.timestamp)
+            .map_err(|e| Error::Parse(format!("timestamp `{}`: {}", self.timestamp, e)))?
+            .with_timezone(&Utc);
+
+        Ok(File {
+            event_type: EventType::from_str(&self.event_type)?,
+            path: self.path,
+            previous_path: self.previous_path,
+            file_name: self.file_name,
+            extension: self.extension,
+            file_size: self.file_size,
+            hash: self.hash,
+            previous_hash: self.previous_hash,
+            file_id: self.file_id,
+            parent_directory: self.parent_directory,
+            permissions: self.permissions,
+            is_read_only: self.is_read_only,
+            timestamp,
+            root: self.root,
+            host: self.host,
+            machine_id: self.machine_id,
+            username: self.username,
+            origin: self.origin,
+        })
+    }
+
+    /// The event attributed to `host`. Its identity is `<host>:<id>`,
+    /// unless it was itself imported, in which case it keeps the identity
+    /// it already had.
+    fn into_imported(self, host: String) -> Result<File> {
+        let origin = self
+            .origin
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", host, self.id));
+
+        let mut file = self.into_file()?;
+        file.host = Some(host);
+        file.origin = Some(origin);
+        Ok(file)
+    }
+}
+
+/// Inserts what isn't in the store yet and clears the batch.
+async fn flush(
+    store: &dyn Store,
+    batch: &mut Vec<File>,
+    seen: &mut HashSet<String>,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let origins: Vec<String> = batch.iter().filter_map(|f| f.origin.clone()).collect();
+    let existing = store.existing_origins(&origins).await?;
+
+    let mut files = Vec::with_capacity(batch.len());
+    for file in batch.drain(..) {
+        let origin = file.origin.clone().unwrap_or_default();
+        if existing.contains(&origin) || !seen.insert(origin) {
+            report.duplicates += 1;
+        } else {
+            files.push(file);
+        }
+    }
+
+    report.imported += files.len() as u64;
+    store.insert(files).await
+}
+
+/// Merges a kongg NDJSON export into the store. Records captured on the
+/// exporting machine, or without a host, are tagged with `host`; the others,
+/// and all of them when `host` is `None`, keep the host they were exported
+/// with.
+/// Records the store already has are skipped, so importing the same file
+/// twice is harmless.
+pub async fn import(
+    store: &dyn Store,
+    input: impl BufRead,
+    host: Option<&str>,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut seen = HashSet::new();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: ExportRecord = serde_json::from_str(&line)
+            .map_err(|e| Error::Parse(format!("line {}: {}", number + 1, e)))?;
+        // records the exporting machine had itself imported already name
+        // the machine they came from, so `host` only covers its own events
+        let relayed = record.origin.is_some() && record.host.is_some();
+        let record_host = match (host, relayed) {
+            (Some(host), false) => host.to_string(),
+            _ => record.host.clone().ok_or_else(|| {
+                Error::Parse(format!(
+                    "line {}: the record has no host, name the source machine explicitly",
+                    number + 1
+                ))
+            })?,
+        };
+        batch.push(record.into_imported(record_host)?);
+        report.read += 1;
+
+        if batch.len() >= IMPORT_BATCH_SIZE {
+            flush(store, &mut batch, &mut seen, &mut report).await?;
+        }
+    }
+    flush(store, &mut batch, &mut seen, &mut report).await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        export::{ExportFormat, export},
+        models::query::FileQuery,
+        store::{MemoryStore, scan},
+    };
+
+    fn captured(path: &str, host: &str) -> File {
+        let mut file = File::missing(EventType::Created, path.to_string());
+        file.host = Some(host.to_string());
+        file
+    }
+
+    #[tokio::test]
+    async fn an_export_imports_once_and_keeps_relayed_origins() {
+        let laptop = MemoryStore::default();
+        let mut relayed = captured("/home/desk/plan.txt", "desk");
+        relayed.origin = Some("desk:file:7".to_string());
+        laptop
+            .insert(vec![
+                captured("/home/me/a.txt", "laptop"),
+                captured("/home/me/b.txt", "laptop"),
+                relayed,
+            ])
+            .await
+            .unwrap();
+        let mut ndjson = Vec::new();
+        export(
+            &laptop,
+            &FileQuery::new(),
+            ExportFormat::Ndjson,
+            &mut ndjson,
+        )
+        .await
+        .unwrap();
+
+        let store = MemoryStore::default();
+        let first = import(&store, &ndjson[..], Some("laptop")).await.unwrap();
+        assert_eq!((first.read, first.imported, first.duplicates), (3, 3, 0));
+        let again = import(&store, &ndjson[..], Some("laptop")).await.unwrap();
+        assert_eq!(again.duplicates, again.read);
+        assert_eq!(again.imported, 0);
+
+        let mut sources: Vec<(String, Option<String>, Option<String>)> =
+            scan(&store, &FileQuery::new())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|f| (f.path, f.host, f.origin))
+                .collect();
+        sources.sort();
+        // relayed records keep where they came from, the others are the
+        // laptop's own under their id there
+        let mut expected: Vec<(String, Option<String>, Option<String>)> =
+            scan(&laptop, &FileQuery::new())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|f| match f.origin {
+                    Some(origin) => (f.path, f.host, Some(origin)),
+                    None => (f.path, f.host, Some(format!("laptop:{}", f.id))),
+                })
+                .collect();
+        expected.sort();
+        assert_eq!(sources, expected);
+        assert_eq!(
+            sources[0],
+            (
+                "/home/desk/plan.txt".to_string(),
+                Some("desk".to_string()),
+                Some("desk:file:7".to_string()),
+            )
+        );
+    }
+}
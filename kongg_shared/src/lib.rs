@@ -1,9 +1,24 @@
+pub mod backup;
+pub mod check;
+pub mod config;
 pub mod constants;
-pub mod extensions;
+pub mod crypto;
+pub mod deleted;
+pub mod error;
+pub mod export;
 pub mod helpers;
+pub mod history;
+pub mod import;
 pub mod models;
+pub mod retention;
+pub mod search;
+pub mod stats;
+pub mod store;
+pub mod stream;
 pub mod utils;
 
+pub use error::{Error, Result};
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
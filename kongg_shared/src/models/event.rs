@@ -1,8 +1,10 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum EventType {
     Created,
     Renamed,
@@ -11,6 +13,16 @@ pub enum EventType {
     Copied,
 }
 
+impl EventType {
+    pub const ALL: [EventType; 5] = [
+        EventType::Created,
+        EventType::Renamed,
+        EventType::Deleted,
+        EventType::Moved,
+        EventType::Copied,
+    ];
+}
+
 impl ToString for EventType {
     fn to_string(&self) -> String {
         match self {
@@ -23,6 +35,21 @@ impl ToString for EventType {
     }
 }
 
+impl FromStr for EventType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "created" => Ok(EventType::Created),
+            "renamed" => Ok(EventType::Renamed),
+            "deleted" => Ok(EventType::Deleted),
+            "moved" => Ok(EventType::Moved),
+            "copied" => Ok(EventType::Copied),
+            _ => Err(Error::Parse(format!("unknown event type `{}`", s))),
+        }
+    }
+}
+
 // ! conflict with ToString trait
 // impl fmt::Display for EventType {
 //     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -7,26 +7,35 @@ use std::{
 use surrealdb::RecordId;
 
 use super::event::EventType;
+use crate::{
+    Error, Result,
+    utils::identity::{host_name, machine_id, owner, process_user},
+};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct File {
-    event_type: EventType, // ? "created", "updated", or "deleted"
-    path: String,          // ? Full file path (e.g., /home/user/docs/file.txt)
-    previous_path: Option<String>,
-    file_name: Option<String>, // ? File name (can be extracted from path)
-    extension: Option<String>, // ? File extension (e.g., .txt, .png)
-    file_size: Option<u64>,    // ? Size of file in bytes (only for created or updated)
-    hash: Option<String>,      // ? checksum/hash of the file content (e.g., SHA256)
-    previous_hash: Option<String>,
-    file_id: Option<String>, // ? Platform-specific unique file ID
-    parent_directory: Option<String>,
-    permissions: Option<String>,
-    is_read_only: bool,
-    timestamp: DateTime<Utc>, // ? When the event occurred (in UTC or local time)
+    pub(crate) event_type: EventType, // ? "created", "updated", or "deleted"
+    pub(crate) path: String,          // ? Full file path (e.g., /home/user/docs/file.txt)
+    pub(crate) previous_path: Option<String>,
+    pub(crate) file_name: Option<String>, // ? File name (can be extracted from path)
+    pub(crate) extension: Option<String>, // ? File extension (e.g., .txt, .png)
+    pub(crate) file_size: Option<u64>,    // ? Size of file in bytes (only for created or updated)
+    pub(crate) hash: Option<String>,      // ? checksum/hash of the file content (e.g., SHA256)
+    pub(crate) previous_hash: Option<String>,
+    pub(crate) file_id: Option<String>, // ? Platform-specific unique file ID
+    pub(crate) parent_directory: Option<String>,
+    pub(crate) permissions: Option<String>,
+    pub(crate) is_read_only: bool,
+    pub(crate) timestamp: DateTime<Utc>, // ? When the event occurred (in UTC or local time)
+    pub(crate) root: Option<String>,     // ? Watch root the event was captured under
+    pub(crate) host: Option<String>,     // ? Machine the event was captured on
+    pub(crate) machine_id: Option<String>, // ? /etc/machine-id of that machine
+    pub(crate) username: Option<String>, // ? Owner of the file, or the user kongg runs as
+    pub(crate) origin: Option<String>, // ? "<host>:<id>" of an imported record, none for local ones
 }
 
 impl File {
-    pub fn new(event_type: EventType, path: String) -> Self {
+    pub fn new(event_type: EventType, path: String) -> Result<Self> {
         let path = path.clone();
         // let extension = path
         //     .clone()
@@ -35,8 +44,10 @@ impl File {
         //     .last()
         //     .map(|v| v.to_string());
 
-        let normalized_path = Path::new(&path).canonicalize().unwrap();
-        let metadata = fs::metadata(&normalized_path).unwrap();
+        let normalized_path = Path::new(&path)
+            .canonicalize()
+            .map_err(|e| Error::path(&path, e))?;
+        let metadata = fs::metadata(&normalized_path).map_err(|e| Error::path(&path, e))?;
 
         let parent_directory = normalized_path.parent().map(|p| p.display().to_string());
         let file_name = normalized_path
@@ -49,8 +60,9 @@ impl File {
             .map(|e| e.to_string_lossy().to_string());
 
         let is_read_only = metadata.permissions().readonly();
+        let username = owner(&metadata);
 
-        Self {
+        Ok(Self {
             event_type,
             path,
             previous_path: None,
@@ -64,6 +76,38 @@ impl File {
             previous_hash: None,
             is_read_only,
             timestamp: Utc::now(),
+            root: None,
+            host: host_name(),
+            machine_id: machine_id(),
+            username: Some(username),
+            origin: None,
+        })
+    }
+
+    /// Builds a record for a path that is no longer on disk (e.g. deletions
+    /// found by a rescan), so nothing is read from the filesystem.
+    pub fn missing(event_type: EventType, path: String) -> Self {
+        let p = PathBuf::from(&path);
+
+        Self {
+            event_type,
+            previous_path: None,
+            extension: p.extension().map(|e| e.to_string_lossy().to_string()),
+            file_id: None,
+            file_name: p.file_name().map(|f| f.to_string_lossy().to_string()),
+            file_size: None,
+            hash: None,
+            parent_directory: p.parent().map(|p| p.display().to_string()),
+            permissions: None,
+            previous_hash: None,
+            is_read_only: false,
+            timestamp: Utc::now(),
+            root: None,
+            host: host_name(),
+            machine_id: machine_id(),
+            username: Some(process_user()),
+            origin: None,
+            path,
         }
     }
 
@@ -71,9 +115,70 @@ impl File {
         self.previous_path = Some(path);
         self
     }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn with_root(mut self, root: String) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// Turns a record into what a store hands back once it has an id, for
+    /// backends that don't round-trip through SurrealDB.
+    pub(crate) fn into_response(self, id: RecordId) -> FileResponse {
+        FileResponse {
+            id,
+            event_type: self.event_type,
+            path: self.path,
+            previous_path: self.previous_path,
+            file_name: self.file_name,
+            extension: self.extension,
+            file_size: self.file_size,
+            hash: self.hash,
+            previous_hash: self.previous_hash,
+            file_id: self.file_id,
+            parent_directory: self.parent_directory,
+            permissions: self.permissions,
+            is_read_only: self.is_read_only,
+            timestamp: self.timestamp,
+            root: self.root,
+            host: self.host,
+            machine_id: self.machine_id,
+            username: self.username,
+            origin: self.origin,
+        }
+    }
+}
+
+impl From<FileResponse> for File {
+    /// The record without its id, e.g. to write it back changed.
+    fn from(file: FileResponse) -> Self {
+        Self {
+            event_type: file.event_type,
+            path: file.path,
+            previous_path: file.previous_path,
+            file_name: file.file_name,
+            extension: file.extension,
+            file_size: file.file_size,
+            hash: file.hash,
+            previous_hash: file.previous_hash,
+            file_id: file.file_id,
+            parent_directory: file.parent_directory,
+            permissions: file.permissions,
+            is_read_only: file.is_read_only,
+            timestamp: file.timestamp,
+            root: file.root,
+            host: file.host,
+            machine_id: file.machine_id,
+            username: file.username,
+            origin: file.origin,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct FileResponse {
     pub id: RecordId,
     pub event_type: EventType,
@@ -82,11 +187,16 @@ pub struct FileResponse {
     pub file_name: Option<String>,
     pub extension: Option<String>,
     pub file_size: Option<u64>,
-    _hash: Option<String>,
-    _previous_hash: Option<String>,
-    _file_id: Option<String>,
+    pub hash: Option<String>,
+    pub previous_hash: Option<String>,
+    pub file_id: Option<String>,
     pub parent_directory: Option<String>,
     pub permissions: Option<String>,
     pub is_read_only: bool,
     pub timestamp: DateTime<Utc>,
+    pub root: Option<String>,
+    pub host: Option<String>,
+    pub machine_id: Option<String>,
+    pub username: Option<String>,
+    pub origin: Option<String>,
 }
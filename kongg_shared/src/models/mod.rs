@@ -1,2 +1,4 @@
 pub mod event;
 pub mod file;
+pub mod query;
+pub mod status;
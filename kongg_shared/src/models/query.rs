@@ -0,0 +1,438 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Result,
+    helpers::inventory::is_under,
+    models::{event::EventType, file::FileResponse},
+    store::record_key,
+    utils::glob::{glob_match, matches_name_only},
+};
+
+/// Rows per page when the caller doesn't set a limit.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    #[default]
+    Timestamp,
+    Size,
+    Name,
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Value of the sort key for one row. Missing sizes sort as `0` and missing
+/// names as `""` in every backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKey {
+    Timestamp(DateTime<Utc>),
+    Number(u64),
+    Text(String),
+}
+
+impl SortField {
+    pub fn key_of(&self, file: &FileResponse) -> SortKey {
+        match self {
+            SortField::Timestamp => SortKey::Timestamp(file.timestamp),
+            SortField::Size => SortKey::Number(file.file_size.unwrap_or(0)),
+            SortField::Name => {
+                SortKey::Text(file.file_name.clone().unwrap_or_default().to_lowercase())
+            }
+            SortField::Path => SortKey::Text(file.path.clone()),
+        }
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (SortKey::Timestamp(a), SortKey::Timestamp(b)) => a.partial_cmp(b),
+            (SortKey::Number(a), SortKey::Number(b)) => a.partial_cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+/// Position after the last row of a page: the sort key plus the record id
+/// to break ties. Handed to callers as an opaque token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub key: SortKey,
+    pub id: String,
+}
+
+const CURSOR_SEPARATOR: char = '\u{1f}';
+
+impl Cursor {
+    pub fn after(field: SortField, file: &FileResponse) -> Self {
+        Self {
+            key: field.key_of(file),
+            id: file.id.to_string(),
+        }
+    }
+
+    pub fn to_token(&self) -> String {
+        let key = match &self.key {
+            SortKey::Timestamp(t) => format!("t{}", t.to_rfc3339()),
+            SortKey::Number(n) => format!("n{}", n),
+            SortKey::Text(s) => format!("s{}", s),
+        };
+        format!("{}{}{}", key, CURSOR_SEPARATOR, self.id)
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn from_token(token: &str) -> Result<Self> {
+        let invalid = || Error::Parse(format!("invalid cursor `{}`", token));
+
+        if !token.is_ascii() || !token.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let decoded = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+        // the id never holds the separator, a path or name might
+        let (key, id) = decoded.rsplit_once(CURSOR_SEPARATOR).ok_or_else(invalid)?;
+        let key = match key.split_at_checked(1) {
+            Some(("t", t)) => SortKey::Timestamp(
+                DateTime::parse_from_rfc3339(t)
+                    .map_err(|_| invalid())?
+                    .with_timezone(&Utc),
+            ),
+            Some(("n", n)) => SortKey::Number(n.parse().map_err(|_| invalid())?),
+            Some(("s", s)) => SortKey::Text(s.to_string()),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            key,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Filters, ordering and page position for reading the event log. Every
+/// store translates this into its own query language; [`FileQuery::matches`]
+/// is the reference behaviour.
+#[derive(Debug, Clone, Default)]
+pub struct FileQuery {
+    pub event_types: Vec<EventType>,
+    /// Exact paths, matched against the path or the previous path.
+    pub paths: Vec<String>,
+    pub path_prefix: Option<String>,
+    pub glob: Option<String>,
+    /// Lowercase, without the leading dot.
+    pub extensions: Vec<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Inclusive lower bound.
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive upper bound.
+    pub until: Option<DateTime<Utc>>,
+    pub root: Option<String>,
+    /// Machine the event was captured on.
+    pub host: Option<String>,
+    /// Contents of `/etc/machine-id` on that machine.
+    pub machine_id: Option<String>,
+    pub username: Option<String>,
+    /// Case-insensitive substring of the path or file name.
+    pub text: Option<String>,
+    pub sort: SortField,
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    pub cursor: Option<Cursor>,
+}
+
+/// One page of results and where the next one starts, if there is one.
+#[derive(Debug, Default)]
+pub struct Page {
+    pub items: Vec<FileResponse>,
+    pub next: Option<Cursor>,
+}
+
+impl FileQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.event_types.push(event_type);
+        self
+    }
+
+    pub fn event_types(mut self, event_types: impl IntoIterator<Item = EventType>) -> Self {
+        self.event_types.extend(event_types);
+        self
+    }
+
+    /// Events at any of `paths` or moved away from one of them.
+    pub fn paths(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn under(mut self, path: impl Into<String>) -> Self {
+        self.path_prefix = Some(path.into());
+        self
+    }
+
+    pub fn glob(mut self, glob: impl Into<String>) -> Self {
+        self.glob = Some(glob.into());
+        self
+    }
+
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extensions
+            .push(extension.trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    pub fn extensions<'a>(mut self, extensions: impl IntoIterator<Item = &'a str>) -> Self {
+        for extension in extensions {
+            self = self.extension(extension);
+        }
+        self
+    }
+
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn root(mut self, root: impl Into<String>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn machine_id(mut self, machine_id: impl Into<String>) -> Self {
+        self.machine_id = Some(machine_id.into());
+        self
+    }
+
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into().to_lowercase());
+        self
+    }
+
+    pub fn sort(mut self, sort: SortField, order: SortOrder) -> Self {
+        self.sort = sort;
+        self.order = order;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Whether a row passes every filter (the cursor is not considered).
+    pub fn matches(&self, file: &FileResponse) -> bool {
+        if !self.event_types.is_empty() && !self.event_types.contains(&file.event_type) {
+            return false;
+        }
+        if !self.paths.is_empty()
+            && !self.paths.contains(&file.path)
+            && !file
+                .previous_path
+                .as_ref()
+                .is_some_and(|p| self.paths.contains(p))
+        {
+            return false;
+        }
+        if let Some(prefix) = &self.path_prefix {
+            if !is_under(&file.path, prefix) {
+                return false;
+            }
+        }
+        if let Some(glob) = &self.glob {
+            let target = if matches_name_only(glob) {
+                file.file_name.as_deref().unwrap_or_default()
+            } else {
+                file.path.as_str()
+            };
+            if !glob_match(glob, target) {
+                return false;
+            }
+        }
+        if !self.extensions.is_empty() {
+            let extension = file.extension.as_deref().unwrap_or_default().to_lowercase();
+            if !self.extensions.contains(&extension) {
+                return false;
+            }
+        }
+        let size = file.file_size.unwrap_or(0);
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        if self.since.is_some_and(|since| file.timestamp < since)
+            || self.until.is_some_and(|until| file.timestamp >= until)
+        {
+            return false;
+        }
+        if let Some(root) = &self.root {
+            if file.root.as_deref() != Some(root.as_str()) {
+                return false;
+            }
+        }
+        if let Some(host) = &self.host {
+            if file.host.as_deref() != Some(host.as_str()) {
+                return false;
+            }
+        }
+        if let Some(machine_id) = &self.machine_id {
+            if file.machine_id.as_deref() != Some(machine_id.as_str()) {
+                return false;
+            }
+        }
+        if let Some(username) = &self.username {
+            if file.username.as_deref() != Some(username.as_str()) {
+                return false;
+            }
+        }
+        if let Some(text) = &self.text {
+            let in_path = file.path.to_lowercase().contains(text);
+            let in_name = file
+                .file_name
+                .as_deref()
+                .is_some_and(|n| n.to_lowercase().contains(text));
+            if !in_path && !in_name {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Orders rows that already passed the filters, skips those up to the
+    /// cursor and returns the page, for stores that evaluate queries in Rust.
+    pub fn page_of(&self, mut events: Vec<FileResponse>) -> Page {
+        // ids break ties numerically where the backend numbers its rows, the
+        // way the SQL backends order and page them, and as text otherwise
+        let tie = |id: &str| (record_key(id).ok(), id.to_string());
+        let position = |file: &FileResponse| (self.sort.key_of(file), tie(&file.id.to_string()));
+        if let Some(cursor) = &self.cursor {
+            let cursor = (cursor.key.clone(), tie(&cursor.id));
+            events.retain(|e| match self.order {
+                SortOrder::Asc => position(e) > cursor,
+                SortOrder::Desc => position(e) < cursor,
+            });
+        }
+
+        events.sort_by(|a, b| {
+            let ordering = position(a)
+                .partial_cmp(&position(b))
+                .unwrap_or(std::cmp::Ordering::Equal);
+            match self.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+        events.truncate(self.page_size() + 1);
+        self.paginate(events)
+    }
+
+    /// Splits an over-fetched result (`page_size() + 1` rows) into a page
+    /// and the cursor for the next one.
+    pub fn paginate(&self, mut items: Vec<FileResponse>) -> Page {
+        let size = self.page_size();
+        if items.len() <= size {
+            return Page { items, next: None };
+        }
+
+        items.truncate(size);
+        let next = items.last().map(|last| Cursor::after(self.sort, last));
+        Page { items, next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn round_trip(key: SortKey) {
+        let cursor = Cursor {
+            key,
+            id: "file:42".to_string(),
+        };
+        assert_eq!(Cursor::from_token(&cursor.to_token()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        round_trip(SortKey::Timestamp(
+            Utc.with_ymd_and_hms(2025, 3, 1, 12, 30, 5).unwrap()
+                + chrono::Duration::microseconds(123_456),
+        ));
+        round_trip(SortKey::Number(0));
+        round_trip(SortKey::Number(u64::MAX));
+        round_trip(SortKey::Text(String::new()));
+        round_trip(SortKey::Text("résumé \u{1f} 報告.pdf".to_string()));
+    }
+
+    #[test]
+    fn bad_cursor_tokens_are_refused() {
+        for token in ["", "abc", "zz", "é1", "7431", "78\u{1f}"] {
+            assert!(Cursor::from_token(token).is_err(), "{:?}", token);
+        }
+        // a known kind with a value that doesn't parse
+        let token = Cursor {
+            key: SortKey::Text("x".to_string()),
+            id: "file:1".to_string(),
+        }
+        .to_token()
+        .replacen("73", "6e", 1);
+        assert!(Cursor::from_token(&token).is_err());
+    }
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The daemon saves its status every few seconds, so one older than this
+/// means it has stopped.
+pub const STATUS_STALE_AFTER_SECS: i64 = 30;
+
+/// Snapshot of the watcher daemon, written periodically so other tools can
+/// see how far behind persistence is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonStatus {
+    pub roots: Vec<String>,
+    pub queue_depth: usize,    // ? records waiting to be written
+    pub queue_capacity: usize, // ? size of the bounded write queue
+    pub pending_events: usize, // ? raw notify events not yet processed
+    pub captured_events: u64,
+    pub dropped_events: u64, // ? events lost because the capture channel was full
+    pub overflows: u64,      // ? kernel queue overflows reported by notify
+    pub written_events: u64,
+    pub batches: u64,
+    pub rescans: u64,
+    pub failed_events: u64, // ? events skipped because the path could not be read
+    pub failed_writes: u64, // ? records the database refused
+    pub watch_errors: u64,  // ? errors reported by notify itself
+    pub pruned_events: u64, // ? events removed by the retention rules
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl DaemonStatus {
+    pub fn is_running(&self, now: DateTime<Utc>) -> bool {
+        self.updated_at
+            .is_some_and(|t| now - t < Duration::seconds(STATUS_STALE_AFTER_SECS))
+    }
+}
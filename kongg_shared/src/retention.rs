@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Result,
+    config::RetentionConfig,
+    helpers::inventory::replay,
+    models::{event::EventType, file::FileResponse},
+    stats::local_day,
+    store::{Store, events_touching},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PruneReason {
+    /// Older than `max_age_days` for its type.
+    Expired,
+    /// Not the last event of its day for a `collapse_daily` type.
+    Collapsed,
+    /// Beyond `max_rows_per_root`.
+    OverCap,
+}
+
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Events looked at.
+    pub scanned: usize,
+    /// Events that are (or, on a dry run, would be) removed, oldest first.
+    pub removed: Vec<(FileResponse, PruneReason)>,
+}
+
+impl PruneReport {
+    pub fn count(&self, reason: PruneReason) -> usize {
+        self.removed.iter().filter(|(_, r)| *r == reason).count()
+    }
+}
+
+/// Events captured here replay as one history, imported ones as one per
+/// source host.
+fn history(event: &FileResponse) -> Option<String> {
+    event.origin.as_ref().and(event.host.clone())
+}
+
+/// Newest record of every path that still exists, per history.
+fn live_records(events: impl IntoIterator<Item = FileResponse>) -> Vec<FileResponse> {
+    let mut histories: HashMap<Option<String>, Vec<FileResponse>> = HashMap::new();
+    for event in events {
+        histories.entry(history(&event)).or_default().push(event);
+    }
+    histories.into_values().flat_map(replay).collect()
+}
+
+/// Whether `event` is older than `config` keeps events of its type.
+fn expired(config: &RetentionConfig, event: &FileResponse, now: DateTime<Utc>) -> bool {
+    config
+        .max_age_days
+        .get(&event.event_type)
+        .is_some_and(|days| event.timestamp < now - Duration::days(*days as i64))
+}
+
+/// [`Store::prunable_paths`] over a whole log held in memory.
+pub fn prunable_paths_in(
+    events: &[FileResponse],
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> HashSet<String> {
+    let mut paths = HashSet::new();
+    let mut per_day: HashMap<(&str, EventType, String), usize> = HashMap::new();
+    let mut per_root: HashMap<Option<&str>, usize> = HashMap::new();
+    for event in events {
+        if expired(config, event, now) {
+            paths.insert(event.path.clone());
+        }
+        if config.collapse_daily.contains(&event.event_type) {
+            let day = (
+                event.path.as_str(),
+                event.event_type,
+                local_day(&event.timestamp),
+            );
+            *per_day.entry(day).or_default() += 1;
+        }
+        *per_root.entry(event.root.as_deref()).or_default() += 1;
+    }
+
+    paths.extend(
+        per_day
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|((path, _, _), _)| path.to_string()),
+    );
+    if let Some(cap) = config.max_rows_per_root {
+        paths.extend(
+            events
+                .iter()
+                .filter(|e| per_root[&e.root.as_deref()] > cap)
+                .map(|e| e.path.clone()),
+        );
+    }
+    paths
+}
+
+/// Decides which events the rules remove from a log sorted oldest first.
+/// The newest event of every path that still exists is always kept, so
+/// inventories and rescans are unaffected by pruning.
+pub fn plan(
+    config: &RetentionConfig,
+    events: Vec<FileResponse>,
+    now: DateTime<Utc>,
+) -> PruneReport {
+    let protected: HashSet<RecordId> = live_records(events.iter().cloned())
+        .into_iter()
+        .map(|f| f.id)
+        .collect();
+
+    let mut report = PruneReport {
+        scanned: events.len(),
+        removed: Vec::new(),
+    };
+
+    // the last event of each (history, path, type, day) survives collapsing
+    let day_key = |e: &FileResponse| {
+        (
+            history(e),
+            e.path.clone(),
+            e.event_type.to_string(),
+            local_day(&e.timestamp),
+        )
+    };
+    let mut last_of_day: HashMap<(Option<String>, String, String, String), RecordId> =
+        HashMap::new();
+    for event in &events {
+        if config.collapse_daily.contains(&event.event_type) {
+            last_of_day.insert(day_key(event), event.id.clone());
+        }
+    }
+
+    let mut kept: Vec<FileResponse> = Vec::with_capacity(events.len());
+    for event in events {
+        if protected.contains(&event.id) {
+            kept.push(event);
+            continue;
+        }
+
+        if expired(config, &event, now) {
+            report.removed.push((event, PruneReason::Expired));
+            continue;
+        }
+
+        if config.collapse_daily.contains(&event.event_type)
+            && last_of_day.get(&day_key(&event)) != Some(&event.id)
+        {
+            report.removed.push((event, PruneReason::Collapsed));
+            continue;
+        }
+
+        kept.push(event);
+    }
+
+    // removing a Deleted (or a rename away) while older events of the same
+    // path stay would bring the file back in the inventory, so those go too
+    let resurrected: HashSet<(Option<String>, String)> = live_records(kept.iter().cloned())
+        .into_iter()
+        .filter(|f| !protected.contains(&f.id))
+        .map(|f| (history(&f), f.path))
+        .collect();
+    if !resurrected.is_empty() {
+        let (dangling, rest): (Vec<_>, Vec<_>) = kept.into_iter().partition(|e| {
+            resurrected.contains(&(history(e), e.path.clone())) && !protected.contains(&e.id)
+        });
+        report
+            .removed
+            .extend(dangling.into_iter().map(|e| (e, PruneReason::Expired)));
+        kept = rest;
+    }
+
+    if let Some(cap) = config.max_rows_per_root {
+        let mut per_root: HashMap<Option<String>, usize> = HashMap::new();
+        for event in &kept {
+            *per_root.entry(event.root.clone()).or_default() += 1;
+        }
+
+        // oldest first, so the first ones over the cap are the oldest
+        for event in kept {
+            let count = per_root.get_mut(&event.root).unwrap();
+            if *count > cap && !protected.contains(&event.id) {
+                *count -= 1;
+                report.removed.push((event, PruneReason::OverCap));
+            }
+        }
+    }
+
+    report
+        .removed
+        .sort_by(|(a, _), (b, _)| a.timestamp.cmp(&b.timestamp));
+    report
+}
+
+/// Applies the rules and removes what they select, unless `dry_run` is
+/// set. Only the events of the paths the store reports as prunable, and of
+/// the paths those were renamed from or to, are read.
+pub async fn prune(
+    store: &dyn Store,
+    config: &RetentionConfig,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let now = Utc::now();
+    let paths = store.prunable_paths(config, now).await?;
+    if paths.is_empty() {
+        return Ok(PruneReport::default());
+    }
+
+    let report = plan(config, events_touching(store, paths).await?, now);
+    if !dry_run && !report.removed.is_empty() {
+        let ids = report.removed.iter().map(|(f, _)| f.id.clone()).collect();
+        store.delete(ids).await?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,109 @@
+/// How many typos a query word of this length may contain and still match.
+fn allowed_typos(len: usize) -> usize {
+    match len {
+        0..=2 => 0,
+        3..=5 => 1,
+        6..=9 => 2,
+        _ => 3,
+    }
+}
+
+/// Optimal string alignment distance (Levenshtein plus adjacent swaps).
+pub fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        rows[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How well a query word matches a word from a file name, from `0.0` (not
+/// at all) to `1.0` (exactly). Prefixes score high so half-typed words
+/// work; a few typos are tolerated depending on the word's length.
+pub fn similarity(query: &str, word: &str) -> f64 {
+    if query == word {
+        return 1.0;
+    }
+    if word.starts_with(query) {
+        return 0.9;
+    }
+    if query.len() >= 3 && word.contains(query) {
+        return 0.7;
+    }
+
+    let q: Vec<char> = query.chars().collect();
+    let w: Vec<char> = word.chars().collect();
+    let allowed = allowed_typos(q.len());
+    if allowed == 0 {
+        return 0.0;
+    }
+
+    // Compare against the whole word and against a prefix of the same
+    // length, so a typo in a half-typed word still matches.
+    let prefix = &w[..w.len().min(q.len())];
+    let typos = edit_distance(&q, &w).min(edit_distance(&q, prefix) + 1);
+    if typos > allowed {
+        return 0.0;
+    }
+    0.8 - 0.15 * typos as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b)
+    }
+
+    #[test]
+    fn edit_distance_counts_edits_and_swaps() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("invoice", "invoce"), 1);
+        assert_eq!(distance("invoice", "ivnoice"), 1);
+        assert_eq!(distance("résumé", "resume"), 2);
+        // a swap is only one edit when nothing else touches the letters
+        assert_eq!(distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn similarity_ranks_exact_then_prefix_then_typos() {
+        assert_eq!(similarity("invoice", "invoice"), 1.0);
+        assert_eq!(similarity("inv", "invoice"), 0.9);
+        assert_eq!(similarity("voice", "invoice"), 0.7);
+        assert_eq!(similarity("invoce", "invoice"), 0.65);
+        assert_eq!(similarity("ivnoice", "invoice"), 0.65);
+        // a typo in a half-typed word
+        assert_eq!(similarity("docuemnt", "documentation"), 0.5);
+        assert_eq!(similarity("résumé", "resume"), 0.5);
+    }
+
+    #[test]
+    fn similarity_refuses_what_is_too_far() {
+        assert_eq!(similarity("invoice", "inverse"), 0.0);
+        assert_eq!(similarity("pdf", "docx"), 0.0);
+        // words of two letters allow no typo
+        assert_eq!(similarity("ab", "ac"), 0.0);
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashSet, ops::Range};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Result,
+    models::{
+        file::FileResponse,
+        query::{FileQuery, SortField, SortOrder},
+    },
+    store::Store,
+};
+
+pub mod fuzzy;
+pub mod tokenize;
+
+pub use tokenize::{search_text, tokenize, word_spans};
+
+/// Candidates read per round trip while ranking.
+const CANDIDATE_PAGE_SIZE: usize = 2000;
+
+/// Newest events ranked when none of the query words is found in the
+/// index.
+const FALLBACK_CANDIDATES: usize = 10_000;
+
+/// Query words are looked up in the index by this many leading characters;
+/// typos after the prefix are left to the fuzzy ranking, typos in it to a
+/// scan when the index finds nothing.
+const PREFIX_LEN: usize = 3;
+
+/// Below this a query word doesn't count as found in a file.
+const MIN_SIMILARITY: f64 = 0.3;
+
+/// Words in the file name count fully, words in the directories less.
+const DIRECTORY_WEIGHT: f64 = 0.6;
+
+/// Share of the final score that comes from how recent the event is.
+const RECENCY_WEIGHT: f64 = 0.15;
+
+/// Age in days at which the recency bonus has halved.
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub file: FileResponse,
+    /// Between `0.0` and `1.0`, higher is better.
+    pub score: f64,
+}
+
+/// Prefixes the stores look up in their full-text index. Single characters
+/// are too common to narrow anything down and are only used for ranking.
+pub fn index_terms(words: &[String]) -> Vec<String> {
+    words
+        .iter()
+        .filter(|w| w.chars().count() > 1)
+        .map(|w| w.chars().take(PREFIX_LEN).collect())
+        .collect()
+}
+
+/// Whether `path` has a word starting with each of `terms`, the same test
+/// the stores' full-text indexes apply.
+pub fn has_terms(path: &str, terms: &[String]) -> bool {
+    let words = tokenize(path);
+    terms
+        .iter()
+        .all(|t| words.iter().any(|w| w.starts_with(t.as_str())))
+}
+
+/// Relevance of `file` to the query words combined with how recent it is,
+/// or `None` when one of the words isn't found at all.
+pub fn score(words: &[String], file: &FileResponse, now: DateTime<Utc>) -> Option<f64> {
+    let name_words = tokenize(file.file_name.as_deref().unwrap_or_default());
+    let path_words = tokenize(&file.path);
+
+    let mut relevance = 0.0;
+    for word in words {
+        let in_name = name_words
+            .iter()
+            .map(|w| fuzzy::similarity(word, w))
+            .fold(0.0, f64::max);
+        let in_path = path_words
+            .iter()
+            .map(|w| fuzzy::similarity(word, w) * DIRECTORY_WEIGHT)
+            .fold(0.0, f64::max);
+        let best = in_name.max(in_path);
+        if best < MIN_SIMILARITY {
+            return None;
+        }
+        relevance += best;
+    }
+    relevance /= words.len().max(1) as f64;
+
+    let age_days = (now - file.timestamp).num_seconds().max(0) as f64 / 86_400.0;
+    let recency = 1.0 / (1.0 + age_days / RECENCY_HALF_LIFE_DAYS);
+
+    Some(relevance * (1.0 - RECENCY_WEIGHT) + recency * RECENCY_WEIGHT)
+}
+
+/// Byte ranges of the words in `text` that one of the query words is
+/// found in, by the same measure as [`score`], for highlighting.
+pub fn matched_spans(words: &[String], text: &str) -> Vec<Range<usize>> {
+    word_spans(text)
+        .into_iter()
+        .filter(|(_, word)| {
+            words
+                .iter()
+                .any(|w| fuzzy::similarity(w, word) >= MIN_SIMILARITY)
+        })
+        .map(|(range, _)| range)
+        .collect()
+}
+
+/// Finds files whose name or path resembles `text`, tolerating typos and
+/// half-typed words, best matches first. `filter` narrows the candidates
+/// and its limit caps the number of hits; only the newest event of each
+/// path is returned.
+pub async fn search(store: &dyn Store, text: &str, filter: &FileQuery) -> Result<Vec<SearchHit>> {
+    let words = tokenize(text);
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let terms = index_terms(&words);
+    let hits = rank(store, &words, Some(&terms), filter, None).await?;
+    if !hits.is_empty() || terms.is_empty() {
+        return Ok(hits);
+    }
+
+    // a typo in the first letters of a word keeps the index from finding
+    // it, so that word is left to the ranking and the others still narrow
+    // the candidates down
+    let mut probe = filter.clone().limit(1);
+    probe.cursor = None;
+    let mut found = Vec::new();
+    for term in &terms {
+        let page = store
+            .search_candidates(std::slice::from_ref(term), &probe)
+            .await?;
+        if !page.items.is_empty() {
+            found.push(term.clone());
+        }
+    }
+    if found.len() == terms.len() {
+        return Ok(hits);
+    }
+    if found.is_empty() {
+        return rank(store, &words, None, filter, Some(FALLBACK_CANDIDATES)).await;
+    }
+    rank(store, &words, Some(&found), filter, None).await
+}
+
+/// Scores the candidates page by page, keeping the best as it goes: those
+/// the index finds for `terms`, or every event matching `filter` without
+/// them. With `max_candidates`, only that many of the newest are read.
+async fn rank(
+    store: &dyn Store,
+    words: &[String],
+    terms: Option<&[String]>,
+    filter: &FileQuery,
+    max_candidates: Option<usize>,
+) -> Result<Vec<SearchHit>> {
+    // newest first, so the first event seen of a path is its newest
+    let mut query = filter
+        .clone()
+        .sort(SortField::Timestamp, SortOrder::Desc)
+        .limit(CANDIDATE_PAGE_SIZE);
+    query.cursor = None;
+
+    let now = Utc::now();
+    let mut seen = HashSet::new();
+    let mut hits: Vec<SearchHit> = Vec::new();
+    let mut read = 0;
+    loop {
+        let page = match terms {
+            Some(terms) => store.search_candidates(terms, &query).await?,
+            None => store.query(&query).await?,
+        };
+        read += page.items.len();
+        hits.extend(
+            page.items
+                .into_iter()
+                .filter(|file| seen.insert(file.path.clone()))
+                .filter_map(|file| score(words, &file, now).map(|score| SearchHit { file, score })),
+        );
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(filter.page_size());
+
+        match page.next {
+            Some(_) if max_candidates.is_some_and(|max| read >= max) => return Ok(hits),
+            Some(cursor) => query = query.after(cursor),
+            None => return Ok(hits),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        models::{event::EventType, file::File},
+        store::MemoryStore,
+    };
+
+    fn created(path: &str, days_ago: i64) -> File {
+        let mut file = File::missing(EventType::Created, path.to_string());
+        file.timestamp = Utc::now() - Duration::days(days_ago);
+        file
+    }
+
+    async fn found(store: &dyn Store, text: &str) -> Vec<String> {
+        search(store, text, &FileQuery::new().limit(5))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.file.path)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn finds_words_with_a_typo_in_their_first_letters() {
+        let store = MemoryStore::default();
+        store
+            .insert(vec![
+                created("/docs/invoice-march.pdf", 3),
+                created("/docs/notes.txt", 2),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(found(&store, "invoce").await, ["/docs/invoice-march.pdf"]);
+        assert_eq!(found(&store, "ivnoice").await, ["/docs/invoice-march.pdf"]);
+        assert_eq!(
+            found(&store, "nivoice pdf").await,
+            ["/docs/invoice-march.pdf"]
+        );
+        assert!(found(&store, "zzzzzz").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ranks_every_candidate_before_limiting() {
+        let store = MemoryStore::default();
+        // the best match is older than a few pages of candidates that share
+        // its first letters without resembling it
+        let mut files = vec![created("/docs/invoice.pdf", 400)];
+        files.extend(
+            (0..CANDIDATE_PAGE_SIZE + 500).map(|i| created(&format!("/tmp/inverse-{}.txt", i), 1)),
+        );
+        store.insert(files).await.unwrap();
+
+        assert_eq!(found(&store, "invoice").await, ["/docs/invoice.pdf"]);
+    }
+
+    #[tokio::test]
+    async fn a_typo_in_every_word_only_ranks_the_newest_events() {
+        let store = MemoryStore::default();
+        let mut files = vec![created("/docs/invoice.pdf", 400)];
+        files
+            .extend((0..FALLBACK_CANDIDATES).map(|i| created(&format!("/tmp/notes-{}.txt", i), 1)));
+        store.insert(files).await.unwrap();
+        assert!(found(&store, "ivnoice").await.is_empty());
+
+        store
+            .insert(vec![created("/docs/invoice.odt", 0)])
+            .await
+            .unwrap();
+        assert_eq!(found(&store, "ivnoice").await, ["/docs/invoice.odt"]);
+        // a word the index knows still narrows the candidates down
+        assert_eq!(found(&store, "ivnoice pdf").await, ["/docs/invoice.pdf"]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+/// Splits file names and paths into lowercase words: on anything that
+/// isn't a letter or digit, between letters and digits, and on camelCase
+/// boundaries (`HTMLParser` -> `html`, `parser`). Duplicates are dropped.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for (_, word) in word_spans(text) {
+        if !tokens.contains(&word) {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+/// The words [`tokenize`] finds, each with the byte range it came from in
+/// `text`, duplicates included.
+pub fn word_spans(text: &str) -> Vec<(Range<usize>, String)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut current = String::new();
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            push_span(&mut spans, &mut current, start..offset);
+            continue;
+        }
+
+        if let Some(&(_, prev)) = i.checked_sub(1).and_then(|p| chars.get(p)) {
+            let next = chars.get(i + 1).map(|&(_, n)| n);
+            let boundary = (prev.is_alphabetic() && c.is_numeric())
+                || (prev.is_numeric() && c.is_alphabetic())
+                || (prev.is_lowercase() && c.is_uppercase())
+                || (prev.is_uppercase()
+                    && c.is_uppercase()
+                    && next.is_some_and(|n| n.is_lowercase()));
+            if boundary {
+                push_span(&mut spans, &mut current, start..offset);
+            }
+        }
+        if current.is_empty() {
+            start = offset;
+        }
+        current.extend(c.to_lowercase());
+    }
+    push_span(&mut spans, &mut current, start..text.len());
+
+    spans
+}
+
+fn push_span(spans: &mut Vec<(Range<usize>, String)>, current: &mut String, range: Range<usize>) {
+    if !current.is_empty() {
+        spans.push((range, std::mem::take(current)));
+    }
+}
+
+/// Text stored in the backends' full-text indexes: the path's tokens
+/// separated by spaces.
+pub fn search_text(path: &str) -> String {
+    tokenize(path).join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(text: &str) -> Vec<(&str, String)> {
+        word_spans(text)
+            .into_iter()
+            .map(|(range, word)| (&text[range], word))
+            .collect()
+    }
+
+    fn words(text: &str) -> Vec<String> {
+        word_spans(text).into_iter().map(|(_, w)| w).collect()
+    }
+
+    #[test]
+    fn splits_on_separators() {
+        assert_eq!(
+            words("/home/ana/my report.final.pdf"),
+            ["home", "ana", "my", "report", "final", "pdf"]
+        );
+        assert!(words("/-_. ").is_empty());
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn splits_camel_case() {
+        assert_eq!(words("myFileName"), ["my", "file", "name"]);
+        assert_eq!(words("HTMLParser"), ["html", "parser"]);
+        assert_eq!(words("getURL"), ["get", "url"]);
+        assert_eq!(words("ALLCAPS"), ["allcaps"]);
+    }
+
+    #[test]
+    fn splits_letters_from_digits() {
+        assert_eq!(words("IMG_20250301"), ["img", "20250301"]);
+        assert_eq!(words("file2024report"), ["file", "2024", "report"]);
+        assert_eq!(words("v2Beta3"), ["v", "2", "beta", "3"]);
+    }
+
+    #[test]
+    fn keeps_unicode_words_and_their_byte_ranges() {
+        assert_eq!(
+            spans("Résumé_Été.pdf"),
+            [
+                ("Résumé", "résumé".to_string()),
+                ("Été", "été".to_string()),
+                ("pdf", "pdf".to_string()),
+            ]
+        );
+        assert_eq!(words("报告2025.docx"), ["报告", "2025", "docx"]);
+        assert_eq!(words("straßeÜber"), ["straße", "über"]);
+    }
+
+    #[test]
+    fn tokenize_drops_duplicates() {
+        assert_eq!(tokenize("/a/b/A/b.txt"), ["a", "b", "txt"]);
+        assert_eq!(search_text("/photos/Cat.jpg"), "photos cat jpg");
+    }
+}
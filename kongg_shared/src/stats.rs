@@ -0,0 +1,236 @@
+//! Aggregates over the event log. The SQL backends compute them in the
+//! database; [`aggregate_events`] and [`largest_deleted_in`] do the same in
+//! memory for the others.
+//!
+//! Deletions don't record a size, so their bytes are the size the file had
+//! at its path just before, which makes summing them the space freed.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::models::{event::EventType, file::FileResponse, query::FileQuery};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// `YYYY-MM-DD` in local time, like the days of the time filters.
+    Day,
+    /// Parent directory.
+    Directory,
+    /// Lowercase, without the dot.
+    Extension,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Bucket {
+    /// Day, directory or extension; empty when the events have none.
+    pub key: String,
+    pub event_type: EventType,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// `count` and `bytes` of `buckets` per key, every event type together.
+pub fn totals_by_key(buckets: &[Bucket]) -> BTreeMap<&str, (u64, u64)> {
+    let mut totals = BTreeMap::new();
+    for bucket in buckets {
+        let (count, bytes) = totals.entry(bucket.key.as_str()).or_insert((0, 0));
+        *count += bucket.count;
+        *bytes += bucket.bytes;
+    }
+    totals
+}
+
+/// `buckets` with their keys replaced by `rekey`, merging those that end
+/// up with the same key and event type.
+fn merge(buckets: Vec<Bucket>, rekey: impl Fn(&str) -> String) -> Vec<Bucket> {
+    let mut merged: BTreeMap<(String, EventType), Bucket> = BTreeMap::new();
+    for bucket in buckets {
+        let key = rekey(&bucket.key);
+        let into = merged
+            .entry((key.clone(), bucket.event_type))
+            .or_insert(Bucket {
+                key,
+                event_type: bucket.event_type,
+                count: 0,
+                bytes: 0,
+            });
+        into.count += bucket.count;
+        into.bytes += bucket.bytes;
+    }
+    merged.into_values().collect()
+}
+
+/// Day buckets folded into ISO weeks, keyed `YYYY-Www`. Keys that aren't
+/// days are kept as they are.
+pub fn by_week(days: Vec<Bucket>) -> Vec<Bucket> {
+    merge(days, |key| {
+        match NaiveDate::parse_from_str(key, "%Y-%m-%d") {
+            Ok(day) => {
+                let week = day.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Err(_) => key.to_string(),
+        }
+    })
+}
+
+/// The local day of an instant, as [`GroupBy::Day`] keys it.
+pub fn local_day(timestamp: &DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Buckets keyed by RFC 3339 instants folded into local days, for backends
+/// that can only group by UTC time. Every time zone is a whole number of
+/// quarter hours away from UTC, so quarter-hour buckets fold exactly.
+pub(crate) fn into_local_days(buckets: Vec<Bucket>) -> Vec<Bucket> {
+    merge(buckets, |key| match DateTime::parse_from_rfc3339(key) {
+        Ok(instant) => local_day(&instant.with_timezone(&Utc)),
+        Err(_) => key.to_string(),
+    })
+}
+
+/// Pairs every event with its bytes: its own size, or for a deletion the
+/// last size seen at its path. `events` must hold every earlier event of
+/// the deleted paths, oldest first, for deletions to find those sizes.
+fn sized(events: impl IntoIterator<Item = FileResponse>) -> Vec<(FileResponse, u64)> {
+    let mut last_size: HashMap<(Option<String>, String), u64> = HashMap::new();
+    let mut sized = Vec::new();
+    for event in events {
+        let key = (
+            event.origin.as_ref().and(event.host.clone()),
+            event.path.clone(),
+        );
+        let bytes = match event.event_type {
+            EventType::Deleted => last_size.get(&key).copied().unwrap_or(0),
+            _ => event.file_size.unwrap_or(0),
+        };
+        if let Some(size) = event.file_size {
+            last_size.insert(key, size);
+        }
+        sized.push((event, bytes));
+    }
+    sized
+}
+
+fn key_of(event: &FileResponse, group: GroupBy) -> String {
+    match group {
+        GroupBy::Day => local_day(&event.timestamp),
+        GroupBy::Directory => event.parent_directory.clone().unwrap_or_default(),
+        GroupBy::Extension => event
+            .extension
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase(),
+    }
+}
+
+/// [`crate::store::Store::aggregate`] over a whole log held in memory,
+/// oldest first.
+pub fn aggregate_events(
+    events: impl IntoIterator<Item = FileResponse>,
+    filter: &FileQuery,
+    group: GroupBy,
+) -> Vec<Bucket> {
+    let mut buckets: BTreeMap<(String, EventType), Bucket> = BTreeMap::new();
+    for (event, bytes) in sized(events) {
+        if !filter.matches(&event) {
+            continue;
+        }
+        let key = key_of(&event, group);
+        let bucket = buckets
+            .entry((key.clone(), event.event_type))
+            .or_insert(Bucket {
+                key,
+                event_type: event.event_type,
+                count: 0,
+                bytes: 0,
+            });
+        bucket.count += 1;
+        bucket.bytes += bytes;
+    }
+    buckets.into_values().collect()
+}
+
+/// [`crate::store::Store::largest_deleted`] over a whole log held in
+/// memory, oldest first.
+pub fn largest_deleted_in(
+    events: impl IntoIterator<Item = FileResponse>,
+    filter: &FileQuery,
+    limit: usize,
+) -> Vec<(FileResponse, u64)> {
+    let mut deleted: Vec<(FileResponse, u64)> = sized(events)
+        .into_iter()
+        .filter(|(e, bytes)| e.event_type == EventType::Deleted && *bytes > 0 && filter.matches(e))
+        .collect();
+    deleted.sort_by_key(|(_, bytes)| Reverse(*bytes));
+    deleted.truncate(limit);
+    deleted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(key: &str, count: u64) -> Bucket {
+        Bucket {
+            key: key.to_string(),
+            event_type: EventType::Created,
+            count,
+            bytes: count * 10,
+        }
+    }
+
+    #[test]
+    fn quarter_hours_fold_into_local_days() {
+        let quarters = [
+            "2025-03-01T23:30:00Z",
+            "2025-03-01T23:45:00Z",
+            "2025-03-02T00:00:00Z",
+        ];
+        let mut expected: Vec<Bucket> = Vec::new();
+        for (i, quarter) in quarters.iter().enumerate() {
+            let instant = DateTime::parse_from_rfc3339(quarter).unwrap();
+            let day = local_day(&instant.with_timezone(&Utc));
+            match expected.iter_mut().find(|b| b.key == day) {
+                Some(b) => {
+                    b.count += i as u64 + 1;
+                    b.bytes += (i as u64 + 1) * 10;
+                }
+                None => expected.push(bucket(&day, i as u64 + 1)),
+            }
+        }
+
+        let buckets = quarters
+            .iter()
+            .enumerate()
+            .map(|(i, q)| bucket(q, i as u64 + 1))
+            .collect();
+        assert_eq!(into_local_days(buckets), expected);
+    }
+
+    #[test]
+    fn days_fold_into_iso_weeks() {
+        let weeks = by_week(vec![
+            bucket("2024-12-30", 1),
+            bucket("2025-01-05", 2),
+            bucket("2025-01-06", 4),
+            bucket("/home", 8),
+        ]);
+        assert_eq!(
+            weeks,
+            [
+                bucket("/home", 8),
+                bucket("2025-W01", 3),
+                bucket("2025-W02", 4)
+            ]
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use std::{collections::HashSet, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Error, Result,
+    config::{Config, KeySource, StorageConfig},
+    crypto::{Keyring, add_key, retire_keys, unlock},
+    helpers::inventory::{is_under, replay},
+    models::{
+        file::{File, FileResponse},
+        query::{FileQuery, Page, SortField},
+        status::DaemonStatus,
+    },
+    search::has_terms,
+    store::{
+        SCAN_PAGE_SIZE, Store, ensure_daemon_stopped, fresh_path, open, remove_store, scan, swap_in,
+    },
+};
+
+/// Records rewritten together by a rotation.
+const RESEAL_BATCH_SIZE: usize = 500;
+
+/// Seals paths, names and hashes before they reach the wrapped store and
+/// opens them on the way back. Event types, sizes, times and the host
+/// fields stay readable, so filters on them still run in the database;
+/// filters on paths and full-text search decrypt and match in memory.
+pub struct EncryptedStore {
+    inner: Arc<dyn Store>,
+    keyring: Keyring,
+}
+
+impl EncryptedStore {
+    pub fn new(inner: Arc<dyn Store>, keyring: Keyring) -> Self {
+        Self { inner, keyring }
+    }
+
+    fn seal(&self, mut file: File) -> Result<File> {
+        let seal = |value: Option<String>| value.map(|v| self.keyring.seal(&v)).transpose();
+        file.path = self.keyring.seal(&file.path)?;
+        file.previous_path = seal(file.previous_path)?;
+        file.file_name = seal(file.file_name)?;
+        file.extension = seal(file.extension)?;
+        file.parent_directory = seal(file.parent_directory)?;
+        file.root = seal(file.root)?;
+        file.hash = seal(file.hash)?;
+        file.previous_hash = seal(file.previous_hash)?;
+        Ok(file)
+    }
+
+    fn open(&self, mut file: FileResponse) -> Result<FileResponse> {
+        let open = |value: Option<String>| value.map(|v| self.keyring.open(v)).transpose();
+        file.path = self.keyring.open(file.path)?;
+        file.previous_path = open(file.previous_path)?;
+        file.file_name = open(file.file_name)?;
+        file.extension = open(file.extension)?;
+        file.parent_directory = open(file.parent_directory)?;
+        file.root = open(file.root)?;
+        file.hash = open(file.hash)?;
+        file.previous_hash = open(file.previous_hash)?;
+        Ok(file)
+    }
+
+    /// Whether a rotation still has to rewrite the record.
+    fn is_stale(&self, file: &FileResponse) -> bool {
+        [
+            Some(&file.path),
+            file.previous_path.as_ref(),
+            file.file_name.as_ref(),
+            file.extension.as_ref(),
+            file.parent_directory.as_ref(),
+            file.root.as_ref(),
+            file.hash.as_ref(),
+            file.previous_hash.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| !self.keyring.is_current(value))
+    }
+
+    /// Every record passing the filters that can run on sealed records,
+    /// oldest first and still sealed.
+    async fn scan(&self, query: &FileQuery) -> Result<Vec<FileResponse>> {
+        scan(self.inner.as_ref(), &clear_filters(query)).await
+    }
+
+    /// Seals every record that isn't sealed with the current key yet and
+    /// returns how many were rewritten.
+    pub async fn reseal(&self) -> Result<u64> {
+        let mut rewritten = 0;
+        let mut batch = Vec::with_capacity(RESEAL_BATCH_SIZE);
+        for file in self.scan(&FileQuery::new()).await? {
+            if !self.is_stale(&file) {
+                continue;
+            }
+
+            let id = file.id.clone();
+            batch.push((id, self.seal(self.open(file)?.into())?));
+            if batch.len() >= RESEAL_BATCH_SIZE {
+                rewritten += batch.len() as u64;
+                self.inner.update(std::mem::take(&mut batch)).await?;
+            }
+        }
+        rewritten += batch.len() as u64;
+        self.inner.update(batch).await?;
+        Ok(rewritten)
+    }
+
+    /// One page of the records `keep` lets through, in the order and from
+    /// the position `query` asks for.
+    async fn page_where(
+        &self,
+        query: &FileQuery,
+        keep: impl Fn(&FileResponse) -> bool + Send,
+    ) -> Result<Page> {
+        // names and paths sort differently once sealed, so those orders
+        // need every matching record in hand
+        if matches!(query.sort, SortField::Name | SortField::Path) {
+            let mut events = Vec::new();
+            for file in self.scan(query).await? {
+                let file = self.open(file)?;
+                if keep(&file) {
+                    events.push(file);
+                }
+            }
+            return Ok(query.page_of(events));
+        }
+
+        // times and sizes are in clear text, so the wrapped store can page
+        // in the requested order and only the remaining filters run here
+        let mut inner = clear_filters(query)
+            .sort(query.sort, query.order)
+            .limit(SCAN_PAGE_SIZE);
+        inner.cursor = query.cursor.clone();
+        let mut items = Vec::new();
+        loop {
+            let page = self.inner.query(&inner).await?;
+            for file in page.items {
+                let file = self.open(file)?;
+                if keep(&file) {
+                    items.push(file);
+                }
+                if items.len() > query.page_size() {
+                    return Ok(query.paginate(items));
+                }
+            }
+            match page.next {
+                Some(cursor) => inner = inner.after(cursor),
+                None => return Ok(query.paginate(items)),
+            }
+        }
+    }
+}
+
+/// The filters of `query` on fields that are stored in clear text.
+fn clear_filters(query: &FileQuery) -> FileQuery {
+    FileQuery {
+        event_types: query.event_types.clone(),
+        min_size: query.min_size,
+        max_size: query.max_size,
+        since: query.since,
+        until: query.until,
+        host: query.host.clone(),
+        machine_id: query.machine_id.clone(),
+        username: query.username.clone(),
+        ..FileQuery::default()
+    }
+}
+
+#[async_trait]
+impl Store for EncryptedStore {
+    async fn insert(&self, files: Vec<File>) -> Result<()> {
+        let files = files
+            .into_iter()
+            .map(|f| self.seal(f))
+            .collect::<Result<_>>()?;
+        self.inner.insert(files).await
+    }
+
+    async fn replace_all(&self, files: Vec<File>) -> Result<()> {
+        let files = files
+            .into_iter()
+            .map(|f| self.seal(f))
+            .collect::<Result<_>>()?;
+        self.inner.replace_all(files).await
+    }
+
+    async fn update(&self, records: Vec<(RecordId, File)>) -> Result<()> {
+        let records = records
+            .into_iter()
+            .map(|(id, f)| Ok((id, self.seal(f)?)))
+            .collect::<Result<_>>()?;
+        self.inner.update(records).await
+    }
+
+    async fn query(&self, query: &FileQuery) -> Result<Page> {
+        self.page_where(query, |file| query.matches(file)).await
+    }
+
+    async fn snapshot(&self, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+        self.inner
+            .snapshot(until)
+            .await?
+            .into_iter()
+            .map(|file| self.open(file))
+            .collect()
+    }
+
+    async fn search_candidates(&self, terms: &[String], filter: &FileQuery) -> Result<Page> {
+        self.page_where(filter, |file| {
+            filter.matches(file) && has_terms(&file.path, terms)
+        })
+        .await
+    }
+
+    async fn existing_origins(&self, origins: &[String]) -> Result<HashSet<String>> {
+        self.inner.existing_origins(origins).await
+    }
+
+    async fn inventory_at(
+        &self,
+        root: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileResponse>> {
+        let mut events = Vec::new();
+        for file in self.scan(&FileQuery::new()).await? {
+            if file.origin.is_some() || at.is_some_and(|at| file.timestamp > at) {
+                continue;
+            }
+
+            let file = self.open(file)?;
+            let touches_root = is_under(&file.path, root)
+                || file
+                    .previous_path
+                    .as_deref()
+                    .is_some_and(|p| is_under(p, root));
+            if touches_root {
+                events.push(file);
+            }
+        }
+
+        let mut files = replay(events);
+        files.retain(|f| is_under(&f.path, root));
+        Ok(files)
+    }
+
+    async fn save_status(&self, status: DaemonStatus) -> Result<()> {
+        self.inner.save_status(status).await
+    }
+
+    async fn status(&self) -> Result<Option<DaemonStatus>> {
+        self.inner.status().await
+    }
+
+    async fn migrate(&self) -> Result<u32> {
+        self.inner.migrate().await
+    }
+
+    async fn schema_version(&self) -> Result<u32> {
+        self.inner.schema_version().await
+    }
+
+    fn latest_schema_version(&self) -> u32 {
+        self.inner.latest_schema_version()
+    }
+
+    async fn malformed(&self) -> Result<Vec<(RecordId, String)>> {
+        self.inner.malformed().await
+    }
+
+    async fn delete(&self, ids: Vec<RecordId>) -> Result<u64> {
+        self.inner.delete(ids).await
+    }
+
+    fn keeps_versions(&self) -> bool {
+        self.inner.keeps_versions()
+    }
+
+    async fn compact(&self) -> Result<()> {
+        self.inner.compact().await
+    }
+}
+
+/// Records written with a key that is about to be dropped would become
+/// unreadable, so keys only change while the daemon is stopped.
+const KEY_CHANGE: &str = "changing the encryption key";
+
+/// Adds a key to `config`, reseals every record of `store` with it, drops
+/// what the storage kept of the old records and then the keys it replaces.
+/// The config is saved after each step, so an interrupted run leaves every
+/// record readable and can be repeated.
+async fn switch_key(
+    store: Arc<dyn Store>,
+    config: &mut Config,
+    previous: Option<Keyring>,
+    prompt: impl Fn(&str) -> Result<String>,
+) -> Result<u64> {
+    let keyring = add_key(&mut config.encryption, previous, &prompt)?;
+    config.encryption.enabled = true;
+    config.save()?;
+
+    let encrypted = EncryptedStore::new(store, keyring);
+    let (rewritten, keyring) = if encrypted.keeps_versions() {
+        // rewriting in place would leave the old records in the history
+        reseal_into_fresh_store(encrypted, &config.storage).await?
+    } else {
+        let rewritten = encrypted.reseal().await?;
+        encrypted.compact().await?;
+        (rewritten, encrypted.keyring)
+    };
+
+    retire_keys(&mut config.encryption, &keyring)?;
+    config.save()?;
+    Ok(rewritten)
+}
+
+/// Writes every record of `encrypted`, sealed with the current key, to a
+/// new store next to the one `storage` points at and swaps it in, so the
+/// earlier versions go with the old store. Records get new ids. Returns how
+/// many were written and the keyring, once both stores are closed.
+async fn reseal_into_fresh_store(
+    encrypted: EncryptedStore,
+    storage: &StorageConfig,
+) -> Result<(u64, Keyring)> {
+    let path = storage.resolved_path()?;
+    let fresh = StorageConfig {
+        path: Some(fresh_path(&path)),
+        ..storage.clone()
+    };
+    // left over from an interrupted run, and incomplete
+    remove_store(&fresh_path(&path))?;
+
+    let files: Vec<File> = scan(&encrypted, &FileQuery::new())
+        .await?
+        .into_iter()
+        .map(File::from)
+        .collect();
+    let written = files.len() as u64;
+    let status = encrypted.status().await?;
+    let EncryptedStore { inner, keyring } = encrypted;
+    drop(inner);
+
+    let encrypted = EncryptedStore::new(open(&fresh).await?, keyring);
+    encrypted.insert(files).await?;
+    if let Some(status) = status {
+        encrypted.save_status(status).await?;
+    }
+    let EncryptedStore { inner, keyring } = encrypted;
+    drop(inner);
+
+    swap_in(&path)?;
+    Ok((written, keyring))
+}
+
+/// Turns on encryption for the unencrypted `store` and seals what it
+/// already holds. Returns how many records were sealed.
+pub async fn encrypt(
+    store: Arc<dyn Store>,
+    config: &mut Config,
+    key_source: KeySource,
+    prompt: impl Fn(&str) -> Result<String>,
+) -> Result<u64> {
+    if config.encryption.enabled {
+        return Err(Error::Config(
+            "the store is already encrypted, rotate the key instead".to_string(),
+        ));
+    }
+    ensure_daemon_stopped(store.as_ref(), KEY_CHANGE).await?;
+
+    config.encryption.key_source = key_source;
+    config.encryption.keys.clear();
+    switch_key(store, config, None, prompt).await
+}
+
+/// Replaces the current key of the encrypted `store` with a new one and
+/// reseals every record with it. Returns how many records were rewritten.
+pub async fn rotate_key(
+    store: Arc<dyn Store>,
+    config: &mut Config,
+    prompt: impl Fn(&str) -> Result<String>,
+) -> Result<u64> {
+    if !config.encryption.enabled {
+        return Err(Error::Config("the store isn't encrypted".to_string()));
+    }
+    ensure_daemon_stopped(store.as_ref(), KEY_CHANGE).await?;
+
+    let previous = unlock(&config.encryption, &prompt)?;
+    switch_key(store, config, Some(previous), prompt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        config::{Backend, EncryptionConfig},
+        crypto::Key,
+        models::event::EventType,
+        store::{SqliteStore, scan},
+    };
+
+    fn secret() -> File {
+        File::missing(EventType::Created, "/w/secret-plan.txt".to_string())
+    }
+
+    #[tokio::test]
+    async fn sealing_leaves_no_clear_text_on_disk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("kongg.sqlite");
+        let sqlite: Arc<dyn Store> =
+            Arc::new(SqliteStore::open(path.to_str().unwrap()).await.unwrap());
+        sqlite.insert(vec![secret(); 50]).await.unwrap();
+
+        let encrypted = EncryptedStore::new(
+            Arc::clone(&sqlite),
+            Keyring::new(vec![Key::generate(1)]).unwrap(),
+        );
+        assert_eq!(encrypted.reseal().await.unwrap(), 50);
+        encrypted.compact().await.unwrap();
+
+        for entry in std::fs::read_dir(dir.path()).unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            // the search index holds the words one by one
+            for word in [&b"secret"[..], b"plan"] {
+                assert!(!bytes.windows(word.len()).any(|w| w == word));
+            }
+        }
+        let files = scan(&encrypted, &FileQuery::new()).await.unwrap();
+        assert_eq!(files[0].path, "/w/secret-plan.txt");
+    }
+
+    /// Every file below `dir`.
+    fn files_under(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(files_under(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    #[tokio::test]
+    async fn rotating_a_versioned_store_leaves_no_old_seal_on_disk() {
+        let dir = TempDir::new().unwrap();
+        let storage = StorageConfig {
+            backend: Backend::SurrealKv,
+            path: Some(dir.path().join("kongg-db").to_string_lossy().to_string()),
+        };
+        let mut keys = EncryptionConfig {
+            key_source: KeySource::KeyFile,
+            key_file: Some(dir.path().join("keys").to_string_lossy().to_string()),
+            ..EncryptionConfig::default()
+        };
+        let no_prompt = |_: &str| -> Result<String> { unreachable!() };
+
+        let sealed = EncryptedStore::new(
+            open(&storage).await.unwrap(),
+            add_key(&mut keys, None, no_prompt).unwrap(),
+        );
+        sealed.insert(vec![secret(); 50]).await.unwrap();
+        sealed.save_status(DaemonStatus::default()).await.unwrap();
+        let first = scan(sealed.inner.as_ref(), &FileQuery::new())
+            .await
+            .unwrap();
+        // the part of the sealed path that differs per record
+        let old_seals: Vec<String> = first
+            .iter()
+            .map(|f| f.path[f.path.len() - 32..].to_string())
+            .collect();
+
+        let EncryptedStore { inner, keyring } = sealed;
+        let rotating =
+            EncryptedStore::new(inner, add_key(&mut keys, Some(keyring), no_prompt).unwrap());
+        assert!(rotating.keeps_versions());
+        let (written, keyring) = reseal_into_fresh_store(rotating, &storage).await.unwrap();
+        assert_eq!(written, 50);
+        retire_keys(&mut keys, &keyring).unwrap();
+
+        // only the new key is left, and it opens every record
+        let rotated = EncryptedStore::new(
+            open(&storage).await.unwrap(),
+            unlock(&keys, no_prompt).unwrap(),
+        );
+        let files = scan(&rotated, &FileQuery::new()).await.unwrap();
+        assert_eq!(files.len(), 50);
+        assert!(files.iter().all(|f| f.path == "/w/secret-plan.txt"));
+        assert!(rotated.status().await.unwrap().is_some());
+
+        for file in files_under(dir.path()) {
+            let bytes = std::fs::read(&file).unwrap();
+            for seal in &old_seals {
+                assert!(
+                    !bytes.windows(seal.len()).any(|w| w == seal.as_bytes()),
+                    "{} still holds a record sealed with the old key",
+                    file.display()
+                );
+            }
+        }
+        let path = storage.path.unwrap();
+        assert!(!Path::new(&fresh_path(&path)).exists());
+        assert!(!Path::new(&format!("{}.retired", path)).exists());
+    }
+}
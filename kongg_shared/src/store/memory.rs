@@ -0,0 +1,175 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        RwLock,
+        atomic::{AtomicI64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Result,
+    constants::FILE_TABLE_NAME,
+    helpers::inventory::{is_under, replay},
+    models::{
+        file::{File, FileResponse},
+        query::{FileQuery, Page},
+        status::DaemonStatus,
+    },
+    search::has_terms,
+    store::Store,
+};
+
+/// Keeps everything in process memory; nothing survives a restart. Handy
+/// for tests and for trying the tools without touching the real log.
+#[derive(Default)]
+pub struct MemoryStore {
+    events: RwLock<Vec<FileResponse>>,
+    status: RwLock<Option<DaemonStatus>>,
+    next_id: AtomicI64,
+}
+
+impl MemoryStore {
+    fn append(&self, events: &mut Vec<FileResponse>, files: Vec<File>) {
+        for file in files {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            events.push(file.into_response(RecordId::from_table_key(FILE_TABLE_NAME, id)));
+        }
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn insert(&self, files: Vec<File>) -> Result<()> {
+        self.append(&mut self.events.write().unwrap(), files);
+        Ok(())
+    }
+
+    async fn replace_all(&self, files: Vec<File>) -> Result<()> {
+        let mut events = self.events.write().unwrap();
+        events.clear();
+        self.append(&mut events, files);
+        Ok(())
+    }
+
+    async fn update(&self, records: Vec<(RecordId, File)>) -> Result<()> {
+        let mut events = self.events.write().unwrap();
+        for (id, file) in records {
+            if let Some(event) = events.iter_mut().find(|e| e.id == id) {
+                *event = file.into_response(id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn query(&self, query: &FileQuery) -> Result<Page> {
+        let events: Vec<FileResponse> = self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| query.matches(e))
+            .cloned()
+            .collect();
+        Ok(query.page_of(events))
+    }
+
+    async fn search_candidates(&self, terms: &[String], filter: &FileQuery) -> Result<Page> {
+        let events: Vec<FileResponse> = self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| filter.matches(e) && has_terms(&e.path, terms))
+            .cloned()
+            .collect();
+        Ok(filter.page_of(events))
+    }
+
+    async fn snapshot(&self, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+        let mut events: Vec<FileResponse> = self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.timestamp < until)
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| e.timestamp);
+        Ok(events)
+    }
+
+    async fn existing_origins(&self, origins: &[String]) -> Result<HashSet<String>> {
+        Ok(self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|e| e.origin.as_ref())
+            .filter(|o| origins.contains(o))
+            .cloned()
+            .collect())
+    }
+
+    async fn inventory_at(
+        &self,
+        root: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileResponse>> {
+        let mut events: Vec<FileResponse> = self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.origin.is_none() && at.is_none_or(|at| e.timestamp <= at))
+            .filter(|e| {
+                is_under(&e.path, root)
+                    || e.previous_path
+                        .as_deref()
+                        .is_some_and(|p| is_under(p, root))
+            })
+            .cloned()
+            .collect();
+        events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+        let mut files = replay(events);
+        files.retain(|f| is_under(&f.path, root));
+        Ok(files)
+    }
+
+    async fn save_status(&self, status: DaemonStatus) -> Result<()> {
+        *self.status.write().unwrap() = Some(status);
+        Ok(())
+    }
+
+    async fn status(&self) -> Result<Option<DaemonStatus>> {
+        Ok(self.status.read().unwrap().clone())
+    }
+
+    async fn migrate(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    async fn schema_version(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn latest_schema_version(&self) -> u32 {
+        0
+    }
+
+    async fn malformed(&self) -> Result<Vec<(RecordId, String)>> {
+        // records are typed all the way through
+        Ok(Vec::new())
+    }
+
+    async fn delete(&self, ids: Vec<RecordId>) -> Result<u64> {
+        let mut events = self.events.write().unwrap();
+        let before = events.len();
+        events.retain(|e| !ids.contains(&e.id));
+        Ok((before - events.len()) as u64)
+    }
+}
@@ -0,0 +1,351 @@
+use std::{collections::HashSet, fs, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Error, Result,
+    config::{Backend, Config, RetentionConfig, StorageConfig},
+    crypto::{prompt_passphrase, unlock},
+    models::{
+        file::{File, FileResponse},
+        query::{FileQuery, Page, SortField, SortOrder},
+        status::DaemonStatus,
+    },
+    retention::prunable_paths_in,
+    stats::{Bucket, GroupBy, aggregate_events, largest_deleted_in},
+};
+
+pub mod encrypted;
+pub mod memory;
+pub mod sqlite;
+pub mod surreal;
+#[cfg(test)]
+mod tests;
+
+pub use encrypted::EncryptedStore;
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+pub use surreal::SurrealStore;
+
+/// Events read per round trip by [`scan`].
+pub(crate) const SCAN_PAGE_SIZE: usize = 1000;
+
+/// Paths asked for at once by [`events_touching`].
+const PATH_CHUNK_SIZE: usize = 500;
+
+/// Appended to a store's path for the one written to take its place.
+const FRESH_SUFFIX: &str = ".fresh";
+
+/// Appended to a store's path while a fresh one is moved in.
+const RETIRED_SUFFIX: &str = ".retired";
+
+/// Everything the daemon, CLI and TUI need from the event log. Backends are
+/// picked at runtime from [`StorageConfig`], so callers hold an
+/// `Arc<dyn Store>`.
+///
+/// The provided [`Store::aggregate`], [`Store::largest_deleted`] and
+/// [`Store::prunable_paths`] read the events they need through [`scan`] and
+/// work them out in memory, which for a wide filter is most of the log.
+/// Backends that can count in the database override them.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Appends events to the log, all or nothing.
+    async fn insert(&self, files: Vec<File>) -> Result<()>;
+
+    /// Removes every event and appends `files` in their place, all or
+    /// nothing.
+    async fn replace_all(&self, files: Vec<File>) -> Result<()>;
+
+    /// Replaces the content of existing events, keeping their ids, all or
+    /// nothing.
+    async fn update(&self, records: Vec<(RecordId, File)>) -> Result<()>;
+
+    /// One page of events matching `query`.
+    async fn query(&self, query: &FileQuery) -> Result<Page>;
+
+    /// One page of the events whose path has a word starting with each of
+    /// `terms`, narrowed, ordered and paged by `filter` and taken from the
+    /// backend's full-text index. Ranking is left to
+    /// [`crate::search::search`].
+    async fn search_candidates(&self, terms: &[String], filter: &FileQuery) -> Result<Page>;
+
+    /// Every event before `until`, oldest first, as the log stood at one
+    /// instant even while the daemon keeps writing and pruning. The default
+    /// reads page by page and suits backends nothing else writes to.
+    async fn snapshot(&self, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+        scan(self, &FileQuery::new().until(until)).await
+    }
+
+    /// Events matching `filter` counted per `group` and event type.
+    /// Deletions weigh the size the file had before, so their bytes add up
+    /// to the space freed.
+    async fn aggregate(&self, filter: &FileQuery, group: GroupBy) -> Result<Vec<Bucket>> {
+        Ok(aggregate_events(
+            scan(self, &path_bounds(filter)).await?,
+            filter,
+            group,
+        ))
+    }
+
+    /// Deletions matching `filter` with the size the file had before,
+    /// largest first. Deletions of files never seen with a size are left
+    /// out.
+    async fn largest_deleted(
+        &self,
+        filter: &FileQuery,
+        limit: usize,
+    ) -> Result<Vec<(FileResponse, u64)>> {
+        Ok(largest_deleted_in(
+            scan(self, &path_bounds(filter)).await?,
+            filter,
+            limit,
+        ))
+    }
+
+    /// Paths with events `config` may remove as of `now`: older than their
+    /// type allows, or several of a `collapse_daily` type on one day, plus
+    /// every path of the roots over `max_rows_per_root`. Pruning only reads
+    /// the events of these.
+    async fn prunable_paths(
+        &self,
+        config: &RetentionConfig,
+        now: DateTime<Utc>,
+    ) -> Result<HashSet<String>> {
+        // the cap counts every event of a root, the other rules only their
+        // own types
+        let mut filter = FileQuery::new();
+        if config.max_rows_per_root.is_none() {
+            filter.event_types = config.max_age_days.keys().copied().collect();
+            filter.event_types.extend(&config.collapse_daily);
+            if filter.event_types.is_empty() {
+                return Ok(HashSet::new());
+            }
+        }
+        Ok(prunable_paths_in(&scan(self, &filter).await?, config, now))
+    }
+
+    /// Which of `origins` are already in the log, so imports can skip them.
+    async fn existing_origins(&self, origins: &[String]) -> Result<HashSet<String>>;
+
+    /// Latest record of every path that currently exists under `root`.
+    async fn inventory(&self, root: &str) -> Result<Vec<FileResponse>> {
+        self.inventory_at(root, None).await
+    }
+
+    /// What `root` contained at `at` according to the locally captured
+    /// events (imported ones are left out), including files that were
+    /// deleted afterwards. `None` means now.
+    async fn inventory_at(
+        &self,
+        root: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileResponse>>;
+
+    async fn save_status(&self, status: DaemonStatus) -> Result<()>;
+
+    async fn status(&self) -> Result<Option<DaemonStatus>>;
+
+    /// Brings the schema up to date and returns its version.
+    async fn migrate(&self) -> Result<u32>;
+
+    /// Version of the schema as it is, without migrating.
+    async fn schema_version(&self) -> Result<u32>;
+
+    /// Newest schema version this build knows about.
+    fn latest_schema_version(&self) -> u32;
+
+    /// Records that can't be read back as a [`FileResponse`], with the
+    /// reason.
+    async fn malformed(&self) -> Result<Vec<(RecordId, String)>>;
+
+    /// Removes events by id and returns how many were removed.
+    async fn delete(&self, ids: Vec<RecordId>) -> Result<u64>;
+
+    /// Whether earlier versions of rewritten and removed records stay in
+    /// the storage, where [`Store::compact`] can't reach them.
+    fn keeps_versions(&self) -> bool {
+        false
+    }
+
+    /// Lets go of what the storage still holds of rewritten and removed
+    /// records, so their old content is gone from disk.
+    async fn compact(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The conditions of `filter` that keep or drop every event of a path alike
+/// and never an earlier one alone, so that a deletion read through them
+/// still finds the size its file had before.
+fn path_bounds(filter: &FileQuery) -> FileQuery {
+    FileQuery {
+        paths: filter.paths.clone(),
+        path_prefix: filter.path_prefix.clone(),
+        glob: filter.glob.clone(),
+        extensions: filter.extensions.clone(),
+        until: filter.until,
+        root: filter.root.clone(),
+        text: filter.text.clone(),
+        ..FileQuery::default()
+    }
+}
+
+/// Every event matching `filter`, oldest first, read a page at a time.
+pub async fn scan<S: Store + ?Sized>(store: &S, filter: &FileQuery) -> Result<Vec<FileResponse>> {
+    let mut query = filter
+        .clone()
+        .sort(SortField::Timestamp, SortOrder::Asc)
+        .limit(SCAN_PAGE_SIZE);
+    query.cursor = None;
+    let mut events = Vec::new();
+    loop {
+        let page = store.query(&query).await?;
+        events.extend(page.items);
+        match page.next {
+            Some(cursor) => query = query.after(cursor),
+            None => return Ok(events),
+        }
+    }
+}
+
+/// Every event at one of `paths` or moved away from one, and in turn those
+/// of the paths they lead to, until no new path turns up. Oldest first.
+pub async fn events_touching<S: Store + ?Sized>(
+    store: &S,
+    paths: impl IntoIterator<Item = String>,
+) -> Result<Vec<FileResponse>> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut pending: Vec<String> = paths
+        .into_iter()
+        .filter(|p| seen.insert(p.clone()))
+        .collect();
+    let mut ids = HashSet::new();
+    let mut events = Vec::new();
+
+    while !pending.is_empty() {
+        let mut found = Vec::new();
+        for chunk in pending.chunks(PATH_CHUNK_SIZE) {
+            for event in scan(store, &FileQuery::new().paths(chunk.iter().cloned())).await? {
+                for path in [Some(&event.path), event.previous_path.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    if seen.insert(path.clone()) {
+                        found.push(path.clone());
+                    }
+                }
+                if ids.insert(event.id.clone()) {
+                    events.push(event);
+                }
+            }
+        }
+        pending = found;
+    }
+
+    // ties in time keep the order the backends page in
+    events.sort_by_key(|e| {
+        let id = e.id.to_string();
+        (e.timestamp, record_key(&id).ok(), id)
+    });
+    Ok(events)
+}
+
+pub async fn open(config: &StorageConfig) -> Result<Arc<dyn Store>> {
+    let path = config.resolved_path()?;
+    if config.backend != Backend::Memory {
+        finish_swap(&path)?;
+    }
+
+    let store: Arc<dyn Store> = match config.backend {
+        Backend::SurrealKv => Arc::new(SurrealStore::open(&path).await?),
+        Backend::Sqlite => Arc::new(SqliteStore::open(&path).await?),
+        Backend::Memory => Arc::new(MemoryStore::default()),
+    };
+    Ok(store)
+}
+
+/// Where a store that replaces the one at `path` is written first.
+pub(crate) fn fresh_path(path: &str) -> String {
+    format!("{}{}", path, FRESH_SUFFIX)
+}
+
+/// Removes a store's directory or file, if there is one.
+pub(crate) fn remove_store(path: &str) -> Result<()> {
+    let removed = match fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => return Ok(()),
+    };
+    removed.map_err(|e| Error::path(path, e))
+}
+
+/// Moves the store at [`fresh_path`] to `path` and removes the one it
+/// replaces. Both stores have to be closed. Every step is a single rename,
+/// and [`finish_swap`] completes an interrupted swap on the next open.
+pub(crate) fn swap_in(path: &str) -> Result<()> {
+    let retired = format!("{}{}", path, RETIRED_SUFFIX);
+    fs::rename(path, &retired).map_err(|e| Error::path(path, e))?;
+    let fresh = fresh_path(path);
+    fs::rename(&fresh, path).map_err(|e| Error::path(fresh, e))?;
+    remove_store(&retired)
+}
+
+/// Completes a [`swap_in`] that stopped after setting the old store aside.
+fn finish_swap(path: &str) -> Result<()> {
+    let retired = format!("{}{}", path, RETIRED_SUFFIX);
+    if !Path::new(&retired).exists() {
+        return Ok(());
+    }
+
+    // the fresh store is only swapped in once it is complete
+    let fresh = fresh_path(path);
+    if !Path::new(path).exists() && Path::new(&fresh).exists() {
+        fs::rename(&fresh, path).map_err(|e| Error::path(fresh, e))?;
+    }
+    if Path::new(path).exists() {
+        remove_store(&retired)
+    } else {
+        fs::rename(&retired, path).map_err(|e| Error::path(retired, e))
+    }
+}
+
+/// Fails while the daemon is running, for work that can't be done under
+/// it; `action` says what, e.g. `"restoring a backup"`.
+pub(crate) async fn ensure_daemon_stopped(store: &dyn Store, action: &str) -> Result<()> {
+    let status = store.status().await?;
+    if status.is_some_and(|s| s.is_running(Utc::now())) {
+        return Err(Error::Config(format!(
+            "the daemon is running, stop it before {}",
+            action
+        )));
+    }
+    Ok(())
+}
+
+/// Numeric key of a record id, for backends that number their rows.
+pub(crate) fn record_key(id: &str) -> Result<i64> {
+    id.rsplit_once(':')
+        .and_then(|(_, key)| key.parse().ok())
+        .ok_or_else(|| Error::Parse(format!("`{}` is not a numeric record id", id)))
+}
+
+/// Opens the store described by `config`, asking for the passphrase first
+/// when it is encrypted.
+pub async fn open_with_config(config: &Config) -> Result<Arc<dyn Store>> {
+    if !config.encryption.enabled {
+        return open(&config.storage).await;
+    }
+
+    let keyring = unlock(&config.encryption, prompt_passphrase)?;
+    Ok(Arc::new(EncryptedStore::new(
+        open(&config.storage).await?,
+        keyring,
+    )))
+}
+
+/// Opens the store described by the user's config file.
+pub async fn open_default() -> Result<Arc<dyn Store>> {
+    open_with_config(&Config::load()?).await
+}
@@ -0,0 +1,653 @@
+use std::{collections::HashSet, str::FromStr};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{
+    QueryBuilder, Row, Sqlite, SqlitePool, Transaction,
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+};
+use surrealdb::RecordId;
+
+use crate::{
+    Error, Result,
+    config::RetentionConfig,
+    constants::{DAEMON_STATUS_ID, FILE_TABLE_NAME},
+    helpers::inventory::{is_under, replay},
+    models::{
+        event::EventType,
+        file::{File, FileResponse},
+        query::{FileQuery, Page, SortField, SortKey, SortOrder},
+        status::DaemonStatus,
+    },
+    search::search_text,
+    stats::{Bucket, GroupBy},
+    store::{SCAN_PAGE_SIZE, Store, record_key},
+    utils::glob::{glob_to_sqlite, matches_name_only},
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// Fixed width so that comparing the stored text compares the instants.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+const FILE_COLUMNS: &str = "id, event_type, path, previous_path, file_name, extension, \
+    file_size, hash, previous_hash, file_id, parent_directory, permissions, is_read_only, timestamp, \
+    root, host, machine_id, username, origin";
+
+/// Event log kept in a plain SQLite file, for people who want to poke at it
+/// with standard SQL tools.
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub async fn open(path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+        let store = Self { pool };
+        store.migrate().await?;
+        store.fill_search_text().await?;
+        Ok(store)
+    }
+
+    /// Indexes rows written before the search index existed. The words come
+    /// from Rust, SQLite can't split camelCase on its own.
+    async fn fill_search_text(&self) -> Result<()> {
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, path FROM file WHERE search_text IS NULL")
+                .fetch_all(&self.pool)
+                .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for (id, path) in rows {
+            sqlx::query("UPDATE file SET search_text = ? WHERE id = ?")
+                .bind(search_text(&path))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+pub(crate) fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
+
+pub(crate) fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| Error::Parse(format!("timestamp `{}`: {}", value, e)))
+}
+
+pub(crate) fn row_to_response(row: &SqliteRow) -> Result<FileResponse> {
+    let id: i64 = row.try_get("id")?;
+    let event_type: String = row.try_get("event_type")?;
+    let file_size: Option<i64> = row.try_get("file_size")?;
+    let timestamp: String = row.try_get("timestamp")?;
+
+    Ok(FileResponse {
+        id: RecordId::from_table_key(FILE_TABLE_NAME, id),
+        event_type: EventType::from_str(&event_type)?,
+        path: row.try_get("path")?,
+        previous_path: row.try_get("previous_path")?,
+        file_name: row.try_get("file_name")?,
+        extension: row.try_get("extension")?,
+        file_size: file_size.map(|s| s as u64),
+        hash: row.try_get("hash")?,
+        previous_hash: row.try_get("previous_hash")?,
+        file_id: row.try_get("file_id")?,
+        parent_directory: row.try_get("parent_directory")?,
+        permissions: row.try_get("permissions")?,
+        is_read_only: row.try_get("is_read_only")?,
+        timestamp: parse_timestamp(&timestamp)?,
+        root: row.try_get("root")?,
+        host: row.try_get("host")?,
+        machine_id: row.try_get("machine_id")?,
+        username: row.try_get("username")?,
+        origin: row.try_get("origin")?,
+    })
+}
+
+fn push_sort_key(builder: &mut QueryBuilder<'_, Sqlite>, key: &SortKey) {
+    match key {
+        SortKey::Timestamp(t) => builder.push_bind(format_timestamp(t)),
+        SortKey::Number(n) => builder.push_bind(*n as i64),
+        SortKey::Text(s) => builder.push_bind(s.clone()),
+    };
+}
+
+/// FTS5 query requiring a word starting with each term.
+fn match_expression(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|t| format!("\"{}\"*", t.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// SQL counterpart of [`FileQuery::matches`], as ` AND ...` conditions on
+/// the `file` columns, and when `terms` is not empty a lookup in the
+/// `file_search` index.
+fn push_filters(builder: &mut QueryBuilder<'static, Sqlite>, query: &FileQuery, terms: &[String]) {
+    if !query.event_types.is_empty() {
+        builder.push(" AND event_type IN (");
+        let mut values = builder.separated(", ");
+        for event_type in &query.event_types {
+            values.push_bind(event_type.to_string());
+        }
+        builder.push(")");
+    }
+    if !query.paths.is_empty() {
+        builder.push(" AND (path IN (");
+        let mut values = builder.separated(", ");
+        for path in &query.paths {
+            values.push_bind(path.clone());
+        }
+        builder.push(") OR previous_path IN (");
+        let mut values = builder.separated(", ");
+        for path in &query.paths {
+            values.push_bind(path.clone());
+        }
+        builder.push("))");
+    }
+    if let Some(prefix) = &query.path_prefix {
+        // not LIKE, which ignores case and reads `_` and `%` as wildcards
+        let dir = format!("{}/", prefix.trim_end_matches(['/', '\\']));
+        builder
+            .push(" AND (path = ")
+            .push_bind(prefix.clone())
+            .push(" OR substr(path, 1, length(")
+            .push_bind(dir.clone())
+            .push(")) = ")
+            .push_bind(dir)
+            .push(")");
+    }
+    if let Some(glob) = &query.glob {
+        let column = if matches_name_only(glob) {
+            "COALESCE(file_name, '')"
+        } else {
+            "path"
+        };
+        builder
+            .push(format!(" AND {} GLOB ", column))
+            .push_bind(glob_to_sqlite(glob));
+    }
+    if !query.extensions.is_empty() {
+        builder.push(" AND LOWER(COALESCE(extension, '')) IN (");
+        let mut values = builder.separated(", ");
+        for extension in &query.extensions {
+            values.push_bind(extension.clone());
+        }
+        builder.push(")");
+    }
+    if let Some(min) = query.min_size {
+        builder
+            .push(" AND COALESCE(file_size, 0) >= ")
+            .push_bind(min as i64);
+    }
+    if let Some(max) = query.max_size {
+        builder
+            .push(" AND COALESCE(file_size, 0) <= ")
+            .push_bind(max as i64);
+    }
+    if let Some(since) = &query.since {
+        builder
+            .push(" AND timestamp >= ")
+            .push_bind(format_timestamp(since));
+    }
+    if let Some(until) = &query.until {
+        builder
+            .push(" AND timestamp < ")
+            .push_bind(format_timestamp(until));
+    }
+    if let Some(root) = &query.root {
+        builder.push(" AND root = ").push_bind(root.clone());
+    }
+    if let Some(host) = &query.host {
+        builder.push(" AND host = ").push_bind(host.clone());
+    }
+    if let Some(machine_id) = &query.machine_id {
+        builder
+            .push(" AND machine_id = ")
+            .push_bind(machine_id.clone());
+    }
+    if let Some(username) = &query.username {
+        builder.push(" AND username = ").push_bind(username.clone());
+    }
+    if let Some(text) = &query.text {
+        builder
+            .push(" AND (INSTR(LOWER(path), ")
+            .push_bind(text.clone())
+            .push(") > 0 OR INSTR(LOWER(COALESCE(file_name, '')), ")
+            .push_bind(text.clone())
+            .push(") > 0)");
+    }
+    if !terms.is_empty() {
+        builder
+            .push(" AND id IN (SELECT rowid FROM file_search WHERE file_search MATCH ")
+            .push_bind(match_expression(terms))
+            .push(")");
+    }
+}
+
+/// Size the file at `f.path` last had before event `f`, from the same log:
+/// the local one, or the events imported from the same host.
+const SIZE_BEFORE: &str = "(SELECT p.file_size FROM file p \
+    WHERE p.path = f.path AND p.file_size IS NOT NULL \
+    AND (p.timestamp < f.timestamp OR (p.timestamp = f.timestamp AND p.id < f.id)) \
+    AND ((p.origin IS NULL AND f.origin IS NULL) \
+    OR (p.origin IS NOT NULL AND f.origin IS NOT NULL AND p.host IS f.host)) \
+    ORDER BY p.timestamp DESC, p.id DESC LIMIT 1)";
+
+/// [`push_filters`] plus keyset pagination.
+fn build_query(query: &FileQuery, terms: &[String]) -> Result<QueryBuilder<'static, Sqlite>> {
+    let sort_expr = match query.sort {
+        SortField::Timestamp => "timestamp",
+        SortField::Size => "COALESCE(file_size, 0)",
+        SortField::Name => "LOWER(COALESCE(file_name, ''))",
+        SortField::Path => "path",
+    };
+    let (direction, before) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+
+    let mut builder = QueryBuilder::new(format!("SELECT {} FROM file WHERE 1 = 1", FILE_COLUMNS));
+    push_filters(&mut builder, query, terms);
+    if let Some(cursor) = &query.cursor {
+        builder.push(format!(" AND ({} {} ", sort_expr, before));
+        push_sort_key(&mut builder, &cursor.key);
+        builder.push(format!(" OR ({} = ", sort_expr));
+        push_sort_key(&mut builder, &cursor.key);
+        builder
+            .push(format!(" AND id {} ", before))
+            .push_bind(record_key(&cursor.id)?)
+            .push("))");
+    }
+
+    builder
+        .push(format!(
+            " ORDER BY {} {}, id {} LIMIT ",
+            sort_expr, direction, direction
+        ))
+        .push_bind((query.page_size() + 1) as i64);
+    Ok(builder)
+}
+
+/// Appends `files` as part of `tx`.
+async fn insert_files(tx: &mut Transaction<'_, Sqlite>, files: Vec<File>) -> Result<()> {
+    for file in files {
+        let search_text = search_text(&file.path);
+        sqlx::query(
+            "INSERT INTO file (event_type, path, previous_path, file_name, extension, \
+             file_size, hash, previous_hash, file_id, parent_directory, permissions, \
+             is_read_only, timestamp, root, host, machine_id, username, origin, \
+             search_text) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(file.event_type.to_string())
+        .bind(file.path)
+        .bind(file.previous_path)
+        .bind(file.file_name)
+        .bind(file.extension)
+        .bind(file.file_size.map(|s| s as i64))
+        .bind(file.hash)
+        .bind(file.previous_hash)
+        .bind(file.file_id)
+        .bind(file.parent_directory)
+        .bind(file.permissions)
+        .bind(file.is_read_only)
+        .bind(format_timestamp(&file.timestamp))
+        .bind(file.root)
+        .bind(file.host)
+        .bind(file.machine_id)
+        .bind(file.username)
+        .bind(file.origin)
+        .bind(search_text)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn insert(&self, files: Vec<File>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        insert_files(&mut tx, files).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_all(&self, files: Vec<File>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM file").execute(&mut *tx).await?;
+        insert_files(&mut tx, files).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn update(&self, records: Vec<(RecordId, File)>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for (id, file) in records {
+            let search_text = search_text(&file.path);
+            sqlx::query(
+                "UPDATE file SET event_type = ?, path = ?, previous_path = ?, file_name = ?, \
+                 extension = ?, file_size = ?, hash = ?, previous_hash = ?, file_id = ?, \
+                 parent_directory = ?, permissions = ?, is_read_only = ?, timestamp = ?, \
+                 root = ?, host = ?, machine_id = ?, username = ?, origin = ?, search_text = ? \
+                 WHERE id = ?",
+            )
+            .bind(file.event_type.to_string())
+            .bind(file.path)
+            .bind(file.previous_path)
+            .bind(file.file_name)
+            .bind(file.extension)
+            .bind(file.file_size.map(|s| s as i64))
+            .bind(file.hash)
+            .bind(file.previous_hash)
+            .bind(file.file_id)
+            .bind(file.parent_directory)
+            .bind(file.permissions)
+            .bind(file.is_read_only)
+            .bind(format_timestamp(&file.timestamp))
+            .bind(file.root)
+            .bind(file.host)
+            .bind(file.machine_id)
+            .bind(file.username)
+            .bind(file.origin)
+            .bind(search_text)
+            .bind(record_key(&id.to_string())?)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn query(&self, query: &FileQuery) -> Result<Page> {
+        let rows = build_query(query, &[])?
+            .build()
+            .fetch_all(&self.pool)
+            .await?;
+        let items = rows
+            .iter()
+            .map(row_to_response)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(query.paginate(items))
+    }
+
+    async fn search_candidates(&self, terms: &[String], filter: &FileQuery) -> Result<Page> {
+        let rows = build_query(filter, terms)?
+            .build()
+            .fetch_all(&self.pool)
+            .await?;
+        let items = rows
+            .iter()
+            .map(row_to_response)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(filter.paginate(items))
+    }
+
+    async fn snapshot(&self, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+        // the pages share one read transaction, so writes committed in
+        // between stay out of them
+        let mut tx = self.pool.begin().await?;
+        let mut query = FileQuery::new()
+            .until(until)
+            .sort(SortField::Timestamp, SortOrder::Asc)
+            .limit(SCAN_PAGE_SIZE);
+        let mut events = Vec::new();
+        loop {
+            let rows = build_query(&query, &[])?
+                .build()
+                .fetch_all(&mut *tx)
+                .await?;
+            let items = rows
+                .iter()
+                .map(row_to_response)
+                .collect::<Result<Vec<_>>>()?;
+            let page = query.paginate(items);
+            events.extend(page.items);
+            match page.next {
+                Some(cursor) => query = query.after(cursor),
+                None => break,
+            }
+        }
+        tx.rollback().await?;
+        Ok(events)
+    }
+
+    async fn aggregate(&self, filter: &FileQuery, group: GroupBy) -> Result<Vec<Bucket>> {
+        let key = match group {
+            GroupBy::Day => "date(timestamp, 'localtime')",
+            GroupBy::Directory => "COALESCE(parent_directory, '')",
+            GroupBy::Extension => "LOWER(COALESCE(extension, ''))",
+        };
+        let mut builder = QueryBuilder::new(format!(
+            "SELECT {} AS key, event_type, COUNT(*) AS count, \
+             SUM(CASE WHEN event_type = 'Deleted' THEN COALESCE({}, 0) \
+             ELSE COALESCE(file_size, 0) END) AS bytes \
+             FROM file f WHERE 1 = 1",
+            key, SIZE_BEFORE
+        ));
+        push_filters(&mut builder, filter, &[]);
+        builder.push(" GROUP BY key, event_type ORDER BY key, event_type");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let event_type: String = row.try_get("event_type")?;
+                let count: i64 = row.try_get("count")?;
+                let bytes: i64 = row.try_get("bytes")?;
+                Ok(Bucket {
+                    key: row.try_get("key")?,
+                    event_type: EventType::from_str(&event_type)?,
+                    count: count as u64,
+                    bytes: bytes as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn largest_deleted(
+        &self,
+        filter: &FileQuery,
+        limit: usize,
+    ) -> Result<Vec<(FileResponse, u64)>> {
+        let mut builder = QueryBuilder::new(format!(
+            "SELECT * FROM (SELECT {}, {} AS size_before FROM file f \
+             WHERE event_type = 'Deleted'",
+            FILE_COLUMNS, SIZE_BEFORE
+        ));
+        push_filters(&mut builder, filter, &[]);
+        builder
+            .push(") WHERE size_before > 0 ORDER BY size_before DESC, id DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let size: i64 = row.try_get("size_before")?;
+                Ok((row_to_response(row)?, size as u64))
+            })
+            .collect()
+    }
+
+    async fn prunable_paths(
+        &self,
+        config: &RetentionConfig,
+        now: DateTime<Utc>,
+    ) -> Result<HashSet<String>> {
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT path FROM file WHERE 0 = 1");
+        for (event_type, days) in &config.max_age_days {
+            builder
+                .push(" OR (event_type = ")
+                .push_bind(event_type.to_string())
+                .push(" AND timestamp < ")
+                .push_bind(format_timestamp(&(now - Duration::days(*days as i64))))
+                .push(")");
+        }
+        if !config.collapse_daily.is_empty() {
+            builder.push(" UNION SELECT path FROM file WHERE event_type IN (");
+            let mut values = builder.separated(", ");
+            for event_type in &config.collapse_daily {
+                values.push_bind(event_type.to_string());
+            }
+            builder.push(
+                ") GROUP BY path, event_type, date(timestamp, 'localtime') HAVING COUNT(*) > 1",
+            );
+        }
+        if let Some(cap) = config.max_rows_per_root {
+            builder
+                .push(
+                    " UNION SELECT f.path FROM file f JOIN \
+                     (SELECT root FROM file GROUP BY root HAVING COUNT(*) > ",
+                )
+                .push_bind(cap as i64)
+                .push(") r ON f.root IS r.root");
+        }
+
+        let paths: Vec<String> = builder.build_query_scalar().fetch_all(&self.pool).await?;
+        Ok(paths.into_iter().collect())
+    }
+
+    async fn existing_origins(&self, origins: &[String]) -> Result<HashSet<String>> {
+        if origins.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT origin FROM file WHERE origin IN (");
+        let mut values = builder.separated(", ");
+        for origin in origins {
+            values.push_bind(origin.clone());
+        }
+        builder.push(")");
+
+        let found: Vec<String> = builder.build_query_scalar().fetch_all(&self.pool).await?;
+        Ok(found.into_iter().collect())
+    }
+
+    async fn inventory_at(
+        &self,
+        root: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileResponse>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM file \
+             WHERE (substr(path, 1, length(?1)) = ?1 \
+             OR substr(previous_path, 1, length(?1)) = ?1) \
+             AND (?2 IS NULL OR timestamp <= ?2) AND origin IS NULL \
+             ORDER BY timestamp ASC, id ASC",
+            FILE_COLUMNS
+        ))
+        .bind(root)
+        .bind(at.as_ref().map(format_timestamp))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let events = rows
+            .iter()
+            .map(row_to_response)
+            .collect::<Result<Vec<_>>>()?;
+        let mut files = replay(events);
+        files.retain(|f| is_under(&f.path, root));
+        Ok(files)
+    }
+
+    async fn save_status(&self, status: DaemonStatus) -> Result<()> {
+        let data = serde_json::to_string(&status).map_err(|e| Error::Parse(e.to_string()))?;
+        sqlx::query(
+            "INSERT INTO status (id, data) VALUES (?, ?) \
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+        )
+        .bind(DAEMON_STATUS_ID)
+        .bind(data)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn status(&self) -> Result<Option<DaemonStatus>> {
+        let data: Option<String> = sqlx::query_scalar("SELECT data FROM status WHERE id = ?")
+            .bind(DAEMON_STATUS_ID)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        data.map(|d| serde_json::from_str(&d).map_err(|e| Error::Parse(e.to_string())))
+            .transpose()
+    }
+
+    async fn migrate(&self) -> Result<u32> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(MIGRATOR.iter().map(|m| m.version as u32).max().unwrap_or(0))
+    }
+
+    async fn schema_version(&self) -> Result<u32> {
+        let version: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(version.unwrap_or(0) as u32)
+    }
+
+    fn latest_schema_version(&self) -> u32 {
+        MIGRATOR.iter().map(|m| m.version as u32).max().unwrap_or(0)
+    }
+
+    async fn malformed(&self) -> Result<Vec<(RecordId, String)>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM file", FILE_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut malformed = Vec::new();
+        for row in &rows {
+            if let Err(e) = row_to_response(row) {
+                let id: i64 = row.try_get("id")?;
+                malformed.push((RecordId::from_table_key(FILE_TABLE_NAME, id), e.to_string()));
+            }
+        }
+        Ok(malformed)
+    }
+
+    async fn compact(&self) -> Result<()> {
+        // the search index keeps the old terms until it is rebuilt,
+        // rebuilding the file drops free pages, and emptying the WAL the
+        // frames that still have the old rows
+        sqlx::query("INSERT INTO file_search(file_search) VALUES('rebuild')")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, ids: Vec<RecordId>) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut removed = 0;
+        for id in ids {
+            removed += sqlx::query("DELETE FROM file WHERE id = ?")
+                .bind(record_key(&id.to_string())?)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(removed)
+    }
+}
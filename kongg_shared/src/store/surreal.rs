@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use surrealdb::{RecordId, Surreal, engine::local::Db};
+
+use crate::{
+    Result,
+    config::RetentionConfig,
+    helpers::{
+        crud::{
+            create_files, get_aggregate, get_existing_origins, get_inventory, get_largest_deleted,
+            get_malformed, get_prunable_paths, get_snapshot, get_status, query_files,
+            query_files_matching, replace_files, save_status, update_files,
+        },
+        migrations::{current_version, latest_version, migrate},
+        surreal_init::init_at,
+    },
+    models::{
+        file::{File, FileResponse},
+        query::{FileQuery, Page},
+        status::DaemonStatus,
+    },
+    stats::{Bucket, GroupBy},
+    store::Store,
+};
+
+/// The default backend: an embedded, versioned SurrealKV database.
+pub struct SurrealStore {
+    db: Surreal<Db>,
+}
+
+impl SurrealStore {
+    pub async fn open(path: &str) -> Result<Self> {
+        Ok(Self {
+            db: init_at(path).await?,
+        })
+    }
+
+    pub fn db(&self) -> &Surreal<Db> {
+        &self.db
+    }
+}
+
+#[async_trait]
+impl Store for SurrealStore {
+    async fn insert(&self, files: Vec<File>) -> Result<()> {
+        create_files(&self.db, files).await
+    }
+
+    async fn replace_all(&self, files: Vec<File>) -> Result<()> {
+        replace_files(&self.db, files).await
+    }
+
+    async fn update(&self, records: Vec<(RecordId, File)>) -> Result<()> {
+        update_files(&self.db, records).await
+    }
+
+    async fn query(&self, query: &FileQuery) -> Result<Page> {
+        query_files(&self.db, query).await
+    }
+
+    async fn search_candidates(&self, terms: &[String], filter: &FileQuery) -> Result<Page> {
+        query_files_matching(&self.db, filter, terms).await
+    }
+
+    async fn snapshot(&self, until: DateTime<Utc>) -> Result<Vec<FileResponse>> {
+        get_snapshot(&self.db, until).await
+    }
+
+    async fn aggregate(&self, filter: &FileQuery, group: GroupBy) -> Result<Vec<Bucket>> {
+        get_aggregate(&self.db, filter, group).await
+    }
+
+    async fn largest_deleted(
+        &self,
+        filter: &FileQuery,
+        limit: usize,
+    ) -> Result<Vec<(FileResponse, u64)>> {
+        get_largest_deleted(&self.db, filter, limit).await
+    }
+
+    async fn prunable_paths(
+        &self,
+        config: &RetentionConfig,
+        now: DateTime<Utc>,
+    ) -> Result<HashSet<String>> {
+        get_prunable_paths(&self.db, config, now).await
+    }
+
+    async fn existing_origins(&self, origins: &[String]) -> Result<HashSet<String>> {
+        get_existing_origins(&self.db, origins).await
+    }
+
+    async fn inventory_at(
+        &self,
+        root: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileResponse>> {
+        get_inventory(&self.db, root, at).await
+    }
+
+    async fn save_status(&self, status: DaemonStatus) -> Result<()> {
+        save_status(&self.db, status).await
+    }
+
+    async fn status(&self) -> Result<Option<DaemonStatus>> {
+        get_status(&self.db).await
+    }
+
+    async fn migrate(&self) -> Result<u32> {
+        migrate(&self.db).await
+    }
+
+    async fn schema_version(&self) -> Result<u32> {
+        current_version(&self.db).await
+    }
+
+    fn latest_schema_version(&self) -> u32 {
+        latest_version()
+    }
+
+    async fn malformed(&self) -> Result<Vec<(RecordId, String)>> {
+        get_malformed(&self.db).await
+    }
+
+    fn keeps_versions(&self) -> bool {
+        true
+    }
+
+    async fn delete(&self, ids: Vec<RecordId>) -> Result<u64> {
+        let count = ids.len() as u64;
+        self.db
+            .query("DELETE $ids")
+            .bind(("ids", ids))
+            .await?
+            .check()?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,692 @@
+//! What every backend must agree on, checked against the ones that run
+//! without a server.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use tempfile::TempDir;
+
+use crate::{
+    backup::{backup, restore},
+    config::RetentionConfig,
+    deleted::{deleted, deleted_files},
+    history::history,
+    models::{
+        event::EventType,
+        file::{File, FileResponse},
+        query::{FileQuery, SortField, SortOrder},
+        status::DaemonStatus,
+    },
+    retention::{prunable_paths_in, prune},
+    stats::{GroupBy, local_day},
+    store::{MemoryStore, SCAN_PAGE_SIZE, SqliteStore, Store, events_touching, scan},
+};
+
+struct Backends {
+    // keeps the SQLite file until the test is done
+    _dir: TempDir,
+    stores: Vec<(&'static str, Box<dyn Store>)>,
+}
+
+/// A fresh, empty store of each backend.
+async fn backends() -> Backends {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("kongg.sqlite");
+    let sqlite = SqliteStore::open(path.to_str().unwrap()).await.unwrap();
+    Backends {
+        _dir: dir,
+        stores: vec![
+            ("memory", Box::new(MemoryStore::default())),
+            ("sqlite", Box::new(sqlite)),
+        ],
+    }
+}
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+}
+
+/// An event at `path`, `minutes` after [`start`].
+fn event(event_type: EventType, path: &str, minutes: i64) -> File {
+    let mut file = File::missing(event_type, path.to_string()).with_root("/w".to_string());
+    file.timestamp = start() + Duration::minutes(minutes);
+    file
+}
+
+fn moved(event_type: EventType, from: &str, to: &str, minutes: i64) -> File {
+    event(event_type, to, minutes).add_previous_path(from.to_string())
+}
+
+/// A short life under `/w`: two files created, one renamed, one deleted.
+fn log() -> Vec<File> {
+    vec![
+        event(EventType::Created, "/w/docs/report.odt", 0),
+        event(EventType::Created, "/w/photos/cat.jpg", 10),
+        moved(
+            EventType::Renamed,
+            "/w/docs/report.odt",
+            "/w/docs/final.odt",
+            20,
+        ),
+        event(EventType::Deleted, "/w/photos/cat.jpg", 30),
+        event(EventType::Created, "/w/docs/notes.txt", 40),
+    ]
+}
+
+fn paths(files: &[FileResponse]) -> Vec<&str> {
+    files.iter().map(|f| f.path.as_str()).collect()
+}
+
+/// Every event matching `query`, following the cursor.
+async fn all_pages(store: &dyn Store, mut query: FileQuery) -> Vec<FileResponse> {
+    let mut events = Vec::new();
+    loop {
+        let page = store.query(&query).await.unwrap();
+        events.extend(page.items);
+        match page.next {
+            Some(cursor) => query = query.after(cursor),
+            None => return events,
+        }
+    }
+}
+
+#[tokio::test]
+async fn query_filters_and_sorts() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+
+        let newest_first = store.query(&FileQuery::new()).await.unwrap();
+        assert_eq!(
+            paths(&newest_first.items),
+            [
+                "/w/docs/notes.txt",
+                "/w/photos/cat.jpg",
+                "/w/docs/final.odt",
+                "/w/photos/cat.jpg",
+                "/w/docs/report.odt",
+            ],
+            "{}",
+            name
+        );
+
+        let docs = FileQuery::new()
+            .under("/w/docs")
+            .event_type(EventType::Created)
+            .sort(SortField::Timestamp, SortOrder::Asc);
+        let page = store.query(&docs).await.unwrap();
+        assert_eq!(
+            paths(&page.items),
+            ["/w/docs/report.odt", "/w/docs/notes.txt"],
+            "{}",
+            name
+        );
+
+        let window = FileQuery::new()
+            .since(start() + Duration::minutes(10))
+            .until(start() + Duration::minutes(30))
+            .extension("jpg");
+        let page = store.query(&window).await.unwrap();
+        assert_eq!(paths(&page.items), ["/w/photos/cat.jpg"], "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn cursor_pages_through_everything_once() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+
+        for (sort, order) in [
+            (SortField::Timestamp, SortOrder::Desc),
+            (SortField::Timestamp, SortOrder::Asc),
+            (SortField::Path, SortOrder::Asc),
+            (SortField::Name, SortOrder::Desc),
+        ] {
+            let unpaged = store
+                .query(&FileQuery::new().sort(sort, order).limit(100))
+                .await
+                .unwrap();
+            assert!(unpaged.next.is_none(), "{}", name);
+
+            let paged =
+                all_pages(store.as_ref(), FileQuery::new().sort(sort, order).limit(2)).await;
+            let ids =
+                |files: &[FileResponse]| files.iter().map(|f| f.id.clone()).collect::<Vec<_>>();
+            assert_eq!(
+                ids(&paged),
+                ids(&unpaged.items),
+                "{} {:?} {:?}",
+                name,
+                sort,
+                order
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn inventory_replays_the_log() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+
+        let now = store.inventory_at("/w", None).await.unwrap();
+        assert_eq!(
+            paths(&now),
+            ["/w/docs/final.odt", "/w/docs/notes.txt"],
+            "{}",
+            name
+        );
+
+        let before_rename = store
+            .inventory_at("/w", Some(start() + Duration::minutes(15)))
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&before_rename),
+            ["/w/docs/report.odt", "/w/photos/cat.jpg"],
+            "{}",
+            name
+        );
+
+        let photos = store
+            .inventory_at("/w/photos", Some(start() + Duration::minutes(15)))
+            .await
+            .unwrap();
+        assert_eq!(paths(&photos), ["/w/photos/cat.jpg"], "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn prune_keeps_what_still_exists() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+
+        let config = RetentionConfig {
+            max_age_days: [(EventType::Created, 1)].into_iter().collect(),
+            ..RetentionConfig::default()
+        };
+        let report = prune(store.as_ref(), &config, true).await.unwrap();
+        assert_eq!(report.scanned, 5, "{}", name);
+        // the creations of files renamed or deleted since; the newest
+        // creation is what `notes.txt` still is
+        assert_eq!(
+            paths(
+                &report
+                    .removed
+                    .iter()
+                    .map(|(f, _)| f.clone())
+                    .collect::<Vec<_>>()
+            ),
+            ["/w/docs/report.odt", "/w/photos/cat.jpg"],
+            "{}",
+            name
+        );
+        assert_eq!(
+            all_pages(store.as_ref(), FileQuery::new()).await.len(),
+            5,
+            "{}",
+            name
+        );
+
+        prune(store.as_ref(), &config, false).await.unwrap();
+        let left = all_pages(
+            store.as_ref(),
+            FileQuery::new().sort(SortField::Timestamp, SortOrder::Asc),
+        )
+        .await;
+        assert_eq!(
+            paths(&left),
+            [
+                "/w/docs/final.odt",
+                "/w/photos/cat.jpg",
+                "/w/docs/notes.txt"
+            ],
+            "{}",
+            name
+        );
+        assert_eq!(
+            paths(&store.inventory_at("/w", None).await.unwrap()),
+            ["/w/docs/final.odt", "/w/docs/notes.txt"],
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn under_is_an_exact_prefix() {
+    for (name, store) in backends().await.stores {
+        store
+            .insert(vec![
+                event(EventType::Created, "/w/docs/a.txt", 0),
+                event(EventType::Created, "/w/Docs/b.txt", 1),
+                event(EventType::Created, "/w/docs_x/c.txt", 2),
+                event(EventType::Created, "/w/docs%/d.txt", 3),
+                event(EventType::Created, "/w/docsier/e.txt", 4),
+                event(EventType::Created, "/w/d_cs/f.txt", 5),
+            ])
+            .await
+            .unwrap();
+
+        for (under, expected) in [
+            ("/w/docs", vec!["/w/docs/a.txt"]),
+            ("/w/docs/", vec!["/w/docs/a.txt"]),
+            ("/w/Docs", vec!["/w/Docs/b.txt"]),
+            ("/w/docs_x", vec!["/w/docs_x/c.txt"]),
+            ("/w/d_cs", vec!["/w/d_cs/f.txt"]),
+            ("/w/docs%", vec!["/w/docs%/d.txt"]),
+            ("/w/docs/a.txt", vec!["/w/docs/a.txt"]),
+        ] {
+            let query = FileQuery::new()
+                .under(under)
+                .sort(SortField::Path, SortOrder::Asc);
+            let page = store.query(&query).await.unwrap();
+            assert_eq!(paths(&page.items), expected, "{} under {}", name, under);
+
+            let inventory = store.inventory_at(under, None).await.unwrap();
+            assert_eq!(
+                paths(&inventory),
+                expected,
+                "{} inventory of {}",
+                name,
+                under
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn paths_match_either_side_of_a_rename() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+
+        let query = FileQuery::new()
+            .paths(["/w/docs/report.odt", "/w/photos/cat.jpg"])
+            .sort(SortField::Timestamp, SortOrder::Asc);
+        let page = store.query(&query).await.unwrap();
+        assert_eq!(
+            paths(&page.items),
+            [
+                "/w/docs/report.odt",
+                "/w/photos/cat.jpg",
+                "/w/docs/final.odt",
+                "/w/photos/cat.jpg"
+            ],
+            "{}",
+            name
+        );
+
+        // the rename leads from one name to the other and back
+        let touching = events_touching(store.as_ref(), ["/w/docs/final.odt".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&touching),
+            ["/w/docs/report.odt", "/w/docs/final.odt"],
+            "{}",
+            name
+        );
+        let entries = history(store.as_ref(), "/w/docs/report.odt").await.unwrap();
+        assert_eq!(entries.len(), 2, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn prunable_paths_agree_with_the_log() {
+    let mut events = log();
+    // three saves of the notes on one day, one the next
+    for minutes in [50, 60, 24 * 60] {
+        events.push(event(EventType::Created, "/w/docs/notes.txt", minutes));
+    }
+    events.push(event(EventType::Created, "/v/lone.txt", 0).with_root("/v".to_string()));
+
+    let now = start() + Duration::days(3);
+    let configs = [
+        RetentionConfig {
+            max_age_days: [(EventType::Deleted, 1)].into_iter().collect(),
+            ..RetentionConfig::default()
+        },
+        RetentionConfig {
+            collapse_daily: vec![EventType::Created],
+            ..RetentionConfig::default()
+        },
+        RetentionConfig {
+            max_rows_per_root: Some(2),
+            ..RetentionConfig::default()
+        },
+    ];
+    let expected: [&[&str]; 3] = [
+        &["/w/photos/cat.jpg"],
+        &["/w/docs/notes.txt"],
+        &[
+            "/w/docs/report.odt",
+            "/w/docs/final.odt",
+            "/w/photos/cat.jpg",
+            "/w/docs/notes.txt",
+        ],
+    ];
+
+    for (name, store) in backends().await.stores {
+        store.insert(events.clone()).await.unwrap();
+        let all = scan(store.as_ref(), &FileQuery::new()).await.unwrap();
+
+        for (config, expected) in configs.iter().zip(expected) {
+            let expected: HashSet<String> = expected.iter().map(|p| p.to_string()).collect();
+            let found = store.prunable_paths(config, now).await.unwrap();
+            assert_eq!(found, expected, "{} {:?}", name, config);
+            assert_eq!(
+                prunable_paths_in(&all, config, now),
+                expected,
+                "{} {:?}",
+                name,
+                config
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn collapse_keeps_the_last_save_of_each_day() {
+    for (name, store) in backends().await.stores {
+        let day = 24 * 60;
+        store
+            .insert(vec![
+                event(EventType::Created, "/w/draft.md", 0),
+                event(EventType::Created, "/w/draft.md", 5),
+                event(EventType::Created, "/w/draft.md", 9),
+                event(EventType::Created, "/w/other.md", 7),
+                event(EventType::Created, "/w/draft.md", day),
+                event(EventType::Created, "/w/draft.md", day + 3),
+                event(EventType::Deleted, "/w/other.md", day + 4),
+            ])
+            .await
+            .unwrap();
+
+        let config = RetentionConfig {
+            collapse_daily: vec![EventType::Created],
+            ..RetentionConfig::default()
+        };
+        prune(store.as_ref(), &config, false).await.unwrap();
+        let left = all_pages(
+            store.as_ref(),
+            FileQuery::new().sort(SortField::Timestamp, SortOrder::Asc),
+        )
+        .await;
+        let minutes: Vec<(&str, i64)> = left
+            .iter()
+            .map(|f| (f.path.as_str(), (f.timestamp - start()).num_minutes()))
+            .collect();
+        assert_eq!(
+            minutes,
+            [
+                ("/w/other.md", 7),
+                ("/w/draft.md", 9),
+                ("/w/draft.md", day + 3),
+                ("/w/other.md", day + 4),
+            ],
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn collapse_counts_local_days() {
+    for (name, store) in backends().await.stores {
+        let minutes = [0, 11 * 60 + 40, 12 * 60 + 10];
+        let events = minutes.map(|minutes| event(EventType::Created, "/w/late.txt", minutes));
+        // the last save of each local day
+        let expected: Vec<i64> = minutes
+            .iter()
+            .zip(&events)
+            .filter(|(_, e)| {
+                !events.iter().any(|later| {
+                    later.timestamp > e.timestamp
+                        && local_day(&later.timestamp) == local_day(&e.timestamp)
+                })
+            })
+            .map(|(minutes, _)| *minutes)
+            .collect();
+        store.insert(events.to_vec()).await.unwrap();
+
+        let config = RetentionConfig {
+            collapse_daily: vec![EventType::Created],
+            ..RetentionConfig::default()
+        };
+        let prunable = store.prunable_paths(&config, start()).await.unwrap();
+        assert_eq!(prunable.is_empty(), expected.len() == 3, "{}", name);
+        prune(store.as_ref(), &config, false).await.unwrap();
+        let left: Vec<i64> = scan(store.as_ref(), &FileQuery::new())
+            .await
+            .unwrap()
+            .iter()
+            .map(|f| (f.timestamp - start()).num_minutes())
+            .collect();
+        assert_eq!(left, expected, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn days_are_local_days() {
+    for (name, store) in backends().await.stores {
+        // late evening in UTC is already the next day east of it
+        let events = [0, 11 * 60 + 40, 12 * 60 + 10]
+            .map(|minutes| event(EventType::Created, "/w/late.txt", minutes));
+        let mut expected: Vec<(String, u64)> = Vec::new();
+        for event in &events {
+            let day = local_day(&event.timestamp);
+            match expected.iter_mut().find(|(d, _)| *d == day) {
+                Some((_, count)) => *count += 1,
+                None => expected.push((day, 1)),
+            }
+        }
+        store.insert(events.to_vec()).await.unwrap();
+
+        let days: Vec<(String, u64)> = store
+            .aggregate(&FileQuery::new(), GroupBy::Day)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.key, b.count))
+            .collect();
+        assert_eq!(days, expected, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn snapshot_reads_up_to_its_instant_oldest_first() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+        let snapshot = store
+            .snapshot(start() + Duration::minutes(30))
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&snapshot),
+            [
+                "/w/docs/report.odt",
+                "/w/photos/cat.jpg",
+                "/w/docs/final.odt"
+            ],
+            "{}",
+            name
+        );
+
+        // more than a page, with ties in time kept in insertion order
+        let many: Vec<File> = (0..SCAN_PAGE_SIZE + 200)
+            .map(|i| event(EventType::Created, &format!("/w/many/{}", i), 45))
+            .collect();
+        store.insert(many).await.unwrap();
+        let snapshot = store.snapshot(start() + Duration::hours(1)).await.unwrap();
+        assert_eq!(snapshot.len(), 5 + SCAN_PAGE_SIZE + 200, "{}", name);
+        assert_eq!(snapshot[5].path, "/w/many/0", "{}", name);
+        assert_eq!(
+            snapshot.last().unwrap().path,
+            format!("/w/many/{}", SCAN_PAGE_SIZE + 199),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn deleted_reads_the_same_as_the_whole_log() {
+    for (name, store) in backends().await.stores {
+        let mut sized = event(EventType::Created, "/w/big.iso", 0);
+        sized.file_size = Some(700);
+        let mut elsewhere = event(EventType::Deleted, "/x/gone.txt", 45);
+        elsewhere.root = Some("/x".to_string());
+        let mut events = log();
+        events.extend([
+            sized,
+            moved(EventType::Renamed, "/w/big.iso", "/w/old.iso", 5),
+            event(EventType::Deleted, "/w/old.iso", 50),
+            event(EventType::Deleted, "/w/docs/notes.txt", 55),
+            event(EventType::Created, "/w/docs/notes.txt", 60),
+            elsewhere,
+        ]);
+        store.insert(events).await.unwrap();
+
+        let everything = scan(store.as_ref(), &FileQuery::new()).await.unwrap();
+        for filter in [
+            FileQuery::new(),
+            FileQuery::new().under("/w"),
+            FileQuery::new().min_size(100),
+        ] {
+            let found: Vec<(String, Option<u64>)> = deleted(store.as_ref(), &filter)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|d| (d.file.path, d.size))
+                .collect();
+            let expected: Vec<(String, Option<u64>)> = deleted_files(everything.clone(), &filter)
+                .into_iter()
+                .map(|d| (d.file.path, d.size))
+                .collect();
+            assert_eq!(found, expected, "{}", name);
+        }
+        let all = deleted(store.as_ref(), &FileQuery::new()).await.unwrap();
+        let paths: Vec<&str> = all.iter().map(|d| d.file.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["/w/old.iso", "/x/gone.txt", "/w/photos/cat.jpg"],
+            "{}",
+            name
+        );
+        assert_eq!(all[0].size, Some(700), "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn deletions_weigh_their_size_before_under_any_filter() {
+    for (name, store) in backends().await.stores {
+        let mut created = event(EventType::Created, "/w/big.iso", 0);
+        created.file_size = Some(700);
+        let mut other = event(EventType::Created, "/x/small.iso", 10);
+        other.file_size = Some(5);
+        store
+            .insert(vec![
+                created,
+                other,
+                event(EventType::Deleted, "/w/big.iso", 30),
+                event(EventType::Deleted, "/x/small.iso", 40),
+            ])
+            .await
+            .unwrap();
+
+        let filter = FileQuery::new()
+            .under("/w")
+            .event_type(EventType::Deleted)
+            .since(start() + Duration::minutes(20));
+        let buckets: Vec<(String, u64, u64)> = store
+            .aggregate(&filter, GroupBy::Directory)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.key, b.count, b.bytes))
+            .collect();
+        assert_eq!(buckets, [("/w".to_string(), 1, 700)], "{}", name);
+
+        let largest: Vec<(String, u64)> = store
+            .largest_deleted(&filter, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(f, size)| (f.path, size))
+            .collect();
+        assert_eq!(largest, [("/w/big.iso".to_string(), 700)], "{}", name);
+    }
+}
+
+/// A backup cut short before its trailer.
+fn without_last_line(snapshot: &[u8]) -> Vec<u8> {
+    let mut text = String::new();
+    GzDecoder::new(snapshot).read_to_string(&mut text).unwrap();
+    let end = text.trim_end().rfind('\n').unwrap();
+
+    let mut out = GzEncoder::new(Vec::new(), Compression::default());
+    out.write_all(&text.as_bytes()[..=end]).unwrap();
+    out.finish().unwrap()
+}
+
+#[tokio::test]
+async fn restore_replaces_all_or_nothing() {
+    for (name, store) in backends().await.stores {
+        store.insert(log()).await.unwrap();
+        let mut snapshot = Vec::new();
+        backup(store.as_ref(), &mut snapshot).await.unwrap();
+
+        store
+            .insert(vec![event(EventType::Created, "/w/later.txt", 50)])
+            .await
+            .unwrap();
+        let before = paths(&all_pages(store.as_ref(), FileQuery::new()).await)
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        // without its trailer the snapshot is refused and nothing changes
+        let truncated = without_last_line(&snapshot);
+        assert!(restore(store.as_ref(), &truncated[..], true).await.is_err());
+        assert_eq!(
+            paths(&all_pages(store.as_ref(), FileQuery::new()).await),
+            before,
+            "{}",
+            name
+        );
+
+        assert!(restore(store.as_ref(), &snapshot[..], false).await.is_err());
+        let summary = restore(store.as_ref(), &snapshot[..], true).await.unwrap();
+        assert_eq!(summary.records, 5, "{}", name);
+        assert_eq!(
+            paths(&all_pages(store.as_ref(), FileQuery::new()).await),
+            [
+                "/w/docs/notes.txt",
+                "/w/photos/cat.jpg",
+                "/w/docs/final.odt",
+                "/w/photos/cat.jpg",
+                "/w/docs/report.odt",
+            ],
+            "{}",
+            name
+        );
+
+        // a backup can be taken under the daemon, a restore can't
+        store
+            .save_status(DaemonStatus {
+                updated_at: Some(Utc::now()),
+                ..DaemonStatus::default()
+            })
+            .await
+            .unwrap();
+        assert!(backup(store.as_ref(), Vec::new()).await.is_ok(), "{}", name);
+        assert!(
+            restore(store.as_ref(), &snapshot[..], true).await.is_err(),
+            "{}",
+            name
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! The daemon's live feed of the events it writes, so `kongg_cli tail` can
+//! follow them without polling the store. Events go over a Unix socket only
+//! the user can open, one JSON object per line, before any encryption at
+//! rest is applied.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use dirs::{config_dir, runtime_dir};
+use surrealdb::RecordId;
+
+use crate::{
+    Error, Result,
+    constants::FILE_TABLE_NAME,
+    models::{
+        event::EventType,
+        file::{File, FileResponse},
+    },
+};
+
+const SOCKET_NAME: &str = "events.sock";
+
+/// In the runtime directory when there is one (`$XDG_RUNTIME_DIR/kongg`),
+/// next to the config file otherwise.
+pub fn socket_path() -> Result<PathBuf> {
+    runtime_dir()
+        .or_else(config_dir)
+        .map(|dir| dir.join("kongg").join(SOCKET_NAME))
+        .ok_or_else(|| Error::Config("could not determine the runtime directory".to_string()))
+}
+
+/// One line of the stream, newline included.
+pub fn encode(file: &File) -> Result<String> {
+    let mut line = serde_json::to_string(file).map_err(|e| Error::Parse(e.to_string()))?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Streamed events weren't read back from the store, so they all carry the
+/// placeholder id `file:0`.
+pub fn decode(line: &str) -> Result<FileResponse> {
+    let file: File = serde_json::from_str(line).map_err(|e| Error::Parse(e.to_string()))?;
+    Ok(file.into_response(placeholder_id()))
+}
+
+/// Whether `file` came from the stream rather than from the store, so its
+/// id isn't a real one.
+pub fn is_streamed(file: &FileResponse) -> bool {
+    file.id == placeholder_id()
+}
+
+fn placeholder_id() -> RecordId {
+    RecordId::from_table_key(FILE_TABLE_NAME, 0)
+}
+
+/// Time, path and type of an event.
+pub type EventKey = (DateTime<Utc>, String, EventType);
+
+/// Recognises an event read from the store when the stream repeats it.
+/// Streamed events carry no id and the writer's batches aren't ordered by
+/// time, so neither can tell them apart.
+pub fn event_key(file: &FileResponse) -> EventKey {
+    (file.timestamp, file.path.clone(), file.event_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn a_streamed_event_has_the_key_it_was_stored_with() {
+        let file = File::missing(EventType::Created, "/w/report.odt".to_string());
+        let stored = file
+            .clone()
+            .into_response(RecordId::from_table_key(FILE_TABLE_NAME, 42));
+        let streamed = decode(&encode(&file).unwrap()).unwrap();
+        assert!(is_streamed(&streamed));
+        assert_eq!(event_key(&streamed), event_key(&stored));
+
+        let mut deleted = file.clone();
+        deleted.event_type = EventType::Deleted;
+        let mut later = file.clone();
+        later.timestamp += Duration::microseconds(1);
+        for other in [deleted, later] {
+            let other = decode(&encode(&other).unwrap()).unwrap();
+            assert_ne!(event_key(&other), event_key(&stored));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! What every binary offers besides its own job: shell completions and man
+//! pages, both generated from its clap definition.
+//!
+//! Completions are dynamic: the shell script calls the binary back with
+//! `COMPLETE=<shell>` set, so values only the database knows can be offered.
+
+use std::{env, fs, io, path::PathBuf};
+
+use clap::{Command, Subcommand, ValueHint};
+use clap_complete::{CompleteEnv, Shell, env::Shells};
+
+use crate::{Error, Result};
+
+/// Set by the completion scripts when they call the binary back.
+const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum DocCommand {
+    /// Print the script that sets up completions, e.g.
+    /// `source <(kongg_cli completions bash)` in ~/.bashrc.
+    Completions { shell: Shell },
+    /// Write man pages for the program and each of its commands.
+    Man {
+        #[arg(value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+}
+
+impl DocCommand {
+    /// `command` is the definition of the binary this runs in.
+    pub fn run(&self, command: Command) -> Result<()> {
+        match self {
+            DocCommand::Completions { shell } => {
+                let shells = Shells::builtins();
+                let completer = shells
+                    .completer(&shell.to_string())
+                    .ok_or_else(|| Error::Config(format!("no completions for {}", shell)))?;
+                let name = command.get_name().to_string();
+                // the full path, so completions work wherever PATH points
+                let bin = env::current_exe()?;
+                completer.write_registration(
+                    COMPLETE_VAR,
+                    &name,
+                    &name,
+                    &bin.to_string_lossy(),
+                    &mut io::stdout(),
+                )?;
+            }
+            DocCommand::Man { dir } => {
+                fs::create_dir_all(dir)?;
+                clap_mangen::generate_to(command, dir)?;
+                println!("man pages written to {}", dir.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Answers the completion script and exits when it is the caller. Must run
+/// before anything is printed, and before an async runtime starts so that
+/// completers can start their own.
+pub fn complete(command: fn() -> Command) {
+    CompleteEnv::with_factory(command)
+        .var(COMPLETE_VAR)
+        .complete();
+}
@@ -1,14 +1,15 @@
 use dirs::home_dir;
 
-pub fn get_home() -> Option<String> {
+use crate::{Error, Result};
+
+pub fn get_home() -> Result<String> {
     // let home = env::var("Home").or_else(|_| env::var("USERPROFILE"));
     // if let Ok(res) = home {
     //     return Some(res);
     // };
     // None
 
-    if let Some(home_dir) = home_dir() {
-        return Some(home_dir.display().to_string());
-    }
-    None
+    home_dir()
+        .map(|home_dir| home_dir.display().to_string())
+        .ok_or_else(|| Error::Config("could not determine the home directory".to_string()))
 }
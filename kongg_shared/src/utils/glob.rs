@@ -0,0 +1,297 @@
+//! Minimal shell-style globs (`*`, `?`, `[abc]`, `[a-z]`, `[!abc]`) shared
+//! by every backend so a pattern means the same thing whichever store
+//! answers it. `*` also matches `/`, like the daemon's ignore list. A `]`
+//! right after the opening `[` (or `[!`) is part of the class, and a `[`
+//! that is never closed is an ordinary character.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// `*`, any run of characters.
+    Any,
+    /// `?`, one character.
+    One,
+    Class {
+        negate: bool,
+        /// Single characters are ranges from themselves to themselves.
+        ranges: Vec<(char, char)>,
+    },
+    Literal(char),
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Any | Token::One => true,
+            Token::Class { negate, ranges } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negate
+            }
+            Token::Literal(l) => *l == c,
+        }
+    }
+}
+
+fn parse(glob: &str) -> Vec<Token> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                if tokens.last() != Some(&Token::Any) {
+                    tokens.push(Token::Any);
+                }
+                i += 1;
+            }
+            '?' => {
+                tokens.push(Token::One);
+                i += 1;
+            }
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    i += 1 + len;
+                }
+                None => {
+                    tokens.push(Token::Literal('['));
+                    i += 1;
+                }
+            },
+            c => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// The class after a `[` and how many characters it took, closing `]`
+/// included, or `None` when it is never closed.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let negate = chars.first() == Some(&'!');
+    let mut i = usize::from(negate);
+    let mut ranges = Vec::new();
+
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !ranges.is_empty() {
+            return Some((Token::Class { negate, ranges }, i + 1));
+        }
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&hi)) if hi != ']' => {
+                ranges.push((c, hi));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Patterns without a separator are matched against the file name only, so
+/// `*.mkv` does what people expect.
+pub fn matches_name_only(glob: &str) -> bool {
+    !glob.contains(['/', '\\'])
+}
+
+/// The pattern as an anchored regular expression, for SurrealDB.
+pub fn glob_to_regex(glob: &str) -> String {
+    fn push_escaped(regex: &mut String, c: char, special: &str) {
+        if special.contains(c) {
+            regex.push('\\');
+        }
+        regex.push(c);
+    }
+
+    let mut regex = String::from("^");
+    for token in parse(glob) {
+        match token {
+            Token::Any => regex.push_str(".*"),
+            Token::One => regex.push('.'),
+            Token::Class { negate, ranges } => {
+                regex.push('[');
+                if negate {
+                    regex.push('^');
+                }
+                for (lo, hi) in ranges {
+                    push_escaped(&mut regex, lo, "\\[]^-&~");
+                    if lo != hi {
+                        regex.push('-');
+                        push_escaped(&mut regex, hi, "\\[]^-&~");
+                    }
+                }
+                regex.push(']');
+            }
+            Token::Literal(c) => push_escaped(&mut regex, c, "\\.+*?()|[]{}^$"),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The pattern in SQLite's `GLOB` syntax, which negates with `^` and has
+/// no escape character, so special characters go in classes of their own.
+pub fn glob_to_sqlite(glob: &str) -> String {
+    let mut pattern = String::new();
+    for token in parse(glob) {
+        match token {
+            Token::Any => pattern.push('*'),
+            Token::One => pattern.push('?'),
+            Token::Class { negate, ranges } => {
+                // `]` only counts as a member first, `^` anywhere but first
+                // and `-` last
+                let (mut first, mut rest, mut last) = (String::new(), String::new(), String::new());
+                for (lo, hi) in ranges {
+                    match (lo, lo == hi) {
+                        (']', true) => first.push(']'),
+                        ('-', true) => last.push('-'),
+                        ('^', true) => last.insert(0, '^'),
+                        _ if lo == hi => rest.push(lo),
+                        _ => {
+                            rest.push(lo);
+                            rest.push('-');
+                            rest.push(hi);
+                        }
+                    }
+                }
+                if !negate && first.is_empty() && rest.is_empty() && last.starts_with('^') {
+                    // a class of `^` alone, which would read as a negation
+                    last.remove(0);
+                    pattern.push('^');
+                    if last.is_empty() {
+                        continue;
+                    }
+                }
+                pattern.push('[');
+                if negate {
+                    pattern.push('^');
+                }
+                pattern.push_str(&first);
+                pattern.push_str(&rest);
+                pattern.push_str(&last);
+                pattern.push(']');
+            }
+            Token::Literal(c @ ('*' | '?' | '[')) => {
+                pattern.push('[');
+                pattern.push(c);
+                pattern.push(']');
+            }
+            Token::Literal(c) => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// Whether the whole of `text` matches `glob`. Stars are retried from the
+/// last one only, so the time stays proportional to the pattern times the
+/// text however many stars there are.
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let tokens = parse(glob);
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // the last star seen and where in the text it currently ends
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Any) => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(token) if token.matches(text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the last star swallow one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|token| *token == Token::Any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(glob_match("*.mkv", "movie.mkv"));
+        assert!(glob_match("*.mkv", ".mkv"));
+        assert!(!glob_match("*.mkv", "movie.mkv.part"));
+        assert!(glob_match("*/docs/*", "/home/ana/docs/a.txt"));
+        assert!(glob_match("IMG_????.jpg", "IMG_0042.jpg"));
+        assert!(!glob_match("IMG_????.jpg", "IMG_042.jpg"));
+        assert!(glob_match("**a*b", "xxaxxb"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+        assert!(glob_match("*", ""));
+        // case matters, as in every backend
+        assert!(!glob_match("*.MKV", "movie.mkv"));
+    }
+
+    #[test]
+    fn matches_classes() {
+        assert!(glob_match("report[0-9].pdf", "report7.pdf"));
+        assert!(!glob_match("report[0-9].pdf", "reportx.pdf"));
+        assert!(glob_match("[!.]*", "visible"));
+        assert!(!glob_match("[!.]*", ".hidden"));
+        assert!(glob_match("[abc]", "b"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[!]]", "a"));
+        assert!(!glob_match("[!]]", "]"));
+        assert!(glob_match("[a-]", "-"));
+        assert!(glob_match("é[à-ü]", "éé"));
+    }
+
+    #[test]
+    fn unterminated_classes_are_literal() {
+        assert!(glob_match("[abc", "[abc"));
+        assert!(!glob_match("[abc", "a"));
+        assert!(glob_match("a[*", "a[xyz"));
+        assert!(glob_match("[!", "[!"));
+        assert!(glob_match("[]", "[]"));
+        assert_eq!(glob_to_regex("[abc"), "^\\[abc$");
+        assert_eq!(glob_to_sqlite("[abc"), "[[]abc");
+        assert_eq!(glob_to_sqlite("a[*"), "a[[]*");
+    }
+
+    #[test]
+    fn many_stars_stay_fast() {
+        let glob = "*a".repeat(30) + "b";
+        let text = "a".repeat(200);
+        assert!(!glob_match(&glob, &text));
+    }
+
+    #[test]
+    fn translates_for_the_backends() {
+        assert_eq!(glob_to_regex("*.tar.gz"), "^.*\\.tar\\.gz$");
+        assert_eq!(glob_to_regex("a?[!x-z]"), "^a.[^x-z]$");
+        assert_eq!(glob_to_regex("[]^]"), "^[\\]\\^]$");
+        assert_eq!(glob_to_regex("(1)+$"), "^\\(1\\)\\+\\$$");
+
+        assert_eq!(glob_to_sqlite("*.tar.gz"), "*.tar.gz");
+        assert_eq!(glob_to_sqlite("a?[!x-z]"), "a?[^x-z]");
+        assert_eq!(glob_to_sqlite("[^]"), "^");
+        assert_eq!(glob_to_sqlite("[-^a]]"), "[a^-]]");
+        assert_eq!(glob_to_sqlite("[!]a]"), "[^]a]");
+        assert_eq!(glob_to_sqlite("what?[?]"), "what?[?]");
+        assert_eq!(glob_to_sqlite("**"), "*");
+    }
+
+    #[test]
+    fn name_only_patterns() {
+        assert!(matches_name_only("*.mkv"));
+        assert!(!matches_name_only("*/tmp/*"));
+        assert!(!matches_name_only("C:\\*"));
+    }
+}
@@ -0,0 +1,48 @@
+//! Who and where an event was captured. Host and machine id don't change
+//! while the daemon runs, so they are looked up once.
+
+use std::{fs, fs::Metadata, sync::OnceLock};
+
+/// Files holding the machine id, in the order systemd and D-Bus use them.
+const MACHINE_ID_FILES: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+pub fn host_name() -> Option<String> {
+    static HOST: OnceLock<Option<String>> = OnceLock::new();
+    HOST.get_or_init(|| whoami::fallible::hostname().ok())
+        .clone()
+}
+
+/// Stable id of this installation, `None` where the OS doesn't provide one.
+pub fn machine_id() -> Option<String> {
+    static MACHINE_ID: OnceLock<Option<String>> = OnceLock::new();
+    MACHINE_ID
+        .get_or_init(|| {
+            MACHINE_ID_FILES
+                .iter()
+                .filter_map(|path| fs::read_to_string(path).ok())
+                .map(|id| id.trim().to_string())
+                .find(|id| !id.is_empty())
+        })
+        .clone()
+}
+
+/// User the current process runs as.
+pub fn process_user() -> String {
+    whoami::username()
+}
+
+/// Owner of a file, falling back to the process user where ownership
+/// can't be resolved to a name.
+#[cfg(unix)]
+pub fn owner(metadata: &Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    uzers::get_user_by_uid(metadata.uid())
+        .map(|user| user.name().to_string_lossy().to_string())
+        .unwrap_or_else(process_user)
+}
+
+#[cfg(not(unix))]
+pub fn owner(_metadata: &Metadata) -> String {
+    process_user()
+}
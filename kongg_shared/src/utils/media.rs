@@ -0,0 +1,93 @@
+//! Rough kinds of files, told apart by extension, so filters can say
+//! `video` instead of listing every container format.
+
+use std::{fmt, str::FromStr};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaCategory {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Code,
+}
+
+impl MediaCategory {
+    pub const ALL: [MediaCategory; 6] = [
+        MediaCategory::Image,
+        MediaCategory::Video,
+        MediaCategory::Audio,
+        MediaCategory::Document,
+        MediaCategory::Archive,
+        MediaCategory::Code,
+    ];
+
+    /// Lowercase, without the dot.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            MediaCategory::Image => &[
+                "jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff", "svg", "heic", "heif",
+                "avif", "raw", "cr2", "nef", "arw", "dng", "ico", "psd",
+            ],
+            MediaCategory::Video => &[
+                "mp4", "mkv", "mov", "avi", "webm", "wmv", "flv", "m4v", "mpg", "mpeg", "3gp",
+            ],
+            MediaCategory::Audio => &[
+                "mp3", "flac", "wav", "ogg", "opus", "m4a", "aac", "wma", "aiff", "alac", "mid",
+            ],
+            MediaCategory::Document => &[
+                "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "xls", "xlsx", "ods", "csv",
+                "ppt", "pptx", "odp", "epub", "pages", "numbers", "key",
+            ],
+            MediaCategory::Archive => &[
+                "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "iso", "dmg",
+            ],
+            MediaCategory::Code => &[
+                "rs", "py", "js", "ts", "tsx", "jsx", "go", "c", "h", "cpp", "hpp", "java", "kt",
+                "swift", "rb", "php", "sh", "html", "css", "json", "toml", "yaml", "yml", "sql",
+            ],
+        }
+    }
+
+    /// The first category listing `extension`.
+    pub fn of(extension: &str) -> Option<Self> {
+        let extension = extension.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|c| c.extensions().contains(&extension.as_str()))
+    }
+}
+
+impl fmt::Display for MediaCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MediaCategory::Image => "image",
+            MediaCategory::Video => "video",
+            MediaCategory::Audio => "audio",
+            MediaCategory::Document => "document",
+            MediaCategory::Archive => "archive",
+            MediaCategory::Code => "code",
+        })
+    }
+}
+
+impl FromStr for MediaCategory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_lowercase();
+        let name = name.strip_suffix('s').unwrap_or(&name);
+        Self::ALL
+            .into_iter()
+            .find(|c| c.to_string() == name)
+            .ok_or_else(|| {
+                Error::Parse(format!(
+                    "unknown category `{}`, expected one of image, video, audio, document, archive, code",
+                    s
+                ))
+            })
+    }
+}
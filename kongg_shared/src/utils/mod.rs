@@ -1 +1,7 @@
+pub mod cli;
 pub mod get_os_home;
+pub mod glob;
+pub mod identity;
+pub mod media;
+pub mod size;
+pub mod time;
@@ -0,0 +1,235 @@
+//! File sizes as people read and type them. Sizes print in SI (`4.7 GB`,
+//! powers of 1000) or IEC (`4.4 GiB`, powers of 1024) units, and filters
+//! take either: `700MB`, `>700MB`, `<=1.5g`, `1GiB..4GiB`, `..100k`.
+//! Single letters (`k`, `m`, `g`, ...) are SI units.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+const SI: &[&str] = &["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+const IEC: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    /// kB, MB, GB, powers of 1000.
+    #[default]
+    Si,
+    /// KiB, MiB, GiB, powers of 1024.
+    Iec,
+}
+
+impl SizeUnits {
+    fn base(self) -> f64 {
+        match self {
+            SizeUnits::Si => 1000.0,
+            SizeUnits::Iec => 1024.0,
+        }
+    }
+
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            SizeUnits::Si => SI,
+            SizeUnits::Iec => IEC,
+        }
+    }
+}
+
+/// `512 B`, `4.7 GB`; one decimal once there is a unit.
+pub fn format_size(bytes: u64, units: SizeUnits) -> String {
+    let names = units.names();
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= units.base() && unit < names.len() - 1 {
+        value /= units.base();
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, names[unit])
+    }
+}
+
+/// Bytes in a size like `700MB`, `1.5 GiB`, `100k` or `4096`.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let invalid = || Error::Parse(format!("`{}` is not a size", value));
+
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+
+    let unit = unit.trim().to_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let (prefix, units) = match unit.strip_suffix('i') {
+        Some(prefix) => (prefix, SizeUnits::Iec),
+        None => (unit, SizeUnits::Si),
+    };
+    let power = match prefix {
+        "" if units == SizeUnits::Si => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        "e" => 6,
+        _ => return Err(invalid()),
+    };
+
+    let bytes = number * units.base().powi(power);
+    if bytes > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Bounds of a size filter, both inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeRange {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+/// `>700MB`, `>=700MB`, `<1G`, `<=1G`, `1GiB..4GiB`, `1G..`, `..1G`, or a
+/// single size for at least that many bytes; sizes are rounded for display,
+/// so nobody means exactly `700MB`.
+pub fn parse_size_range(value: &str) -> Result<SizeRange> {
+    let value = value.trim();
+    let bound = |s: &str| -> Result<Option<u64>> {
+        match s.trim() {
+            "" => Ok(None),
+            s => parse_size(s).map(Some),
+        }
+    };
+
+    let range = if let Some((min, max)) = value.split_once("..") {
+        SizeRange {
+            min: bound(min)?,
+            max: bound(max)?,
+        }
+    } else if let Some(size) = value.strip_prefix(">=") {
+        SizeRange {
+            min: Some(parse_size(size)?),
+            max: None,
+        }
+    } else if let Some(size) = value.strip_prefix('>') {
+        SizeRange {
+            min: Some(parse_size(size)?.saturating_add(1)),
+            max: None,
+        }
+    } else if let Some(size) = value.strip_prefix("<=") {
+        SizeRange {
+            min: None,
+            max: Some(parse_size(size)?),
+        }
+    } else if let Some(size) = value.strip_prefix('<') {
+        let size = parse_size(size)?;
+        let max = size
+            .checked_sub(1)
+            .ok_or_else(|| Error::Parse(format!("nothing is smaller than `{}`", value)))?;
+        SizeRange {
+            min: None,
+            max: Some(max),
+        }
+    } else {
+        SizeRange {
+            min: Some(parse_size(value)?),
+            max: None,
+        }
+    };
+
+    if range.min.zip(range.max).is_some_and(|(min, max)| min > max) {
+        return Err(Error::Parse(format!("`{}` ends before it starts", value)));
+    }
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: Option<u64>, max: Option<u64>) -> SizeRange {
+        SizeRange { min, max }
+    }
+
+    #[test]
+    fn parses_unit_suffixes() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("4096B").unwrap(), 4096);
+        assert_eq!(parse_size("100k").unwrap(), 100_000);
+        assert_eq!(parse_size("100kB").unwrap(), 100_000);
+        assert_eq!(parse_size("100KiB").unwrap(), 102_400);
+        assert_eq!(parse_size("700MB").unwrap(), 700_000_000);
+        assert_eq!(parse_size(" 1.5 GiB ").unwrap(), 1_610_612_736);
+        assert_eq!(parse_size("1.5g").unwrap(), 1_500_000_000);
+        assert_eq!(parse_size("2T").unwrap(), 2_000_000_000_000);
+        assert_eq!(parse_size("1EiB").unwrap(), 1 << 60);
+    }
+
+    #[test]
+    fn refuses_what_is_not_a_size() {
+        for value in [
+            "",
+            "MB",
+            "abc",
+            "1.2.3",
+            "-5",
+            "10 apples",
+            "1i",
+            "1e3",
+            "99EB",
+        ] {
+            assert!(parse_size(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parses_bounds_and_ranges() {
+        assert_eq!(
+            parse_size_range(">700MB").unwrap(),
+            range(Some(700_000_001), None)
+        );
+        assert_eq!(
+            parse_size_range(">=700MB").unwrap(),
+            range(Some(700_000_000), None)
+        );
+        assert_eq!(parse_size_range("<1k").unwrap(), range(None, Some(999)));
+        assert_eq!(parse_size_range("<=1k").unwrap(), range(None, Some(1000)));
+        assert_eq!(
+            parse_size_range("1GiB..4GiB").unwrap(),
+            range(Some(1 << 30), Some(4 << 30))
+        );
+        assert_eq!(parse_size_range("1k..").unwrap(), range(Some(1000), None));
+        assert_eq!(parse_size_range("..1k").unwrap(), range(None, Some(1000)));
+        assert_eq!(parse_size_range("..").unwrap(), range(None, None));
+    }
+
+    #[test]
+    fn a_bare_size_is_a_minimum() {
+        assert_eq!(
+            parse_size_range("700MB").unwrap(),
+            range(Some(700_000_000), None)
+        );
+    }
+
+    #[test]
+    fn refuses_bad_ranges() {
+        for value in ["", ">", "<0", "4G..1G", "1k..x", ">=-1", "<<1k"] {
+            assert!(parse_size_range(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn formats_in_either_unit() {
+        assert_eq!(format_size(0, SizeUnits::Si), "0 B");
+        assert_eq!(format_size(999, SizeUnits::Si), "999 B");
+        assert_eq!(format_size(1000, SizeUnits::Si), "1.0 kB");
+        assert_eq!(format_size(4_700_000_000, SizeUnits::Si), "4.7 GB");
+        assert_eq!(format_size(1024, SizeUnits::Iec), "1.0 KiB");
+        assert_eq!(format_size(u64::MAX, SizeUnits::Iec), "16.0 EiB");
+    }
+}
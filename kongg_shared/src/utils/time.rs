@@ -0,0 +1,553 @@
+//! Parsing of the times users type on the command line and in the TUI.
+//! Every expression stands for a span of time in the local time zone:
+//!
+//! - instants: `now`, RFC 3339, `2025-03-14 18:00`, `3d`, `2 weeks ago`,
+//!   `an hour ago`
+//! - calendar periods: `2025`, `2025-03`, `2025-03-14`, `today`,
+//!   `yesterday`, `monday`, `march`, `march 2025`, `this week`,
+//!   `last month`
+//! - rolling periods: `past 3 days`, `last 2 weeks`
+//! - ranges: `2025-03-01..2025-03-14`, `2025-03..` (up to now)
+//! - `around <expression>`, which widens it by a quarter of its length, or
+//!   of its distance from now for an instant
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Utc, Weekday,
+};
+
+use crate::{Error, Result};
+
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"];
+
+const MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const NUMBERS: &[&str] = &[
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven",
+    "twelve",
+];
+
+/// The least `around` widens an instant by.
+const MIN_SLACK_MINUTES: i64 = 60;
+
+/// A span of time, `start` inclusive and `end` exclusive. Instants have
+/// `start == end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeRange {
+    pub fn is_instant(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Wider by a quarter of the length on both sides, or for an instant by
+    /// a quarter of its distance from `now`.
+    fn around(self, now: DateTime<Utc>) -> Self {
+        let span = if self.is_instant() {
+            (now - self.start).abs()
+        } else {
+            self.end - self.start
+        };
+        let slack = (span / 4).max(Duration::minutes(MIN_SLACK_MINUTES));
+        Self {
+            start: self.start - slack,
+            end: self.end + slack,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Unit {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word {
+            "s" | "sec" | "secs" | "second" | "seconds" => Unit::Second,
+            "m" | "min" | "mins" | "minute" | "minutes" => Unit::Minute,
+            "h" | "hr" | "hrs" | "hour" | "hours" => Unit::Hour,
+            "d" | "day" | "days" => Unit::Day,
+            "w" | "wk" | "wks" | "week" | "weeks" => Unit::Week,
+            "mo" | "month" | "months" => Unit::Month,
+            "y" | "yr" | "yrs" | "year" | "years" => Unit::Year,
+            _ => return None,
+        })
+    }
+
+    /// `count` of these units before `time`.
+    fn before(self, time: NaiveDateTime, count: u32) -> Option<NaiveDateTime> {
+        let n = i64::from(count);
+        match self {
+            Unit::Second => time.checked_sub_signed(Duration::seconds(n)),
+            Unit::Minute => time.checked_sub_signed(Duration::minutes(n)),
+            Unit::Hour => time.checked_sub_signed(Duration::hours(n)),
+            Unit::Day => time.checked_sub_signed(Duration::days(n)),
+            Unit::Week => time.checked_sub_signed(Duration::weeks(n)),
+            Unit::Month => time.checked_sub_months(Months::new(count)),
+            Unit::Year => time.checked_sub_months(Months::new(count.checked_mul(12)?)),
+        }
+    }
+
+    /// The calendar period of this unit containing `date`, weeks starting
+    /// on Monday. Units shorter than a day are not calendar periods.
+    fn period(self, date: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        let start = match self {
+            Unit::Day => date,
+            Unit::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Unit::Month => date.with_day(1)?,
+            Unit::Year => date.with_day(1)?.with_month(1)?,
+            _ => return None,
+        };
+        let end = match self {
+            Unit::Day => start.succ_opt()?,
+            Unit::Week => start + Duration::weeks(1),
+            Unit::Month => start.checked_add_months(Months::new(1))?,
+            _ => start.checked_add_months(Months::new(12))?,
+        };
+        Some((start, end))
+    }
+}
+
+/// A count typed as digits or as a word.
+fn number(word: &str) -> Option<u32> {
+    match word {
+        "a" | "an" => Some(1),
+        _ => word.parse().ok().or_else(|| {
+            NUMBERS
+                .iter()
+                .position(|n| *n == word)
+                .map(|i| i as u32 + 1)
+        }),
+    }
+}
+
+/// `3d`, `10min` and the like.
+fn compact(word: &str) -> Option<(u32, Unit)> {
+    let split = word.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = word.split_at(split);
+    Some((count.parse().ok()?, Unit::parse(unit)?))
+}
+
+fn month(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|m| *m == word || (word.len() >= 3 && m.starts_with(word)))
+        .map(|i| i as u32 + 1)
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    word.parse().ok()
+}
+
+fn start_of(date: NaiveDate) -> NaiveDateTime {
+    date.and_time(NaiveTime::MIN)
+}
+
+/// Local wall-clock time to UTC. Times skipped by a DST change move to the
+/// first one that exists.
+fn to_utc(time: NaiveDateTime) -> Result<DateTime<Utc>> {
+    (0..=2)
+        .find_map(|hours| {
+            Local
+                .from_local_datetime(&(time + Duration::hours(hours)))
+                .earliest()
+        })
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| Error::Parse(format!("{} does not exist in the local time zone", time)))
+}
+
+fn instant(time: NaiveDateTime) -> Result<TimeRange> {
+    let time = to_utc(time)?;
+    Ok(TimeRange {
+        start: time,
+        end: time,
+    })
+}
+
+fn days(start: NaiveDate, end: NaiveDate) -> Result<TimeRange> {
+    Ok(TimeRange {
+        start: to_utc(start_of(start))?,
+        end: to_utc(start_of(end))?,
+    })
+}
+
+fn month_range(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    Unit::Month.period(NaiveDate::from_ymd_opt(year, month, 1)?)
+}
+
+/// Parses a time expression relative to `now`, see the module docs.
+pub fn parse_range_at(value: &str, now: DateTime<Local>) -> Result<TimeRange> {
+    let value = value.trim();
+    let invalid = || Error::Parse(format!("`{}` is not a time or date", value));
+
+    if let Some((from, to)) = value.split_once("..") {
+        let start = parse_range_at(from, now)?.start;
+        let end = if to.trim().is_empty() {
+            now.with_timezone(&Utc)
+        } else {
+            parse_range_at(to, now)?.end
+        };
+        if end < start {
+            return Err(Error::Parse(format!("`{}` ends before it starts", value)));
+        }
+        return Ok(TimeRange { start, end });
+    }
+
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        let t = t.with_timezone(&Utc);
+        return Ok(TimeRange { start: t, end: t });
+    }
+    if let Some(t) = DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    {
+        return instant(t);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return days(date, date.succ_opt().ok_or_else(invalid)?);
+    }
+    if let Some((year, month)) = value
+        .split_once('-')
+        .and_then(|(y, m)| Some((y.parse::<i32>().ok()?, m.parse::<u32>().ok()?)))
+    {
+        let (start, end) = month_range(year, month).ok_or_else(invalid)?;
+        return days(start, end);
+    }
+    if let (4, Ok(year)) = (value.len(), value.parse::<i32>()) {
+        let start = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(invalid)?;
+        let (start, end) = Unit::Year.period(start).ok_or_else(invalid)?;
+        return days(start, end);
+    }
+
+    let lower = value.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let naive_now = now.naive_local();
+    let today = naive_now.date();
+
+    match words.as_slice() {
+        ["around" | "about", rest @ ..] if !rest.is_empty() => {
+            Ok(parse_range_at(&rest.join(" "), now)?.around(now.with_timezone(&Utc)))
+        }
+        ["now"] => instant(naive_now),
+        ["today"] => days(today, today.succ_opt().ok_or_else(invalid)?),
+        ["yesterday"] => days(today.pred_opt().ok_or_else(invalid)?, today),
+
+        // 3d, 3d ago, 3 days, 3 days ago, an hour ago
+        [amount] | [amount, "ago"] if compact(amount).is_some() => {
+            let (count, unit) = compact(amount).ok_or_else(invalid)?;
+            instant(unit.before(naive_now, count).ok_or_else(invalid)?)
+        }
+        [count, unit] | [count, unit, "ago"]
+            if number(count).is_some() && Unit::parse(unit).is_some() =>
+        {
+            let (count, unit) = (number(count).unwrap(), Unit::parse(unit).unwrap());
+            instant(unit.before(naive_now, count).ok_or_else(invalid)?)
+        }
+
+        // past 3 days, last 2 weeks
+        ["past" | "last", count, unit] => {
+            let count = number(count).ok_or_else(invalid)?;
+            let unit = Unit::parse(unit).ok_or_else(invalid)?;
+            Ok(TimeRange {
+                start: to_utc(unit.before(naive_now, count).ok_or_else(invalid)?)?,
+                end: now.with_timezone(&Utc),
+            })
+        }
+        ["past", unit] => {
+            let unit = Unit::parse(unit).ok_or_else(invalid)?;
+            Ok(TimeRange {
+                start: to_utc(unit.before(naive_now, 1).ok_or_else(invalid)?)?,
+                end: now.with_timezone(&Utc),
+            })
+        }
+
+        // this week, last month
+        [which @ ("this" | "last" | "previous"), unit] if Unit::parse(unit).is_some() => {
+            let unit = Unit::parse(unit).unwrap();
+            let this = *which == "this";
+            let anchor = if this {
+                naive_now
+            } else {
+                unit.before(naive_now, 1).ok_or_else(invalid)?
+            };
+            match unit.period(anchor.date()) {
+                Some((start, end)) => days(start, end),
+                // the last hour is the past hour
+                None if !this => Ok(TimeRange {
+                    start: to_utc(anchor)?,
+                    end: now.with_timezone(&Utc),
+                }),
+                None => Err(invalid()),
+            }
+        }
+
+        // monday, last friday
+        [day] | ["last", day] if weekday(day).is_some() => {
+            let day = weekday(day).unwrap();
+            let back =
+                (7 + today.weekday().num_days_from_monday() - day.num_days_from_monday()) % 7;
+            let back = if back == 0 && words[0] == "last" {
+                7
+            } else {
+                back
+            };
+            let date = today - Duration::days(back as i64);
+            days(date, date.succ_opt().ok_or_else(invalid)?)
+        }
+
+        // march, last march, march 2025
+        [name] | ["last", name] if month(name).is_some() => {
+            let month = month(name).unwrap();
+            let current = today.month();
+            let year = if month > current || (month == current && words[0] == "last") {
+                today.year() - 1
+            } else {
+                today.year()
+            };
+            let (start, end) = month_range(year, month).ok_or_else(invalid)?;
+            days(start, end)
+        }
+        [name, year] if month(name).is_some() => {
+            let year = year.parse().map_err(|_| invalid())?;
+            let (start, end) = month_range(year, month(name).unwrap()).ok_or_else(invalid)?;
+            days(start, end)
+        }
+
+        _ => Err(invalid()),
+    }
+}
+
+pub fn parse_range(value: &str) -> Result<TimeRange> {
+    parse_range_at(value, Local::now())
+}
+
+/// Like [`parse_range`], but instants become the span around them, for
+/// filters that take a single expression (`--when 2 weeks ago`).
+pub fn parse_period(value: &str) -> Result<TimeRange> {
+    let range = parse_range(value)?;
+    if range.is_instant() {
+        return Ok(range.around(Utc::now()));
+    }
+    Ok(range)
+}
+
+/// The moment an expression starts at, e.g. the start of the day for a
+/// bare date.
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
+    Ok(parse_range(value)?.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Friday 14 March 2025, mid-afternoon.
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, 14, 15, 30, 0).unwrap()
+    }
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Local
+            .with_ymd_and_hms(y, mo, d, h, mi, 0)
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn day(y: i32, mo: u32, d: u32) -> DateTime<Utc> {
+        local(y, mo, d, 0, 0)
+    }
+
+    fn range(start: DateTime<Utc>, end: DateTime<Utc>) -> TimeRange {
+        TimeRange { start, end }
+    }
+
+    fn at(value: &str) -> TimeRange {
+        parse_range_at(value, now()).unwrap()
+    }
+
+    #[test]
+    fn calendar_periods() {
+        assert_eq!(at("2025"), range(day(2025, 1, 1), day(2026, 1, 1)));
+        assert_eq!(at("2025-03"), range(day(2025, 3, 1), day(2025, 4, 1)));
+        assert_eq!(at("2024-12"), range(day(2024, 12, 1), day(2025, 1, 1)));
+        assert_eq!(at("2024-02-29"), range(day(2024, 2, 29), day(2024, 3, 1)));
+        assert_eq!(at("today"), range(day(2025, 3, 14), day(2025, 3, 15)));
+        assert_eq!(at("Yesterday"), range(day(2025, 3, 13), day(2025, 3, 14)));
+        assert_eq!(at("this week"), range(day(2025, 3, 10), day(2025, 3, 17)));
+        assert_eq!(at("last week"), range(day(2025, 3, 3), day(2025, 3, 10)));
+        assert_eq!(at("last month"), range(day(2025, 2, 1), day(2025, 3, 1)));
+        assert_eq!(at("this year"), range(day(2025, 1, 1), day(2026, 1, 1)));
+    }
+
+    #[test]
+    fn named_days_and_months_look_back() {
+        assert_eq!(at("friday"), at("today"));
+        assert_eq!(at("last friday"), range(day(2025, 3, 7), day(2025, 3, 8)));
+        assert_eq!(at("mon"), range(day(2025, 3, 10), day(2025, 3, 11)));
+        assert_eq!(at("march"), range(day(2025, 3, 1), day(2025, 4, 1)));
+        assert_eq!(at("last march"), range(day(2024, 3, 1), day(2024, 4, 1)));
+        // a month still to come this year is last year's
+        assert_eq!(at("april"), range(day(2024, 4, 1), day(2024, 5, 1)));
+        assert_eq!(at("jan"), range(day(2025, 1, 1), day(2025, 2, 1)));
+        assert_eq!(at("sept 2023"), range(day(2023, 9, 1), day(2023, 10, 1)));
+    }
+
+    #[test]
+    fn instants() {
+        let now = now().with_timezone(&Utc);
+        assert_eq!(at("now"), range(now, now));
+        assert!(at("now").is_instant());
+        assert_eq!(at("3d").start, local(2025, 3, 11, 15, 30));
+        assert_eq!(at("3d ago"), at("3 days ago"));
+        assert_eq!(at("2 weeks ago").start, local(2025, 2, 28, 15, 30));
+        assert_eq!(at("an hour ago").start, now - Duration::hours(1));
+        assert_eq!(at("ten minutes").start, now - Duration::minutes(10));
+        assert_eq!(at("1 month ago").start, local(2025, 2, 14, 15, 30));
+        assert_eq!(at("2025-03-01 18:00").start, local(2025, 3, 1, 18, 0));
+        assert_eq!(
+            at("2025-03-01T18:00:00Z").start,
+            Utc.with_ymd_and_hms(2025, 3, 1, 18, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rolling_periods_end_now() {
+        let now = now().with_timezone(&Utc);
+        assert_eq!(at("past 3 days"), range(local(2025, 3, 11, 15, 30), now));
+        assert_eq!(at("last 2 weeks"), range(local(2025, 2, 28, 15, 30), now));
+        assert_eq!(at("past hour"), range(now - Duration::hours(1), now));
+        assert_eq!(at("last hour"), at("past hour"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            at("2025-03-01..2025-03-14"),
+            range(day(2025, 3, 1), day(2025, 3, 15))
+        );
+        assert_eq!(
+            at("2025-03.."),
+            range(day(2025, 3, 1), now().with_timezone(&Utc))
+        );
+        assert!(parse_range_at("..2025-03-02", now()).is_err());
+        assert!(parse_range_at("2025-03-14..2025-03-01", now()).is_err());
+    }
+
+    #[test]
+    fn around_widens() {
+        // a day by a quarter of it on each side
+        assert_eq!(
+            at("around 2025-03-10"),
+            range(local(2025, 3, 9, 18, 0), local(2025, 3, 11, 6, 0))
+        );
+        // an instant by a quarter of its distance from now, an hour at least
+        let four_days_ago = at("4 days ago").start;
+        assert_eq!(
+            at("about 4 days ago"),
+            range(
+                four_days_ago - Duration::days(1),
+                four_days_ago + Duration::days(1)
+            )
+        );
+        let now = now().with_timezone(&Utc);
+        assert_eq!(
+            at("around now"),
+            range(now - Duration::hours(1), now + Duration::hours(1))
+        );
+    }
+
+    #[test]
+    fn refuses_what_is_not_a_time() {
+        for value in [
+            "",
+            "soon",
+            "2025-13",
+            "2025-02-30",
+            "last 2 fortnights",
+            "past",
+            "around",
+            "this monday",
+            "this minute",
+            "march 20x5",
+            "99999999999 days ago",
+        ] {
+            assert!(parse_range_at(value, now()).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn periods_widen_instants_only() {
+        assert_eq!(
+            parse_period("2025-03").unwrap(),
+            parse_range("2025-03").unwrap()
+        );
+        let period = parse_period("2 weeks ago").unwrap();
+        let instant = parse_range("2 weeks ago").unwrap().start;
+        assert!(period.start < instant && instant < period.end);
+        assert!(period.end - period.start >= Duration::hours(2));
+        assert!(parse_period("whenever").is_err());
+    }
+
+    #[test]
+    fn every_local_time_has_an_instant_across_dst_changes() {
+        // in zones with daylight saving, one night a year skips an hour
+        // and another repeats one
+        let mut time = start_of(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        while time.year() == 2025 {
+            let utc = to_utc(time).unwrap();
+            match Local.from_local_datetime(&time).earliest() {
+                // a repeated time is the first of the two
+                Some(local) => assert_eq!(utc, local.with_timezone(&Utc), "{}", time),
+                // a skipped one moves forward by the gap
+                None => {
+                    let shifted = utc.with_timezone(&Local).naive_local();
+                    assert!(
+                        shifted > time && shifted - time <= Duration::hours(2),
+                        "{}",
+                        time
+                    );
+                }
+            }
+            time += Duration::minutes(30);
+        }
+    }
+
+    #[test]
+    fn days_last_23_to_25_hours() {
+        let mut date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        while date.year() == 2025 {
+            let day = days(date, date.succ_opt().unwrap()).unwrap();
+            let length = day.end - day.start;
+            assert!(
+                length >= Duration::hours(23) && length <= Duration::hours(25),
+                "{}",
+                date
+            );
+            date = date.succ_opt().unwrap();
+        }
+    }
+}
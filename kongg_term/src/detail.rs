@@ -0,0 +1,140 @@
+//! The detail pane: every field of the selected event, and the life of its
+//! file as `kongg_cli history` tells it.
+
+use chrono::{DateTime, Local, Utc};
+use kongg_shared::{
+    history::HistoryEntry,
+    models::file::FileResponse,
+    stream::is_streamed,
+    utils::size::{SizeUnits, format_size},
+};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Wide enough for the longest label.
+const LABEL_WIDTH: usize = 17;
+
+fn local_time(timestamp: &DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn field(label: &str, value: Option<String>) -> Line<'static> {
+    let value = match value {
+        Some(value) => Span::raw(value),
+        None => Span::styled("-", Style::default().fg(Color::DarkGray)),
+    };
+    Line::from(vec![
+        Span::styled(
+            format!("{:<width$}", label, width = LABEL_WIDTH),
+            Style::default().fg(Color::Magenta),
+        ),
+        value,
+    ])
+}
+
+fn heading(text: &str) -> Line<'static> {
+    Line::from(Span::styled(
+        text.to_string(),
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    ))
+}
+
+/// Every field of `file`, one per line.
+pub fn fields(file: &FileResponse, units: SizeUnits) -> Vec<Line<'static>> {
+    vec![
+        field("event", Some(file.event_type.to_string())),
+        field("path", Some(file.path.clone())),
+        field("previous path", file.previous_path.clone()),
+        field("file name", file.file_name.clone()),
+        field("extension", file.extension.clone()),
+        field(
+            "size",
+            file.file_size
+                .map(|s| format!("{} ({} bytes)", format_size(s, units), s)),
+        ),
+        field("hash", file.hash.clone()),
+        field("previous hash", file.previous_hash.clone()),
+        field("parent directory", file.parent_directory.clone()),
+        field("permissions", file.permissions.clone()),
+        field(
+            "read-only",
+            Some(if file.is_read_only { "yes" } else { "no" }.to_string()),
+        ),
+        field(
+            "time",
+            Some(format!(
+                "{} ({} UTC)",
+                local_time(&file.timestamp),
+                file.timestamp.format("%Y-%m-%d %H:%M:%S")
+            )),
+        ),
+        field("root", file.root.clone()),
+        field("host", file.host.clone()),
+        field("machine id", file.machine_id.clone()),
+        field("user", file.username.clone()),
+        field("origin", file.origin.clone()),
+        field("file id", file.file_id.clone()),
+        field(
+            "record",
+            Some(if is_streamed(file) {
+                "(live)".to_string()
+            } else {
+                file.id.to_string()
+            }),
+        ),
+    ]
+}
+
+/// The file's history, oldest first: when, what happened, the path it
+/// happened at and how the size and content changed.
+pub fn lineage(entries: &[HistoryEntry], units: SizeUnits) -> Vec<Line<'static>> {
+    let mut lines = vec![heading("History")];
+    if entries.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "nothing recorded",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for entry in entries {
+        let file = &entry.file;
+        let path = match &file.previous_path {
+            Some(previous) => format!("{} -> {}", previous, file.path),
+            None => file.path.clone(),
+        };
+        let mut spans = vec![
+            Span::styled(
+                local_time(&file.timestamp),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::raw(" "),
+            Span::styled(
+                format!("{:<11}", entry.step.to_string()),
+                Style::default().fg(Color::Cyan),
+            ),
+            Span::raw(" "),
+            Span::raw(path),
+        ];
+        if let Some(delta) = entry.size_delta.filter(|d| *d != 0) {
+            let sign = if delta < 0 { "-" } else { "+" };
+            spans.push(Span::styled(
+                format!(" {}{}", sign, format_size(delta.unsigned_abs(), units)),
+                Style::default().fg(if delta < 0 { Color::Red } else { Color::Green }),
+            ));
+        }
+        if entry.hash_changed {
+            spans.push(Span::styled(
+                " content changed",
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines
+}
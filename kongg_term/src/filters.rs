@@ -0,0 +1,258 @@
+//! The filter bar above the search box. Event types and categories are
+//! toggled from a list; extensions, sizes and periods are typed the way
+//! `kongg_cli` takes them (`--ext`, `--size`, `--when`).
+
+use kongg_shared::{
+    models::{event::EventType, query::FileQuery},
+    utils::{
+        media::MediaCategory,
+        size::{SizeRange, parse_size_range},
+        time::{TimeRange, parse_period},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Type,
+    Category,
+    Ext,
+    Size,
+    When,
+}
+
+impl Field {
+    pub const ALL: [Field; 5] = [
+        Field::Type,
+        Field::Category,
+        Field::Ext,
+        Field::Size,
+        Field::When,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Type => "type",
+            Field::Category => "category",
+            Field::Ext => "ext",
+            Field::Size => "size",
+            Field::When => "when",
+        }
+    }
+
+    /// The choices of a field picked from a list, none for typed ones.
+    pub fn options(self) -> Vec<String> {
+        match self {
+            Field::Type => EventType::ALL
+                .iter()
+                .map(|t| t.to_string().to_lowercase())
+                .collect(),
+            Field::Category => MediaCategory::ALL.iter().map(|c| c.to_string()).collect(),
+            Field::Ext | Field::Size | Field::When => Vec::new(),
+        }
+    }
+
+    /// What a typed field looks like, shown while it is empty.
+    pub fn example(self) -> &'static str {
+        match self {
+            Field::Ext => "pdf docx",
+            Field::Size => ">700MB, <=10k, 1GiB..4GiB",
+            Field::When => "yesterday, last week, 2025-03",
+            Field::Type | Field::Category => "",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterBar {
+    field: usize,
+    /// Choice under the cursor in the type and category lists.
+    pub option: usize,
+    /// What is being typed into the ext, size or when field.
+    pub input: String,
+    /// Why the last value typed was refused.
+    pub error: Option<String>,
+    pub types: Vec<EventType>,
+    pub categories: Vec<MediaCategory>,
+    pub extensions: Vec<String>,
+    /// The expression as typed, and what it came to.
+    pub size: Option<(String, SizeRange)>,
+    pub when: Option<(String, TimeRange)>,
+}
+
+impl FilterBar {
+    pub fn field(&self) -> Field {
+        Field::ALL[self.field]
+    }
+
+    pub fn next_field(&mut self) {
+        self.move_to((self.field + 1) % Field::ALL.len());
+    }
+
+    pub fn previous_field(&mut self) {
+        self.move_to((self.field + Field::ALL.len() - 1) % Field::ALL.len());
+    }
+
+    fn move_to(&mut self, field: usize) {
+        self.field = field;
+        self.option = 0;
+        self.reset_input();
+    }
+
+    /// Drops what was typed but not applied.
+    pub fn reset_input(&mut self) {
+        self.error = None;
+        self.input = match self.field() {
+            Field::Ext => self.extensions.join(" "),
+            Field::Size => self
+                .size
+                .as_ref()
+                .map(|(s, _)| s.clone())
+                .unwrap_or_default(),
+            Field::When => self
+                .when
+                .as_ref()
+                .map(|(s, _)| s.clone())
+                .unwrap_or_default(),
+            Field::Type | Field::Category => String::new(),
+        };
+    }
+
+    pub fn next_option(&mut self) {
+        let count = self.field().options().len();
+        if count > 0 {
+            self.option = (self.option + 1) % count;
+        }
+    }
+
+    pub fn previous_option(&mut self) {
+        let count = self.field().options().len();
+        if count > 0 {
+            self.option = (self.option + count - 1) % count;
+        }
+    }
+
+    /// Whether the choice at `index` of the current field is on.
+    pub fn is_on(&self, index: usize) -> bool {
+        match self.field() {
+            Field::Type => self.types.contains(&EventType::ALL[index]),
+            Field::Category => self.categories.contains(&MediaCategory::ALL[index]),
+            Field::Ext | Field::Size | Field::When => false,
+        }
+    }
+
+    /// Switches the choice under the cursor.
+    pub fn toggle(&mut self) {
+        match self.field() {
+            Field::Type => toggle(&mut self.types, EventType::ALL[self.option]),
+            Field::Category => toggle(&mut self.categories, MediaCategory::ALL[self.option]),
+            Field::Ext | Field::Size | Field::When => {}
+        }
+    }
+
+    /// Sets the typed field from the input, or keeps the old value and
+    /// says why in `error`. An empty input removes the filter.
+    pub fn apply(&mut self) -> bool {
+        let input = self.input.trim();
+        let applied = match self.field() {
+            Field::Ext => {
+                self.extensions = input
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .filter(|e| !e.is_empty())
+                    .collect();
+                Ok(())
+            }
+            Field::Size if input.is_empty() => {
+                self.size = None;
+                Ok(())
+            }
+            Field::Size => parse_size_range(input).map(|range| {
+                self.size = Some((input.to_string(), range));
+            }),
+            Field::When if input.is_empty() => {
+                self.when = None;
+                Ok(())
+            }
+            Field::When => parse_period(input).map(|range| {
+                self.when = Some((input.to_string(), range));
+            }),
+            Field::Type | Field::Category => return false,
+        };
+        match applied {
+            Ok(()) => {
+                self.reset_input();
+                true
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Removes the current field's filter.
+    pub fn clear(&mut self) {
+        match self.field() {
+            Field::Type => self.types.clear(),
+            Field::Category => self.categories.clear(),
+            Field::Ext => self.extensions.clear(),
+            Field::Size => self.size = None,
+            Field::When => self.when = None,
+        }
+        self.reset_input();
+    }
+
+    /// The filters in effect, one per value, e.g. `type deleted`,
+    /// `size >700MB`.
+    pub fn chips(&self) -> Vec<String> {
+        let mut chips = Vec::new();
+        for event_type in &self.types {
+            chips.push(format!("type {}", event_type.to_string().to_lowercase()));
+        }
+        for category in &self.categories {
+            chips.push(format!("category {}", category));
+        }
+        for extension in &self.extensions {
+            chips.push(format!("ext {}", extension));
+        }
+        if let Some((size, _)) = &self.size {
+            chips.push(format!("size {}", size));
+        }
+        if let Some((when, _)) = &self.when {
+            chips.push(format!("when {}", when));
+        }
+        chips
+    }
+
+    pub fn to_query(&self) -> FileQuery {
+        let mut query = FileQuery::new()
+            .event_types(self.types.iter().copied())
+            .extensions(self.extensions.iter().map(String::as_str))
+            .extensions(
+                self.categories
+                    .iter()
+                    .flat_map(|c| c.extensions().iter().copied()),
+            );
+        if let Some((_, size)) = self.size {
+            if let Some(min) = size.min {
+                query = query.min_size(min);
+            }
+            if let Some(max) = size.max {
+                query = query.max_size(max);
+            }
+        }
+        if let Some((_, when)) = self.when {
+            query = query.since(when.start).until(when.end);
+        }
+        query
+    }
+}
+
+fn toggle<T: PartialEq>(values: &mut Vec<T>, value: T) {
+    match values.iter().position(|v| *v == value) {
+        Some(i) => {
+            values.remove(i);
+        }
+        None => values.push(value),
+    }
+}
@@ -0,0 +1,92 @@
+//! New events as the daemon writes them, read from the socket
+//! `kongg_cli tail` follows. The daemon may start after the TUI or restart
+//! under it, so the connection is retried for as long as the TUI runs.
+
+use std::time::Duration;
+
+use kongg_shared::{models::file::FileResponse, stream::decode};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Wait between attempts to reach the daemon.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub enum Update {
+    Connected,
+    Disconnected,
+    Event(Box<FileResponse>),
+}
+
+/// Starts following the daemon in the background.
+pub fn subscribe() -> UnboundedReceiver<Update> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(follow(sender));
+    receiver
+}
+
+/// Forwards the stream until the receiver is dropped.
+async fn follow(sender: UnboundedSender<Update>) {
+    loop {
+        if let Ok(mut stream) = socket::connect().await {
+            if sender.send(Update::Connected).is_err() {
+                return;
+            }
+            while let Ok(Some(line)) = socket::next_line(&mut stream).await {
+                // a line that doesn't decode is skipped, not the end of the feed
+                let Ok(file) = decode(&line) else {
+                    continue;
+                };
+                if sender.send(Update::Event(Box::new(file))).is_err() {
+                    return;
+                }
+            }
+            if sender.send(Update::Disconnected).is_err() {
+                return;
+            }
+        }
+        if sender.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::io;
+
+    use kongg_shared::stream::socket_path;
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader, Lines},
+        net::UnixStream,
+    };
+
+    pub type Stream = Lines<BufReader<UnixStream>>;
+
+    pub async fn connect() -> io::Result<Stream> {
+        let path = socket_path().map_err(io::Error::other)?;
+        let socket = UnixStream::connect(&path).await?;
+        Ok(BufReader::new(socket).lines())
+    }
+
+    pub async fn next_line(stream: &mut Stream) -> io::Result<Option<String>> {
+        stream.next_line().await
+    }
+}
+
+#[cfg(not(unix))]
+mod socket {
+    use std::io;
+
+    pub struct Stream;
+
+    pub async fn connect() -> io::Result<Stream> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "following the daemon needs Unix sockets",
+        ))
+    }
+
+    pub async fn next_line(_: &mut Stream) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
@@ -1,10 +1,26 @@
-use std::io;
+mod detail;
+mod filters;
+mod live;
 
-use crossterm::event::{KeyCode, KeyModifiers};
+use std::{collections::VecDeque, io, ops::Range, sync::Arc, thread, time::Duration};
+
+use chrono::Utc;
+use clap::{CommandFactory, Parser};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
 use kongg_shared::{
-    extensions::format_file_size::FormateToString,
-    helpers::{crud::get_file, surreal_init::init},
-    models::file::FileResponse,
+    config::Config,
+    history::{HistoryEntry, history},
+    models::{
+        file::FileResponse,
+        query::{Cursor, FileQuery, Page},
+    },
+    search::{matched_spans, score, search, tokenize},
+    store::{Store, open_with_config},
+    utils::{
+        cli::{DocCommand, complete},
+        size::{SizeUnits, format_size},
+        time::parse_period,
+    },
 };
 use ratatui::{
     DefaultTerminal, Frame,
@@ -13,58 +29,351 @@ use ratatui::{
     style::{Color, Modifier, Style, Stylize as _},
     text::{Line, Span},
     widgets::{
-        self, Block, Borders, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState,
-        Table, TableState,
+        self, Block, Borders, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Table, TableState, Wrap,
     },
 };
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::{
+    filters::{Field, FilterBar},
+    live::{Update, subscribe},
+};
 
 const ITEM_HEIGHT: usize = 4;
+/// Rows fetched at a time; more are loaded when the selection reaches the end.
+const PAGE_SIZE: usize = 200;
+/// Most rows kept while the daemon's feed adds new ones on top; the oldest
+/// are dropped and loaded again when scrolled to.
+const MAX_ROWS: usize = 10_000;
+/// How often the screen is redrawn when nothing happens.
+const TICK_RATE: Duration = Duration::from_millis(250);
+/// Narrower than this, the detail pane goes below the rows instead of
+/// beside them.
+const DETAIL_SIDE_MIN_WIDTH: u16 = 120;
+
+/// Browse the file history recorded by the kongg daemon.
+#[derive(Debug, Parser)]
+#[command(name = "kongg_term", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<DocCommand>,
+}
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    let mut terminal = ratatui::init();
+    complete(Cli::command);
+    if let Some(command) = Cli::parse().command {
+        return command.run(Cli::command()).map_err(io::Error::other);
+    }
 
-    let db = init().await.unwrap();
-    let files = get_file(&db).await;
+    // Load before taking over the terminal so errors are printed normally
+    let config = Config::load().map_err(io::Error::other)?;
+    let store = open_with_config(&config).await.map_err(io::Error::other)?;
+    let query = FileQuery::new().limit(PAGE_SIZE);
+    let page = store.query(&query).await.map_err(io::Error::other)?;
 
-    let mut app = App {
-        exit: false,
-        scroll_state: ScrollbarState::new((files.len() - 1) * ITEM_HEIGHT),
-        items: files,
-        state: TableState::default().with_selected(0),
-        search_text: String::new(),
-        swipe_page: 0,
-    };
+    let mut terminal = ratatui::init();
 
-    let app_result = app.run(&mut terminal);
+    let mut app = App::new(store, query, page, config.display.size_units);
+    let app_result = app.run(&mut terminal).await;
 
     ratatui::restore();
     app_result
 }
 
+/// Where key presses go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    /// Characters are typed into the search box.
+    Search,
+    /// Keys move through the rows.
+    Table,
+    /// Keys edit the filter bar.
+    Filters,
+}
+
+impl Focus {
+    /// The next one in `Tab` order.
+    fn next(self) -> Self {
+        match self {
+            Focus::Search => Focus::Table,
+            Focus::Table => Focus::Filters,
+            Focus::Filters => Focus::Search,
+        }
+    }
+
+    fn previous(self) -> Self {
+        match self {
+            Focus::Search => Focus::Filters,
+            Focus::Table => Focus::Search,
+            Focus::Filters => Focus::Table,
+        }
+    }
+}
+
 pub struct App {
     state: TableState,
     exit: bool,
-    items: Vec<FileResponse>,
+    /// Rows loaded from the store.
+    items: VecDeque<FileResponse>,
+    /// Indexes of the `items` the search box lets through, in order.
+    visible: Vec<usize>,
     scroll_state: ScrollbarState,
+    focus: Focus,
+    filter_bar: FilterBar,
     search_text: String,
     swipe_page: i8,
+    store: Arc<dyn Store>,
+    query: FileQuery,
+    next_page: Option<Cursor>,
+    wants_more: bool,
+    wants_search: bool,
+    /// Whether the detail pane is open.
+    detail: bool,
+    detail_scroll: u16,
+    /// History of the file at `history_of`.
+    history: Vec<HistoryEntry>,
+    history_of: Option<String>,
+    /// Whether the daemon's feed is connected.
+    live: bool,
+    /// Events added above the rows in view since the table was last
+    /// scrolled to the top.
+    unseen: usize,
+    size_units: SizeUnits,
 }
 
 impl App {
-    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+    /// Shows the first `page` of `query`, newest first, with the search box
+    /// focused.
+    fn new(store: Arc<dyn Store>, query: FileQuery, page: Page, size_units: SizeUnits) -> Self {
+        Self {
+            exit: false,
+            scroll_state: ScrollbarState::new(page.items.len().saturating_sub(1) * ITEM_HEIGHT),
+            visible: (0..page.items.len()).collect(),
+            items: page.items.into(),
+            state: TableState::default().with_selected(0),
+            focus: Focus::Search,
+            filter_bar: FilterBar::default(),
+            search_text: String::new(),
+            swipe_page: 0,
+            store,
+            query,
+            next_page: page.next,
+            wants_more: false,
+            wants_search: false,
+            detail: false,
+            detail_scroll: 0,
+            history: Vec::new(),
+            history_of: None,
+            live: false,
+            unseen: 0,
+            size_units,
+        }
+    }
+
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut terminal_events = read_terminal();
+        let mut updates = subscribe();
+        let mut tick = tokio::time::interval(TICK_RATE);
+
         while !self.exit {
-            match crossterm::event::read()? {
-                crossterm::event::Event::Key(key_event) => self.handle_key_event(key_event)?,
-                _ => {}
-            }
             terminal.draw(|frame| self.draw(frame))?;
-            // self.handle_events()?;
+            tokio::select! {
+                Some(event) = terminal_events.recv() => {
+                    if let Event::Key(key_event) = event? {
+                        self.handle_key_event(key_event)?;
+                    }
+                }
+                Some(update) = updates.recv() => self.receive(update),
+                _ = tick.tick() => {}
+            }
+            if self.wants_search {
+                self.search().await?;
+            }
+            if self.wants_more {
+                self.load_more().await?;
+            }
+            if self.detail {
+                self.load_history().await?;
+            }
         }
 
         Ok(())
     }
 
+    async fn load_more(&mut self) -> io::Result<()> {
+        self.wants_more = false;
+        let Some(cursor) = self.next_page.take() else {
+            return Ok(());
+        };
+
+        let (_, query) = self.search_query();
+        let query = query.after(cursor);
+        let page = self.store.query(&query).await.map_err(io::Error::other)?;
+        self.items.extend(page.items);
+        self.next_page = page.next;
+        self.filter_rows();
+        Ok(())
+    }
+
+    /// Takes in what the daemon's feed brings. New events go on top of the
+    /// rows when the filters and the search box let them through, and the
+    /// selected row stays selected.
+    fn receive(&mut self, update: Update) {
+        let file = match update {
+            Update::Connected => {
+                self.live = true;
+                return;
+            }
+            Update::Disconnected => {
+                self.live = false;
+                return;
+            }
+            Update::Event(file) => *file,
+        };
+        let (_, query) = self.search_query();
+        if !query.matches(&file) {
+            return;
+        }
+        // the file's history has one more step
+        if self.history_of.as_deref() == Some(file.path.as_str())
+            || (self.history_of.is_some() && self.history_of == file.previous_path)
+        {
+            self.history_of = None;
+        }
+
+        // `filter_rows` finds the selected row again by its index in `items`
+        for index in &mut self.visible {
+            *index += 1;
+        }
+        self.items.push_front(file);
+        if self.items.len() > MAX_ROWS {
+            self.items.truncate(MAX_ROWS);
+            self.visible.retain(|&index| index < MAX_ROWS);
+            // the next page starts again after the rows still loaded
+            let (words, query) = self.search_query();
+            if words.is_empty() {
+                self.next_page = self
+                    .items
+                    .back()
+                    .map(|last| Cursor::after(query.sort, last));
+            }
+        }
+        self.filter_rows();
+
+        if self.visible.first() == Some(&0) && self.state.offset() > 0 {
+            // scrolled down: keep the rows in view where they are
+            *self.state.offset_mut() += 1;
+            self.unseen += 1;
+        }
+    }
+
+    /// Selects the newest row.
+    fn first_row(&mut self) {
+        self.state.select(Some(0));
+        *self.state.offset_mut() = 0;
+        self.scroll_state = self.scroll_state.position(0);
+    }
+
+    /// Loads the history of the selected file for the detail pane, unless
+    /// it is already there.
+    async fn load_history(&mut self) -> io::Result<()> {
+        let Some(path) = self.selected_item().map(|file| file.path.clone()) else {
+            return Ok(());
+        };
+        if self.history_of.as_deref() == Some(path.as_str()) {
+            return Ok(());
+        }
+        self.history = history(self.store.as_ref(), &path)
+            .await
+            .map_err(io::Error::other)?;
+        self.history_of = Some(path);
+        self.detail_scroll = 0;
+        Ok(())
+    }
+
+    fn selected_item(&self) -> Option<&FileResponse> {
+        let index = self.visible.get(self.state.selected()?)?;
+        self.items.get(*index)
+    }
+
+    /// The words of the search box and the query narrowed to the time
+    /// after its `@`, e.g. `report @ last week`. A time that doesn't parse
+    /// yet (still being typed) is left out.
+    fn search_query(&self) -> (&str, FileQuery) {
+        let (words, when) = match self.search_text.split_once('@') {
+            Some((words, when)) => (words, parse_period(when).ok()),
+            None => (self.search_text.as_str(), None),
+        };
+        let mut query = self.query.clone();
+        // within the filter bar's period, when it has one
+        if let Some(when) = when {
+            query.since = Some(query.since.map_or(when.start, |s| s.max(when.start)));
+            query.until = Some(query.until.map_or(when.end, |u| u.min(when.end)));
+        }
+        (words.trim(), query)
+    }
+
+    /// Reloads the rows with the filter bar's filters.
+    fn apply_filters(&mut self) {
+        self.query = self.filter_bar.to_query().limit(PAGE_SIZE);
+        self.wants_search = true;
+    }
+
+    /// Replaces the rows with the best matches for the search box, or with
+    /// the whole log again when it is empty.
+    async fn search(&mut self) -> io::Result<()> {
+        self.wants_search = false;
+
+        let (words, query) = self.search_query();
+        if words.is_empty() {
+            let page = self.store.query(&query).await.map_err(io::Error::other)?;
+            self.items = page.items.into();
+            self.next_page = page.next;
+        } else {
+            let hits = search(self.store.as_ref(), words, &query)
+                .await
+                .map_err(io::Error::other)?;
+            self.items = hits.into_iter().map(|hit| hit.file).collect();
+            self.next_page = None;
+        }
+
+        // the rows are new, nothing to keep selected
+        self.state.select(None);
+        self.filter_rows();
+        Ok(())
+    }
+
+    /// Narrows the loaded rows to those the search box matches, by the
+    /// same fuzzy measure as the store search, keeping the selected row
+    /// selected when it still shows.
+    fn filter_rows(&mut self) {
+        let selected = self
+            .state
+            .selected()
+            .and_then(|i| self.visible.get(i).copied());
+
+        let (words, query) = self.search_query();
+        let words = tokenize(words);
+        let now = Utc::now();
+        self.visible = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| query.matches(file))
+            .filter(|(_, file)| words.is_empty() || score(&words, file, now).is_some())
+            .map(|(i, _)| i)
+            .collect();
+
+        let position = selected
+            .and_then(|item| self.visible.iter().position(|&i| i == item))
+            .unwrap_or(0);
+        self.state.select(Some(position));
+        self.scroll_state = ScrollbarState::new(self.visible.len().saturating_sub(1) * ITEM_HEIGHT)
+            .position(position * ITEM_HEIGHT);
+    }
+
     fn increment_swipe_page(&mut self) {
         if self.swipe_page == 2 {
             self.swipe_page = 0;
@@ -83,7 +392,7 @@ impl App {
     pub fn next_row(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
-                if i >= self.items.len() - 1 {
+                if i >= self.visible.len().saturating_sub(1) {
                     0
                 } else {
                     i + 1
@@ -91,6 +400,9 @@ impl App {
             }
             None => 0,
         };
+        if self.next_page.is_some() && i + 1 >= self.visible.len() {
+            self.wants_more = true;
+        }
         self.state.select(Some(i));
         self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
     }
@@ -99,7 +411,7 @@ impl App {
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.items.len() - 1
+                    self.visible.len().saturating_sub(1)
                 } else {
                     i - 1
                 }
@@ -115,89 +427,178 @@ impl App {
         let layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(vec![
-                Constraint::Percentage(10),
-                Constraint::Percentage(5),
-                Constraint::Percentage(80),
-                Constraint::Percentage(5),
+                Constraint::Length(2),
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
             ])
             .split(frame.area());
 
         self.render_filter_field(frame, layout[0]);
         self.render_input_search_field(frame, layout[1]);
-        self.render_table(frame, layout[2]);
-        self.render_scrollbar(frame, layout[2]);
+        let mut table_area = layout[2];
+        if self.detail {
+            // beside the rows when there is room, below them otherwise
+            let direction = if table_area.width >= DETAIL_SIDE_MIN_WIDTH {
+                Direction::Horizontal
+            } else {
+                Direction::Vertical
+            };
+            let [rows, detail] = Layout::default()
+                .direction(direction)
+                .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(table_area);
+            self.render_detail(frame, detail);
+            table_area = rows;
+        }
+        self.render_table(frame, table_area);
+        self.render_scrollbar(frame, table_area);
+        if self.state.offset() == 0 {
+            self.unseen = 0;
+        }
         self.render_title(frame, layout[3]);
     }
 
     fn handle_key_event(&mut self, key_event: crossterm::event::KeyEvent) -> io::Result<()> {
-        let ke_kind = key_event.kind;
+        if key_event.kind != KeyEventKind::Press {
+            return Ok(());
+        }
         let ke_code = key_event.code;
         let ke_mod = key_event.modifiers;
 
-        // if let KeyCode::Char(c) = ke_code {}
+        match (self.focus, ke_mod, ke_code) {
+            (_, KeyModifiers::CONTROL, KeyCode::Char('q')) => self.exit = true,
+            (_, _, KeyCode::Tab) => self.focus = self.focus.next(),
+            (_, _, KeyCode::BackTab) => self.focus = self.focus.previous(),
+            (_, KeyModifiers::NONE, KeyCode::Home) => self.first_row(),
+
+            (Focus::Filters, _, KeyCode::Esc) => {
+                self.filter_bar.reset_input();
+                self.focus = Focus::Table;
+            }
+            (Focus::Filters, KeyModifiers::NONE, KeyCode::Left) => self.filter_bar.previous_field(),
+            (Focus::Filters, KeyModifiers::NONE, KeyCode::Right) => self.filter_bar.next_field(),
+            (Focus::Filters, _, KeyCode::Delete) => {
+                self.filter_bar.clear();
+                self.apply_filters();
+            }
+            (Focus::Filters, _, _) => self.handle_filter_key(ke_mod, ke_code),
+            (_, KeyModifiers::NONE, KeyCode::Down) => self.next_row(),
+            (_, KeyModifiers::NONE, KeyCode::Up) => self.previous_row(),
 
-        match (ke_kind, ke_mod, ke_code) {
-            (_k, KeyModifiers::NONE, KeyCode::Char(char)) => {
+            (Focus::Search, KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(char)) => {
                 self.search_text.push(char);
+                self.filter_rows();
             }
-            (_k, KeyModifiers::NONE, KeyCode::Backspace) => {
+            (Focus::Search, _, KeyCode::Backspace) => {
                 self.search_text.pop();
+                self.filter_rows();
+            }
+            // the loaded rows are already filtered, Enter looks through
+            // the whole history
+            (Focus::Search, _, KeyCode::Enter) => {
+                self.wants_search = true;
+                self.focus = Focus::Table;
+            }
+            (Focus::Search, _, KeyCode::Esc) => self.focus = Focus::Table,
+
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('/')) => self.focus = Focus::Search,
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('f')) => self.focus = Focus::Filters,
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('d')) => self.detail = !self.detail,
+            (Focus::Table, _, KeyCode::PageDown) if self.detail => {
+                self.detail_scroll = self.detail_scroll.saturating_add(5)
             }
-            (_k, KeyModifiers::CONTROL, KeyCode::Char('q')) => self.exit = true,
-            //(_k, KeyModifiers::CONTROL, KeyCode::Char('j') | KeyCode::Down) => {
-            (_k, KeyModifiers::NONE, KeyCode::Down) => {
-                self.next_row();
+            (Focus::Table, _, KeyCode::PageUp) if self.detail => {
+                self.detail_scroll = self.detail_scroll.saturating_sub(5)
             }
-            // (_k, KeyModifiers::CONTROL, KeyCode::Char('k') | KeyCode::Up) => {
-            (_k, KeyModifiers::NONE, KeyCode::Up) => {
-                self.previous_row();
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('q')) => self.exit = true,
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('j')) => self.next_row(),
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Char('k')) => self.previous_row(),
+            (Focus::Table, _, KeyCode::Esc) if !self.search_text.is_empty() => {
+                self.search_text.clear();
+                self.wants_search = true;
             }
-            (_k, KeyModifiers::NONE, KeyCode::Left) => self.decrement_swipe_page(),
-            (_k, KeyModifiers::NONE, KeyCode::Right) => self.increment_swipe_page(),
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Left) => self.decrement_swipe_page(),
+            (Focus::Table, KeyModifiers::NONE, KeyCode::Right) => self.increment_swipe_page(),
             _ => {}
         }
 
         Ok(())
     }
 
-    fn handle_events(&mut self) -> io::Result<()> {
-        todo!()
+    /// Keys for the filter bar's current field: a list of choices takes
+    /// `↑`/`↓` and `Space`, a typed one takes text and `Enter`.
+    fn handle_filter_key(&mut self, ke_mod: KeyModifiers, ke_code: KeyCode) {
+        let picked = !self.filter_bar.field().options().is_empty();
+        match (picked, ke_mod, ke_code) {
+            (true, KeyModifiers::NONE, KeyCode::Down | KeyCode::Char('j')) => {
+                self.filter_bar.next_option()
+            }
+            (true, KeyModifiers::NONE, KeyCode::Up | KeyCode::Char('k')) => {
+                self.filter_bar.previous_option()
+            }
+            (true, KeyModifiers::NONE, KeyCode::Char(' ') | KeyCode::Enter) => {
+                self.filter_bar.toggle();
+                self.apply_filters();
+            }
+            (false, KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(char)) => {
+                self.filter_bar.input.push(char);
+                self.filter_bar.error = None;
+            }
+            (false, _, KeyCode::Backspace) => {
+                self.filter_bar.input.pop();
+                self.filter_bar.error = None;
+            }
+            (false, _, KeyCode::Enter) => {
+                let applied = self.filter_bar.apply();
+                if applied {
+                    self.apply_filters();
+                }
+            }
+            _ => {}
+        }
     }
 
     fn render_table(&mut self, frame: &mut Frame, area: Rect) {
         // let mut table_state = TableState::default();
 
-        let rows = self.items.iter().enumerate().map(|(i, data)| {
-            let item = data;
-            let num = (i + 1).to_string();
+        let (words, _) = self.search_query();
+        let words = tokenize(words);
+        let highlight = |text: &str| Cell::from(highlighted(text, &words));
+
+        let rows = self.visible.iter().enumerate().map(|(i, &index)| {
+            let item = &self.items[index];
+            let num = Cell::from((i + 1).to_string());
+            let event_type = Cell::from(item.event_type.to_string());
 
             let cells = match self.swipe_page {
                 0 => vec![
                     num,
-                    item.event_type.to_string(),
-                    item.path.clone(),
-                    item.timestamp.format("%Y-%m-%d [%H:%M]").to_string(),
+                    event_type,
+                    highlight(&item.path),
+                    Cell::from(item.timestamp.format("%Y-%m-%d [%H:%M]").to_string()),
                 ],
                 1 => vec![
                     num,
-                    item.event_type.to_string(),
-                    item.file_name.clone().unwrap_or("_".to_string()),
-                    item.extension.clone().unwrap_or("_".to_string()),
+                    event_type,
+                    highlight(item.file_name.as_deref().unwrap_or("_")),
+                    Cell::from(item.extension.clone().unwrap_or("_".to_string())),
                 ],
                 2 => vec![
                     num,
-                    item.event_type.to_string(),
-                    item.parent_directory.clone().unwrap_or("_".to_string()),
-                    item.file_size
-                        .unwrap_or_default()
-                        .formate_file_size()
-                        .unwrap_or("_".to_string()),
+                    event_type,
+                    highlight(item.parent_directory.as_deref().unwrap_or("_")),
+                    Cell::from(
+                        item.file_size
+                            .map(|size| format_size(size, self.size_units))
+                            .unwrap_or("_".to_string()),
+                    ),
                 ],
                 _ => vec![
-                    "S/N".to_string(),
-                    "_".to_string(),
-                    "_".to_string(),
-                    "_".to_string(),
+                    Cell::from("S/N"),
+                    Cell::from("_"),
+                    Cell::from("_"),
+                    Cell::from("_"),
                 ],
             };
             Row::new(cells)
@@ -223,6 +624,7 @@ impl App {
         };
         let widget = Table::new(rows, widths)
             .style(Style::new().light_yellow())
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
             .header(
                 Row::new(match self.swipe_page {
                     0 => vec!["S/N", "Event Type", "path", "time & date"],
@@ -236,6 +638,8 @@ impl App {
             );
         frame.render_stateful_widget(widget, area, &mut self.state);
     }
+    /// The title, whether the daemon's feed is connected and how many new
+    /// events are above the rows in view.
     fn render_title(&self, frame: &mut Frame, area: Rect) {
         // let widget = Line::from("Kongg on the Terminal")
         //     .bold()
@@ -252,26 +656,167 @@ impl App {
         .centered();
 
         frame.render_widget(widget, area);
-    }
-    fn render_input_search_field(&self, frame: &mut Frame, area: Rect) {
-        // let widget = Line::from("Kongg on the Terminal")
-        //     .bold()
-        //     .centered()
-        //     .yellow();
 
-        let area = frame.area();
+        let status = if self.live {
+            Span::styled(" ● live", Style::default().fg(Color::Green))
+        } else {
+            Span::styled(" ○ offline", Style::default().fg(Color::DarkGray))
+        };
+        frame.render_widget(Line::from(status), area);
 
-        let block = Block::default().title("INput").borders(Borders::all());
+        if self.unseen > 0 {
+            let noun = if self.unseen == 1 { "event" } else { "events" };
+            let notice = Line::from(Span::styled(
+                format!("▲ {} new {} (Home) ", self.unseen, noun),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ))
+            .right_aligned();
+            frame.render_widget(notice, area);
+        }
+    }
+    fn render_input_search_field(&self, frame: &mut Frame, area: Rect) {
+        let focused = self.focus == Focus::Search;
+        let count = format!(
+            " {} of {}{} ",
+            self.visible.len(),
+            self.items.len(),
+            if self.next_page.is_some() { "+" } else { "" }
+        );
+        let block = Block::default()
+            .title("Search (words @ time)")
+            .title(Line::from(count).right_aligned())
+            .borders(Borders::all())
+            .border_style(if focused {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            });
         let paragraph = Paragraph::new(self.search_text.as_str())
             .style(Style::default().fg(Color::Yellow))
             .block(block);
 
         frame.render_widget(paragraph, area);
+        if focused {
+            let typed = self.search_text.chars().count() as u16;
+            frame.set_cursor_position((
+                (area.x + 1 + typed).min(area.right().saturating_sub(2)),
+                area.y + 1,
+            ));
+        }
     }
+    /// The fields, the current one's choices or input while focused, and
+    /// below them the filters in effect as chips.
     fn render_filter_field(&self, frame: &mut Frame, area: Rect) {
-        let widget = Line::from("Filter Stuff").bold().centered().yellow();
+        let focused = self.focus == Focus::Filters;
+        let bar = &self.filter_bar;
+        let [fields_area, chips_area] = Layout::vertical([Constraint::Length(1); 2]).areas(area);
 
-        frame.render_widget(widget, area);
+        let mut spans = vec![Span::raw(" Filters ").bold()];
+        for field in Field::ALL {
+            let style = if focused && field == bar.field() {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            spans.push(Span::styled(format!(" {} ", field.name()), style));
+        }
+        spans.push(Span::raw(" │ "));
+
+        let mut cursor = None;
+        if !focused {
+            spans.push(Span::styled(
+                "f to filter",
+                Style::default().fg(Color::DarkGray),
+            ));
+        } else if bar.field().options().is_empty() {
+            let typed_at = spans.iter().map(|s| s.width()).sum::<usize>() as u16;
+            cursor = Some(typed_at + bar.input.chars().count() as u16);
+            if bar.input.is_empty() {
+                spans.push(Span::styled(
+                    bar.field().example(),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            } else {
+                spans.push(Span::styled(
+                    bar.input.clone(),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            if let Some(error) = &bar.error {
+                spans.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+        } else {
+            for (i, option) in bar.field().options().into_iter().enumerate() {
+                let mut style = if bar.is_on(i) {
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                if i == bar.option {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                let mark = if bar.is_on(i) { "✓" } else { " " };
+                spans.push(Span::styled(format!("{}{} ", mark, option), style));
+            }
+        }
+        frame.render_widget(Line::from(spans), fields_area);
+        if let Some(x) = cursor {
+            frame.set_cursor_position((
+                (fields_area.x + x).min(fields_area.right().saturating_sub(1)),
+                fields_area.y,
+            ));
+        }
+
+        let chips = bar.chips();
+        let line = if chips.is_empty() {
+            Line::from(Span::styled(
+                " no filters",
+                Style::default().fg(Color::DarkGray),
+            ))
+        } else {
+            let mut spans = vec![Span::raw(" ")];
+            for chip in chips {
+                spans.push(Span::styled(
+                    format!(" {} ", chip),
+                    Style::default().fg(Color::Black).bg(Color::Cyan),
+                ));
+                spans.push(Span::raw(" "));
+            }
+            Line::from(spans)
+        };
+        frame.render_widget(line, chips_area);
+    }
+
+    /// Every field of the selected event, then its file's history.
+    fn render_detail(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .title("Details (d)")
+            .borders(Borders::all())
+            .border_style(Style::default().fg(Color::Magenta));
+        let lines = match self.selected_item() {
+            Some(file) => {
+                let mut lines = detail::fields(file, self.size_units);
+                lines.push(Line::default());
+                if self.history_of.as_deref() == Some(file.path.as_str()) {
+                    lines.extend(detail::lineage(&self.history, self.size_units));
+                }
+                lines
+            }
+            None => vec![Line::from("nothing selected").dark_gray()],
+        };
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((self.detail_scroll, 0));
+
+        frame.render_widget(paragraph, area);
     }
 
     fn render_scrollbar(&mut self, frame: &mut Frame, area: Rect) {
@@ -289,6 +834,161 @@ impl App {
     }
 }
 
+/// Terminal events, read on a thread of their own since crossterm blocks
+/// while it waits for one.
+fn read_terminal() -> UnboundedReceiver<io::Result<Event>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        loop {
+            let event = crossterm::event::read();
+            let failed = event.is_err();
+            if sender.send(event).is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// `text` with the words the search box matches picked out.
+fn highlighted(text: &str, words: &[String]) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut shown = 0;
+    for Range { start, end } in matched_spans(words, text) {
+        spans.push(Span::raw(text[shown..start].to_string()));
+        spans.push(Span::styled(
+            text[start..end].to_string(),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ));
+        shown = end;
+    }
+    spans.push(Span::raw(text[shown..].to_string()));
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use kongg_shared::{
+        models::{event::EventType, file::File},
+        store::MemoryStore,
+        stream::{decode, encode},
+    };
+
+    use super::*;
+
+    /// An event at `path` as the daemon's feed brings it.
+    fn streamed(path: &str) -> FileResponse {
+        decode(&encode(&File::missing(EventType::Created, path.to_string())).unwrap()).unwrap()
+    }
+
+    fn app(paths: &[&str]) -> App {
+        let page = Page {
+            items: paths.iter().map(|path| streamed(path)).collect(),
+            next: None,
+        };
+        App::new(
+            Arc::new(MemoryStore::default()),
+            FileQuery::new().limit(PAGE_SIZE),
+            page,
+            SizeUnits::default(),
+        )
+    }
+
+    fn receive(app: &mut App, path: &str) {
+        app.receive(Update::Event(Box::new(streamed(path))));
+    }
+
+    /// Paths of the rows that show, top to bottom.
+    fn shown(app: &App) -> Vec<&str> {
+        app.visible
+            .iter()
+            .map(|&i| app.items[i].path.as_str())
+            .collect()
+    }
+
+    fn selected(app: &App) -> &str {
+        &app.selected_item().unwrap().path
+    }
+
+    #[test]
+    fn new_rows_go_on_top_and_the_selection_stays() {
+        let mut app = app(&["/w/c.txt", "/w/b.txt", "/w/a.txt"]);
+        app.state.select(Some(1));
+
+        receive(&mut app, "/w/d.txt");
+        assert_eq!(app.visible, [0, 1, 2, 3]);
+        assert_eq!(
+            shown(&app),
+            ["/w/d.txt", "/w/c.txt", "/w/b.txt", "/w/a.txt"]
+        );
+        assert_eq!(selected(&app), "/w/b.txt");
+        assert_eq!(app.unseen, 0);
+    }
+
+    #[test]
+    fn rows_the_search_hides_keep_their_place() {
+        let mut app = app(&["/w/report-3.odt", "/w/notes.txt", "/w/report-1.odt"]);
+        app.search_text = "report".to_string();
+        app.filter_rows();
+        app.state.select(Some(1));
+        assert_eq!(app.visible, [0, 2]);
+
+        receive(&mut app, "/w/todo.txt");
+        receive(&mut app, "/w/report-4.odt");
+        assert_eq!(app.visible, [0, 2, 4]);
+        assert_eq!(
+            shown(&app),
+            ["/w/report-4.odt", "/w/report-3.odt", "/w/report-1.odt"]
+        );
+        assert_eq!(selected(&app), "/w/report-1.odt");
+    }
+
+    #[test]
+    fn scrolled_down_the_rows_in_view_stay_put() {
+        let paths: Vec<String> = (0..20).map(|i| format!("/w/{}.txt", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let mut app = app(&paths);
+        app.state.select(Some(10));
+        *app.state.offset_mut() = 8;
+
+        receive(&mut app, "/w/new.txt");
+        assert_eq!(app.state.offset(), 9);
+        assert_eq!(app.unseen, 1);
+        assert_eq!(selected(&app), "/w/10.txt");
+    }
+
+    #[test]
+    fn the_oldest_rows_go_past_max_rows() {
+        let paths: Vec<String> = (0..MAX_ROWS).map(|i| format!("/w/{}.txt", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let mut app = app(&paths);
+        app.state.select(Some(MAX_ROWS - 2));
+
+        receive(&mut app, "/w/new.txt");
+        assert_eq!(app.items.len(), MAX_ROWS);
+        assert_eq!(app.visible, (0..MAX_ROWS).collect::<Vec<_>>());
+        assert_eq!(app.items[0].path, "/w/new.txt");
+        assert_eq!(
+            app.items.back().unwrap().path,
+            format!("/w/{}.txt", MAX_ROWS - 2)
+        );
+        assert_eq!(selected(&app), format!("/w/{}.txt", MAX_ROWS - 2));
+        // the next page starts after the last row still loaded
+        assert_eq!(
+            app.next_page,
+            Some(Cursor::after(app.query.sort, app.items.back().unwrap()))
+        );
+
+        // a selected row that is dropped hands the selection to the top
+        app.state.select(Some(MAX_ROWS - 1));
+        receive(&mut app, "/w/newer.txt");
+        assert_eq!(app.state.selected(), Some(0));
+        assert_eq!(selected(&app), "/w/newer.txt");
+    }
+}
+
 // impl Widget for &App {
 //     fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer)
 //     where